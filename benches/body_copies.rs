@@ -0,0 +1,145 @@
+//! Counts the allocations made when collecting a 1 MiB body, comparing the copies the runtime and
+//! `Mtls` used to make with how they collect bodies now.
+//!
+//! Run with `cargo bench --bench body_copies`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use bytes::{Buf, Bytes};
+use http_body::{Body, Frame};
+use http_body_util::BodyExt;
+
+const BODY_SIZE: usize = 1024 * 1024;
+const ITERATIONS: usize = 100;
+
+// Counts every allocation and the bytes allocated.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// A body of `chunks`, one frame each, like a body received from the network.
+struct Chunks(Vec<Bytes>);
+
+impl Body for Chunks {
+    type Data = Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let chunks = &mut self.get_mut().0;
+        if chunks.is_empty() {
+            return Poll::Ready(None);
+        }
+        Poll::Ready(Some(Ok(Frame::data(chunks.remove(0)))))
+    }
+}
+
+fn body(frames: usize) -> Chunks {
+    let chunk = BODY_SIZE / frames;
+    Chunks(
+        (0..frames)
+            .map(|_| Bytes::from(vec![b'a'; chunk]))
+            .collect(),
+    )
+}
+
+// The request path before: aggregate the frames and copy them into one buffer.
+async fn aggregate_and_copy(body: Chunks) -> Bytes {
+    let mut buf = body.collect().await.unwrap().aggregate();
+    buf.copy_to_bytes(buf.remaining())
+}
+
+// The request path now.
+async fn to_bytes(body: Chunks) -> Bytes {
+    body.collect().await.unwrap().to_bytes()
+}
+
+// The response path before, which copied the collected bytes into the `Vec` handed to callers.
+async fn copy_to_vec(body: Chunks) -> Vec<u8> {
+    aggregate_and_copy(body).await.to_vec()
+}
+
+// The response path now, which hands on the buffer of a single unshared frame.
+async fn reuse_vec(body: Chunks) -> Vec<u8> {
+    Vec::from(to_bytes(body).await)
+}
+
+// Run `collect` on freshly built bodies of `frames` frames, counting only the allocations it
+// makes itself.
+fn measure<F, T>(name: &str, frames: usize, collect: impl Fn(Chunks) -> F) -> usize
+where
+    F: std::future::Future<Output = T>,
+{
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let (mut allocations, mut allocated) = (0, 0);
+    let started_at = Instant::now();
+    for _ in 0..ITERATIONS {
+        let body = body(frames);
+
+        let (before, before_bytes) = (
+            ALLOCATIONS.load(Ordering::Relaxed),
+            ALLOCATED.load(Ordering::Relaxed),
+        );
+        let collected = runtime.block_on(collect(body));
+        allocations += ALLOCATIONS.load(Ordering::Relaxed) - before;
+        allocated += ALLOCATED.load(Ordering::Relaxed) - before_bytes;
+
+        drop(collected);
+    }
+
+    println!(
+        "{name:>18} {frames:>2} frame(s): {:>5.1} allocations, {:>9} bytes, {:>8.1?} per body",
+        allocations as f64 / ITERATIONS as f64,
+        allocated / ITERATIONS,
+        started_at.elapsed() / ITERATIONS as u32,
+    );
+
+    allocated / ITERATIONS
+}
+
+fn main() {
+    for frames in [1, 16] {
+        measure("aggregate and copy", frames, aggregate_and_copy);
+        measure("to_bytes", frames, to_bytes);
+
+        let before = measure("copy to Vec", frames, copy_to_vec);
+        let now = measure("reuse Vec", frames, reuse_vec);
+        if frames == 1 {
+            assert!(
+                now < before,
+                "collecting single frame responses should not copy"
+            );
+        }
+    }
+}
@@ -1,15 +1,36 @@
 use std::sync::Arc;
 
+use anyhow::Context;
 use bytes::Bytes;
 use futures::future::BoxFuture;
-use http::Method;
+use http::{HeaderMap, HeaderValue, Method, StatusCode};
 use http_body_util::{Empty, Full};
 use rustserve::ServiceRequest;
 use serde_json::Value;
 
-use crate::mtls;
+use crate::filters::IDEMPOTENCY_KEY_HEADER;
+use crate::{mtls, EntityWithId, SeqApiResponse};
+
+mod cert_cache;
+mod circuit_breaker;
+mod pagination;
+mod query;
+mod retry;
+
+pub use cert_cache::CachedCertPath;
+pub use circuit_breaker::{
+    CircuitBreaker, CircuitBreakerConfig, CircuitOpen, CircuitState, DEFAULT_COOL_DOWN,
+    DEFAULT_FAILURE_RATIO, DEFAULT_FAILURE_WINDOW, DEFAULT_MIN_REQUESTS,
+};
+pub use pagination::{fetch_all, fetch_all_with_max_pages, DEFAULT_MAX_PAGES};
+pub use query::QueryParams;
+pub use retry::{RetryPolicy, DEFAULT_BASE_DELAY, DEFAULT_MAX_ATTEMPTS, DEFAULT_MAX_DELAY};
 
 /// Send a request to `path` using `controller` with payload `req`
+///
+/// Any `2xx` response is parsed into `Res`, other statuses are returned as a [`ClientError`].  An
+/// empty body, as sent with `204 No Content`, parses as JSON `null`, so `Res` can be `()` or an
+/// `Option`.
 pub async fn make_and_send_request<'a, C, Req, Res>(
     controller: Arc<C>,
     path: &'a str,
@@ -20,14 +41,313 @@ where
     Req: serde::Serialize + Send + 'a,
     Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
 {
-    let res = send_request(controller.clone(), &path, req).await?;
+    make_and_send_request_expecting(controller, path, req, StatusCode::is_success).await
+}
+
+/// Send a request like [`make_and_send_request`], treating the statuses for which `is_success`
+/// returns true as success instead of the `2xx` range.
+pub async fn make_and_send_request_expecting<'a, C, Req, Res>(
+    controller: Arc<C>,
+    path: &'a str,
+    req: Req,
+    is_success: impl Fn(&StatusCode) -> bool + Send,
+) -> anyhow::Result<http::Response<Res>>
+where
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Send + 'a,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
+{
+    send_and_parse(controller, path, req, HeaderMap::new(), is_success).await
+}
+
+/// Send a request like [`make_and_send_request`] with `headers` added to the request built by the
+/// controller, see [`send_request_with_headers`].
+pub async fn make_and_send_request_with_headers<'a, C, Req, Res>(
+    controller: Arc<C>,
+    path: &'a str,
+    req: Req,
+    headers: HeaderMap,
+) -> anyhow::Result<http::Response<Res>>
+where
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Send + 'a,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
+{
+    send_and_parse(controller, path, req, headers, StatusCode::is_success).await
+}
+
+/// Send a request like [`make_and_send_request`], parsing the body of unsuccessful responses as
+/// the error type `Err` the upstream is known to return.
+///
+/// Only responses with a status `Err` [expects](TypedError::expects_status) are parsed as `Err`.
+/// Other responses, and bodies that don't parse as `Err` or carry fields it doesn't know, are
+/// kept as JSON in [`TypedClientError::Untyped`], so unexpected errors, such as those of a proxy
+/// in front of the upstream, aren't lost or mistaken for `Err`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use rustserve::ServiceRequest;
+/// # use rustserve_platform::client::CertificatePath;
+/// # #[derive(serde::Deserialize)]
+/// # struct User {
+/// #     name: String,
+/// # }
+/// # async fn run<C>(users: Arc<C>, path: String) -> anyhow::Result<Option<User>>
+/// # where
+/// #     C: for<'a> ServiceRequest<'a, (), User>,
+/// #     C: for<'a> CertificatePath<'a, (), User>,
+/// # {
+/// use rustserve_platform::client::{make_and_send_request_typed, TypedClientError};
+/// use rustserve_platform::EntityNotFoundError;
+///
+/// let res = make_and_send_request_typed::<_, _, User, EntityNotFoundError>(users, &path, ());
+/// let user = match res.await {
+///     Ok(res) => Some(res.into_body()),
+///     Err(TypedClientError::Upstream { body, .. }) => {
+///         tracing::debug!(error = ?body, "user not found");
+///         None
+///     }
+///     Err(err) => return Err(err.into()),
+/// };
+/// # Ok(user)
+/// # }
+/// ```
+pub async fn make_and_send_request_typed<'a, C, Req, Res, Err>(
+    controller: Arc<C>,
+    path: &'a str,
+    req: Req,
+) -> Result<http::Response<Res>, TypedClientError<Err>>
+where
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Send + 'a,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
+    Err: TypedError,
+{
+    make_and_send_request(controller, path, req)
+        .await
+        .map_err(TypedClientError::from_error)
+}
+
+async fn send_and_parse<'a, C, Req, Res>(
+    controller: Arc<C>,
+    path: &'a str,
+    req: Req,
+    headers: HeaderMap,
+    is_success: impl Fn(&StatusCode) -> bool + Send,
+) -> anyhow::Result<http::Response<Res>>
+where
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Send + 'a,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
+{
+    let res = send_request_with_headers(controller.clone(), &path, req, headers).await?;
 
-    if res.status().as_u16() == 200 {
-        Ok(controller.parse_response(res).await?)
+    if is_success(&res.status()) {
+        let preview = body_preview(res.body());
+        let res = res.map(|body| {
+            if body.is_empty() {
+                b"null".to_vec()
+            } else {
+                body
+            }
+        });
+        controller.parse_response(res).await.with_context(|| {
+            format!(
+                "failed to deserialize {} from upstream body to {path}: {preview}",
+                std::any::type_name::<Res>()
+            )
+        })
     } else {
-        let (_, body) = res.into_parts();
-        let error_payload: Value = serde_json::from_slice(&body)?;
-        Err(anyhow::anyhow!("{error_payload}"))
+        let (parts, body) = res.into_parts();
+        Err(ClientError::new(parts.status, &body).into())
+    }
+}
+
+// How much of an upstream body is included in errors about it.
+const BODY_PREVIEW_LEN: usize = 512;
+
+// The start of `body` for error messages, marked when cut off.
+fn body_preview(body: &[u8]) -> String {
+    let preview = String::from_utf8_lossy(&body[..body.len().min(BODY_PREVIEW_LEN)]);
+
+    if body.len() > BODY_PREVIEW_LEN {
+        format!("{preview}... ({} bytes)", body.len())
+    } else {
+        preview.into_owned()
+    }
+}
+
+/// Send a request like [`make_and_send_request`], retrying it with exponential backoff when it
+/// fails in a way `policy` considers transient.
+///
+/// The error of the last attempt is returned once `policy` gives up.  When `policy` has an
+/// idempotency key, every attempt carries the same `Idempotency-Key` header.
+pub async fn make_and_send_request_with_retry<'a, C, Req, Res>(
+    controller: Arc<C>,
+    path: &'a str,
+    req: Req,
+    policy: RetryPolicy,
+) -> anyhow::Result<http::Response<Res>>
+where
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Clone + Send + 'a,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
+{
+    let mut headers = HeaderMap::new();
+    if let Some(key) = policy.idempotency_key() {
+        headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::try_from(key)?);
+    }
+
+    let mut attempt = 1;
+
+    loop {
+        let res = send_and_parse(
+            controller.clone(),
+            path,
+            req.clone(),
+            headers.clone(),
+            StatusCode::is_success,
+        )
+        .await;
+
+        let err = match res {
+            Ok(res) => return Ok(res),
+            Err(err) => err,
+        };
+
+        if attempt >= policy.max_attempts()
+            || !policy.allows(&C::method())
+            || !policy.is_retryable(&err)
+        {
+            return Err(err);
+        }
+
+        let delay = policy.delay(attempt - 1);
+        tracing::warn!(error = %err, attempt, ?delay, "retrying request to {path}");
+        tokio::time::sleep(delay).await;
+
+        attempt += 1;
+    }
+}
+
+/// The upstream answered a request with an unsuccessful status.
+///
+/// Returned from [`make_and_send_request`] wrapped in an [`anyhow::Error`], recover it with
+/// `err.downcast_ref::<ClientError>()` to react to the status code.  Requests rejected by an open
+/// [`CircuitBreaker`] without being sent are reported as [`CircuitOpen`] instead.
+#[derive(Debug)]
+pub struct ClientError {
+    /// The status code the upstream responded with.
+    pub status: StatusCode,
+    /// The response body, or the body as a JSON string if it wasn't valid JSON.
+    pub body: Value,
+}
+
+impl ClientError {
+    /// Construct a new instance of the ClientError struct from the status and raw body of an
+    /// upstream response.
+    pub fn new(status: StatusCode, body: &[u8]) -> Self {
+        let body = serde_json::from_slice(body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()));
+
+        Self { status, body }
+    }
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "upstream responded with {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// An error body an upstream answers unsuccessful requests with, as parsed by
+/// [`make_and_send_request_typed`].
+///
+/// Implemented for the general reusable errors, such as
+/// [`EntityNotFoundError`](crate::EntityNotFoundError), expecting the status each is answered
+/// with.
+pub trait TypedError: for<'de> serde::Deserialize<'de> {
+    /// Whether the upstream answers with this error when responding with `status`.
+    ///
+    /// Bodies of responses with other statuses are never parsed as this error, so errors of the
+    /// same shape, such as the `403` and `503` errors carrying only a message, aren't mistaken
+    /// for each other.
+    fn expects_status(status: StatusCode) -> bool;
+}
+
+/// A request sent with [`make_and_send_request_typed`] failed.
+#[derive(Debug)]
+pub enum TypedClientError<E> {
+    /// The upstream answered with an unsuccessful status and a body parsed as `E`.
+    Upstream {
+        /// The status code the upstream responded with.
+        status: StatusCode,
+        /// The error body.
+        body: E,
+    },
+    /// The upstream answered with an unsuccessful status and a body that isn't an `E`.
+    Untyped(ClientError),
+    /// The request failed without an unsuccessful response, because it couldn't be sent or the
+    /// successful response couldn't be parsed.
+    Request(anyhow::Error),
+}
+
+impl<E: TypedError> TypedClientError<E> {
+    fn from_error(err: anyhow::Error) -> Self {
+        match err.downcast::<ClientError>() {
+            Ok(client_error) => Self::from_client_error(client_error),
+            Err(err) => Self::Request(err),
+        }
+    }
+
+    fn from_client_error(client_error: ClientError) -> Self {
+        if !E::expects_status(client_error.status) {
+            return Self::Untyped(client_error);
+        }
+
+        match E::deserialize(&client_error.body) {
+            Ok(body) => Self::Upstream {
+                status: client_error.status,
+                body,
+            },
+            Err(_) => Self::Untyped(client_error),
+        }
+    }
+}
+
+impl<E> TypedClientError<E> {
+    /// The status code the upstream responded with, if it responded unsuccessfully.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Upstream { status, .. } => Some(*status),
+            Self::Untyped(client_error) => Some(client_error.status),
+            Self::Request(_) => None,
+        }
+    }
+}
+
+impl<E: std::fmt::Debug> std::fmt::Display for TypedClientError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Upstream { status, body } => {
+                write!(f, "upstream responded with {status}: {body:?}")
+            }
+            Self::Untyped(client_error) => client_error.fmt(f),
+            Self::Request(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: std::fmt::Debug> std::error::Error for TypedClientError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Request(err) => Some(err.as_ref()),
+            _ => None,
+        }
     }
 }
 
@@ -39,7 +359,46 @@ where
     Res: for<'de> serde::Deserialize<'de> + Send + 'a,
 {
     /// Returns the location of the certificates to use for this Req/Res pair.
+    ///
+    /// Called for every request, back lookups that are expensive with a [`CachedCertPath`].
     fn cert_path(self: Arc<Self>) -> BoxFuture<'a, anyhow::Result<String>>;
+
+    /// Returns the location of the client certificate chain and private key to present to servers
+    /// requiring client authentication, or `None` to connect without a client certificate.
+    fn client_cert_path(self: Arc<Self>) -> BoxFuture<'a, anyhow::Result<Option<ClientCertPath>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// Whether requests for this Req/Res pair reuse idle connections to the same upstream instead
+    /// of establishing a new connection for every request.
+    fn reuse_connections(&self) -> bool {
+        false
+    }
+
+    /// Whether to offer HTTP/2 to the upstream, falling back to HTTP/1.1 if it isn't supported.
+    fn prefer_h2(&self) -> bool {
+        false
+    }
+
+    /// The largest response body, in bytes, read into memory for this Req/Res pair.  Larger
+    /// responses fail the request.
+    fn max_response_size(&self) -> usize {
+        mtls::DEFAULT_MAX_RESPONSE_SIZE
+    }
+
+    /// The circuit breaker guarding the upstream of this Req/Res pair, or `None` to always send
+    /// requests.
+    fn circuit_breaker(&self) -> Option<Arc<CircuitBreaker>> {
+        None
+    }
+}
+
+/// Location of a client certificate chain and its private key.
+pub struct ClientCertPath {
+    /// Path to the PEM encoded certificate chain, leaf first.
+    pub chain: String,
+    /// Path to the PEM encoded private key.
+    pub key: String,
 }
 
 /// Establish a TLS connection to a TLS host and send an HTTP request to that host.
@@ -50,41 +409,455 @@ pub async fn send_request<'a, C, Req, Res>(
     path: &'a str,
     req: Req,
 ) -> anyhow::Result<http::Response<Vec<u8>>>
+where
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Send + 'a,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
+{
+    send_request_with_headers(controller, path, req, HeaderMap::new()).await
+}
+
+/// Send a request like [`send_request`] to `path` with `query` appended to it, see
+/// [`QueryParams::append_to`].
+///
+/// The path with the query is built by the call and only borrowed for its duration, so the
+/// controller has to handle paths of any lifetime, as controllers implemented for every `'a`
+/// do.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use rustserve::ServiceRequest;
+/// # use rustserve_platform::client::{CertificatePath, QueryParams};
+/// # async fn run<C>(users: Arc<C>) -> anyhow::Result<()>
+/// # where
+/// #     C: for<'a> ServiceRequest<'a, (), Vec<String>>,
+/// #     C: for<'a> CertificatePath<'a, (), Vec<String>>,
+/// # {
+/// use rustserve_platform::client::send_request_with_query;
+///
+/// let query = QueryParams::new().push("name", "Ada Lovelace").push("limit", 10);
+/// let res = send_request_with_query(users, "/users", &query, ()).await?;
+/// println!("{}", res.status());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn send_request_with_query<C, Req, Res>(
+    controller: Arc<C>,
+    path: &str,
+    query: &QueryParams,
+    req: Req,
+) -> anyhow::Result<http::Response<Vec<u8>>>
+where
+    C: for<'a> ServiceRequest<'a, Req, Res> + for<'a> CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Send + 'static,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'static,
+{
+    let path = query.append_to(path);
+    send_request(controller, &path, req).await
+}
+
+/// Send a request like [`send_request`] with `headers` added to the request built by the
+/// controller's `create_request`, replacing any headers of the same name.
+///
+/// The `host` header set by `create_request` is kept, a `host` in `headers` is ignored.  Requests
+/// created without a `host` header are sent with the host of the controller's address.
+pub async fn send_request_with_headers<'a, C, Req, Res>(
+    controller: Arc<C>,
+    path: &'a str,
+    req: Req,
+    headers: HeaderMap,
+) -> anyhow::Result<http::Response<Vec<u8>>>
+where
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Send + 'a,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
+{
+    let send_body = C::method() != Method::GET;
+    send_with_body(controller, path, req, headers, send_body).await
+}
+
+/// Send a DELETE request to `path` using `controller`, whose method should be DELETE, without a
+/// body.
+///
+/// `200 OK`, `202 Accepted` and `204 No Content` are treated as success without reading the
+/// response body, other statuses are returned as a [`ClientError`].
+pub async fn delete_request<'a, C, Req, Res>(
+    controller: Arc<C>,
+    path: &'a str,
+    req: Req,
+) -> anyhow::Result<()>
+where
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
+    Req: serde::Serialize + Send + 'a,
+    Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
+{
+    let res = send_with_body(controller, path, req, HeaderMap::new(), false).await?;
+
+    match res.status() {
+        StatusCode::OK | StatusCode::ACCEPTED | StatusCode::NO_CONTENT => Ok(()),
+        status => Err(ClientError::new(status, res.body()).into()),
+    }
+}
+
+/// Parse a response returned by [`send_request`] as a page of entities with their ids, keeping
+/// the pagination metadata of the [`SeqApiResponse`] envelope.
+///
+/// Statuses outside the `2xx` range are returned as a [`ClientError`].  Follow the pagination
+/// with [`SeqApiResponse::has_more`] and [`SeqApiResponse::next_offset`].
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::client::parse_seq_response;
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// let body = br#"{
+///     "total": 3, "count": 1, "offset": 0, "entity_name": "users",
+///     "entities": [{ "id": 7, "name": "ada" }]
+/// }"#;
+/// let page = parse_seq_response::<User>(http::Response::new(body.to_vec()))?;
+///
+/// for user in page.entities() {
+///     assert_eq!((user.id, user.entity.name.as_str()), (7, "ada"));
+/// }
+/// assert_eq!(page.next_offset(), Some(1));
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+pub fn parse_seq_response<T>(
+    res: http::Response<Vec<u8>>,
+) -> anyhow::Result<SeqApiResponse<Vec<EntityWithId<T>>>>
+where
+    T: serde::Serialize + for<'de> serde::Deserialize<'de>,
+{
+    parse_json_response(res)
+}
+
+/// Parse a response returned by [`send_request`] as a page of entities without ids, like
+/// [`parse_seq_response`].
+pub fn parse_seq_response_without_ids<T>(
+    res: http::Response<Vec<u8>>,
+) -> anyhow::Result<SeqApiResponse<Vec<T>>>
+where
+    T: serde::Serialize + for<'de> serde::Deserialize<'de>,
+{
+    parse_json_response(res)
+}
+
+fn parse_json_response<R>(res: http::Response<Vec<u8>>) -> anyhow::Result<R>
+where
+    R: for<'de> serde::Deserialize<'de>,
+{
+    if !res.status().is_success() {
+        return Err(ClientError::new(res.status(), res.body()).into());
+    }
+
+    serde_json::from_slice(res.body()).with_context(|| {
+        format!(
+            "failed to deserialize {} from upstream body: {}",
+            std::any::type_name::<R>(),
+            body_preview(res.body())
+        )
+    })
+}
+
+async fn send_with_body<'a, C, Req, Res>(
+    controller: Arc<C>,
+    path: &'a str,
+    req: Req,
+    headers: HeaderMap,
+    send_body: bool,
+) -> anyhow::Result<http::Response<Vec<u8>>>
 where
     C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
     Req: serde::Serialize + Send + 'a,
     Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
 {
     let cert_path = controller.clone().cert_path().await?;
-    tls_connect_and_send(controller, &path, cert_path, req).await
+    let client_cert_path = controller.clone().client_cert_path().await?;
+    tls_connect_and_send(
+        controller,
+        path,
+        cert_path,
+        client_cert_path,
+        req,
+        headers,
+        send_body,
+    )
+    .await
 }
 
 async fn tls_connect_and_send<'a, C, Req, Res>(
     controller: Arc<C>,
     path: &'a str,
     full_cert_path: String,
+    client_cert_path: Option<ClientCertPath>,
     req: Req,
+    mut headers: HeaderMap,
+    send_body: bool,
 ) -> anyhow::Result<http::Response<Vec<u8>>>
 where
-    C: ServiceRequest<'a, Req, Res>,
+    C: ServiceRequest<'a, Req, Res> + CertificatePath<'a, Req, Res>,
     Req: serde::Serialize + Send + 'a,
     Res: for<'de> serde::Deserialize<'de> + Send + Unpin + 'a,
 {
     let addr = controller.clone().addr().await?;
-    let request = controller.clone().create_request(addr.clone(), path, req).await?;
+    let mut request = controller
+        .clone()
+        .create_request(addr.clone(), path, req)
+        .await?;
 
-    let mtls = mtls::Mtls::new(
-        addr,
-        full_cert_path,
-        request.headers().get("host").unwrap().to_str()?,
-    )?;
+    headers.remove(http::header::HOST);
+    request.headers_mut().extend(headers);
 
-    let res = if C::method() == Method::GET {
-        mtls.send(request.map(|_| Empty::<Bytes>::new())).await?
+    let host = resolve_host(request.headers_mut(), &addr, path)?;
+
+    #[cfg(feature = "opentelemetry")]
+    crate::otel::inject_context(request.headers_mut());
+
+    let mut mtls = mtls::Mtls::new(addr.clone(), full_cert_path, host.clone())?
+        .with_prefer_h2(controller.prefer_h2())
+        .with_max_response_size(controller.max_response_size());
+
+    if let Some(client_cert_path) = client_cert_path {
+        mtls = mtls.with_client_cert(client_cert_path.chain, client_cert_path.key)?;
+    }
+
+    let circuit_breaker = controller.circuit_breaker();
+    if let Some(circuit_breaker) = &circuit_breaker {
+        if !circuit_breaker.allows(&addr, &host) {
+            return Err(CircuitOpen { addr, host }.into());
+        }
+    }
+
+    let res = if controller.reuse_connections() {
+        let body = |bytes: Vec<u8>| match send_body {
+            true => Full::new(Bytes::from(bytes)),
+            false => Full::new(Bytes::new()),
+        };
+        mtls.send_pooled(request.map(body)).await
+    } else if !send_body {
+        mtls.send(request.map(|_| Empty::<Bytes>::new())).await
     } else {
         mtls.send(request.map(|bytes| Full::new(Bytes::from(bytes))))
-            .await?
+            .await
     };
 
-    Ok(res)
+    if let Some(circuit_breaker) = &circuit_breaker {
+        match &res {
+            Ok(res) => circuit_breaker.record(&addr, &host, !res.status().is_server_error()),
+            Err(err) if is_upstream_failure(err) => circuit_breaker.record(&addr, &host, false),
+            // Requests failing locally say nothing about the health of the upstream.
+            Err(_) => {}
+        }
+    }
+
+    res
+}
+
+// Whether `err` means the upstream couldn't be reached or broke the connection, rather than the
+// request failing locally, for example with a response over the max response size.
+fn is_upstream_failure(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| cause.is::<std::io::Error>() || cause.is::<hyper::Error>())
+}
+
+/// Send `req` to the upstream named by the authority of its URI, such as
+/// `https://users.internal:8443/users/1`, without a controller, verifying that the upstream
+/// presents a certificate for `host` signed by one of the CAs in the PEM file at `cert_path`.
+///
+/// The port defaults to `443`, and a `host` header is added unless `req` has one.  The response
+/// body is read into memory whatever the status, like [`send_request`].  Meant for scripts,
+/// health checks and one-off calls, services calling an upstream repeatedly are better served by
+/// a controller implementing [`CertificatePath`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use rustserve_platform::client::send_raw_request;
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let req = http::Request::get("https://users.internal:8443/users/1").body(Vec::new())?;
+/// let res = send_raw_request(req, "/etc/ssl/certs/ca.cert", "users.internal").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn send_raw_request(
+    req: http::Request<Vec<u8>>,
+    cert_path: &str,
+    host: &str,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    let (mut parts, body) = req.into_parts();
+
+    let authority = parts
+        .uri
+        .authority()
+        .ok_or_else(|| anyhow::anyhow!("raw request to {} has no authority", parts.uri))?;
+    let addr = format!(
+        "{}:{}",
+        authority.host(),
+        authority.port_u16().unwrap_or(443)
+    );
+
+    // HTTP/1.1 requests to origin servers are sent with just the path and query.
+    parts.uri = parts
+        .uri
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str())
+        .parse()?;
+
+    if !parts.headers.contains_key(http::header::HOST) {
+        parts
+            .headers
+            .insert(http::header::HOST, HeaderValue::from_str(host)?);
+    }
+
+    let mtls = mtls::Mtls::new(addr, cert_path, host)?;
+
+    if body.is_empty() {
+        mtls.send(http::Request::from_parts(parts, Empty::<Bytes>::new()))
+            .await
+    } else {
+        mtls.send(http::Request::from_parts(
+            parts,
+            Full::new(Bytes::from(body)),
+        ))
+        .await
+    }
+}
+
+// The host an outbound request to `path` on `addr` is sent to, taken from its `host` header or
+// else derived from `addr` and added as the `host` header.
+fn resolve_host(headers: &mut HeaderMap, addr: &str, path: &str) -> anyhow::Result<String> {
+    if let Some(host) = headers.get(http::header::HOST) {
+        return Ok(host
+            .to_str()
+            .map_err(|_| anyhow::anyhow!("outbound request to {path} has an invalid host header"))?
+            .to_string());
+    }
+
+    let host = host_of(addr).ok_or_else(|| {
+        anyhow::anyhow!(
+            "outbound request to {path} is missing a host header and none can be derived from \
+             {addr}"
+        )
+    })?;
+    headers.insert(http::header::HOST, HeaderValue::from_str(&host)?);
+
+    Ok(host)
+}
+
+// The host part of an upstream address such as `users.internal:8443`, for requests created
+// without a `host` header.
+fn host_of(addr: &str) -> Option<String> {
+    let authority = addr.parse::<http::uri::Authority>().ok()?;
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntityNotFoundError, ForbiddenError, NotFoundError};
+
+    #[test]
+    fn only_counts_connection_failures_against_upstreams() {
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert!(is_upstream_failure(
+            &anyhow::Error::from(refused).context("connecting")
+        ));
+
+        let too_large = anyhow::anyhow!("upstream response exceeded 1024 bytes");
+        assert!(!is_upstream_failure(&too_large));
+    }
+
+    fn typed<E: TypedError>(status: StatusCode, body: Value) -> TypedClientError<E> {
+        TypedClientError::from_client_error(ClientError::new(
+            status,
+            &serde_json::to_vec(&body).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn parses_expected_errors() {
+        let body = serde_json::json!({ "id": 1, "entity": "users", "error": "entity not found" });
+
+        match typed::<EntityNotFoundError>(StatusCode::NOT_FOUND, body) {
+            TypedClientError::Upstream { status, body } => {
+                assert_eq!(status, StatusCode::NOT_FOUND);
+                assert_eq!(body.id(), 1);
+                assert_eq!(body.entity(), "users");
+            }
+            err => panic!("expected an upstream error, got {err}"),
+        }
+    }
+
+    #[test]
+    fn keeps_errors_with_other_statuses_untyped() {
+        let body = serde_json::json!({ "error": "service unavailable" });
+
+        let err = typed::<ForbiddenError>(StatusCode::SERVICE_UNAVAILABLE, body);
+
+        assert!(matches!(err, TypedClientError::Untyped(_)), "{err}");
+        assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[test]
+    fn keeps_errors_of_other_shapes_untyped() {
+        let body = serde_json::json!({ "id": 1, "entity": "users", "error": "entity not found" });
+
+        let err = typed::<NotFoundError>(StatusCode::NOT_FOUND, body);
+        assert!(matches!(err, TypedClientError::Untyped(_)), "{err}");
+
+        let body = serde_json::json!({ "path": "/users", "error": "not found", "trace": "abc" });
+        let err = typed::<NotFoundError>(StatusCode::NOT_FOUND, body);
+        assert!(matches!(err, TypedClientError::Untyped(_)), "{err}");
+    }
+
+    #[test]
+    fn keeps_the_host_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::HOST,
+            HeaderValue::from_static("users.example.com"),
+        );
+
+        let host = resolve_host(&mut headers, "10.0.0.1:8443", "/users").unwrap();
+
+        assert_eq!(host, "users.example.com");
+        assert_eq!(headers[http::header::HOST], "users.example.com");
+    }
+
+    #[test]
+    fn derives_missing_hosts_from_the_address() {
+        let mut headers = HeaderMap::new();
+
+        assert_eq!(
+            resolve_host(&mut headers, "users.internal:8443", "/users").unwrap(),
+            "users.internal"
+        );
+        assert_eq!(headers[http::header::HOST], "users.internal");
+    }
+
+    #[test]
+    fn fails_without_a_host() {
+        let mut headers = HeaderMap::new();
+
+        let err = resolve_host(&mut headers, "not an address", "/users").unwrap_err();
+
+        assert!(
+            err.to_string().contains("/users is missing a host header"),
+            "{err}"
+        );
+        assert!(!headers.contains_key(http::header::HOST));
+    }
 }
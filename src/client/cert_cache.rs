@@ -0,0 +1,79 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Remembers the certificate path a [`cert_path`](super::CertificatePath::cert_path)
+/// implementation resolved, so the lookup behind it runs once instead of on every request.
+///
+/// Hold one in the controller and resolve the path through [`CachedCertPath::get_or_load`].  Only
+/// successful lookups are cached.  The cached path is kept until the TTL set with
+/// [`CachedCertPath::with_ttl`] runs out, or forever without one, so call
+/// [`CachedCertPath::invalidate`] whenever the certificate rotates to a new location.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustserve_platform::client::CachedCertPath;
+///
+/// let cache = CachedCertPath::new().with_ttl(Duration::from_secs(300));
+///
+/// assert_eq!(cache.ttl(), Some(Duration::from_secs(300)));
+/// ```
+#[derive(Debug, Default)]
+pub struct CachedCertPath {
+    ttl: Option<Duration>,
+    cached: Mutex<Option<(String, Instant)>>,
+}
+
+impl CachedCertPath {
+    /// Create a new CachedCertPath keeping the resolved path until it is invalidated.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve the path again once it has been cached for `ttl`, so rotated certificates are
+    /// picked up without invalidating the cache explicitly.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// How long a resolved path is cached for, if limited.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Returns the cached path, or resolves it with `load` and caches it if there is none or it
+    /// has expired.
+    pub async fn get_or_load<F, Fut>(&self, load: F) -> anyhow::Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<String>>,
+    {
+        if let Some(path) = self.cached() {
+            return Ok(path);
+        }
+
+        let path = load().await?;
+        *self.cached.lock().unwrap() = Some((path.clone(), Instant::now()));
+
+        Ok(path)
+    }
+
+    /// Forget the cached path, so the next request resolves it again.
+    pub fn invalidate(&self) {
+        *self.cached.lock().unwrap() = None;
+    }
+
+    fn cached(&self) -> Option<String> {
+        let cached = self.cached.lock().unwrap();
+        let (path, resolved_at) = cached.as_ref()?;
+
+        match self.ttl {
+            Some(ttl) if resolved_at.elapsed() >= ttl => None,
+            _ => Some(path.clone()),
+        }
+    }
+}
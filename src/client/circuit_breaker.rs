@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The ratio of failed requests that opens a circuit unless configured otherwise.
+pub const DEFAULT_FAILURE_RATIO: f64 = 0.5;
+
+/// How many requests a circuit must have seen in a window before it can open unless configured
+/// otherwise.
+pub const DEFAULT_MIN_REQUESTS: u32 = 10;
+
+/// How long failures are counted for before the counts start over unless configured otherwise.
+pub const DEFAULT_FAILURE_WINDOW: Duration = Duration::from_secs(30);
+
+/// How long an open circuit rejects requests before letting a trial request through unless
+/// configured otherwise.
+pub const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(10);
+
+/// When a [`CircuitBreaker`] opens the circuit to an upstream and for how long.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustserve_platform::client::CircuitBreakerConfig;
+///
+/// let config = CircuitBreakerConfig::default()
+///     .with_failure_ratio(0.25)
+///     .with_cool_down(Duration::from_secs(30));
+///
+/// assert_eq!(config.failure_ratio(), 0.25);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerConfig {
+    failure_ratio: f64,
+    min_requests: u32,
+    window: Duration,
+    cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_ratio: DEFAULT_FAILURE_RATIO,
+            min_requests: DEFAULT_MIN_REQUESTS,
+            window: DEFAULT_FAILURE_WINDOW,
+            cool_down: DEFAULT_COOL_DOWN,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Open the circuit once at least `failure_ratio` of the requests in a window failed.
+    pub fn with_failure_ratio(mut self, failure_ratio: f64) -> Self {
+        self.failure_ratio = failure_ratio;
+        self
+    }
+
+    /// Only open the circuit once at least `min_requests` requests were sent in a window, so a
+    /// single failure of a rarely called upstream doesn't open it.
+    pub fn with_min_requests(mut self, min_requests: u32) -> Self {
+        self.min_requests = min_requests;
+        self
+    }
+
+    /// Count failures for `window` before starting over.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Reject requests for `cool_down` after opening the circuit before letting a trial request
+    /// through.
+    pub fn with_cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// The ratio of failed requests that opens the circuit.
+    pub fn failure_ratio(&self) -> f64 {
+        self.failure_ratio
+    }
+
+    /// How many requests a window must have seen before the circuit can open.
+    pub fn min_requests(&self) -> u32 {
+        self.min_requests
+    }
+
+    /// How long failures are counted for.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// How long an open circuit rejects requests.
+    pub fn cool_down(&self) -> Duration {
+        self.cool_down
+    }
+}
+
+/// The state of the circuit to an upstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent, and their failures counted.
+    Closed,
+    /// Requests are rejected without being sent.
+    Open,
+    /// The cool-down has passed and a single trial request decides whether the circuit closes
+    /// or opens again.
+    HalfOpen,
+}
+
+/// A request was rejected without being sent because the circuit to its upstream is open.
+///
+/// Returned from [`make_and_send_request`](super::make_and_send_request) wrapped in an
+/// [`anyhow::Error`].  Rejected requests are never retried, the circuit is open because the
+/// upstream needs room to recover.
+#[derive(Debug)]
+pub struct CircuitOpen {
+    /// The address of the upstream.
+    pub addr: String,
+    /// The host the request was for.
+    pub host: String,
+}
+
+impl std::fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "circuit to {} at {} is open", self.host, self.addr)
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// Stops sending requests to upstreams that keep failing, giving them room to recover.
+///
+/// Every `(addr, host)` pair has a circuit of its own.  Requests that fail to connect or break off
+/// mid request, and requests answered with a `5xx` status count as failures, while requests that
+/// fail locally, such as with a response over the max response size, aren't counted at all.  Once
+/// too many requests in a window failed the circuit opens, and requests are rejected right away
+/// with a [`CircuitOpen`] error until the cool-down has passed.  A single trial request is then
+/// let through, closing the circuit again if it succeeds.
+///
+/// Share one breaker between the controllers calling an upstream by returning it from
+/// [`CertificatePath::circuit_breaker`](super::CertificatePath::circuit_breaker).
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    circuits: Mutex<HashMap<(String, String), Circuit>>,
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed {
+        since: Instant,
+        successes: u32,
+        failures: u32,
+    },
+    Open {
+        since: Instant,
+    },
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+impl Circuit {
+    fn closed(now: Instant) -> Self {
+        Self::Closed {
+            since: now,
+            successes: 0,
+            failures: 0,
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a new CircuitBreaker opening circuits as described by `config`.
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The state of the circuit to `host` at `addr`.
+    pub fn state(&self, addr: &str, host: &str) -> CircuitState {
+        match self.circuits.lock().unwrap().get(&key(addr, host)) {
+            None | Some(Circuit::Closed { .. }) => CircuitState::Closed,
+            Some(Circuit::Open { .. }) => CircuitState::Open,
+            Some(Circuit::HalfOpen { .. }) => CircuitState::HalfOpen,
+        }
+    }
+
+    // Whether a request to `host` at `addr` may be sent.
+    pub(super) fn allows(&self, addr: &str, host: &str) -> bool {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits
+            .entry(key(addr, host))
+            .or_insert_with(|| Circuit::closed(now));
+
+        match circuit {
+            Circuit::Closed { since, .. } => {
+                if now.duration_since(*since) >= self.config.window {
+                    *circuit = Circuit::closed(now);
+                }
+                true
+            }
+            // A trial that never reported back, for example because it was cancelled, doesn't
+            // keep the circuit half open forever.
+            Circuit::Open { since } | Circuit::HalfOpen { since }
+                if now.duration_since(*since) >= self.config.cool_down =>
+            {
+                *circuit = Circuit::HalfOpen { since: now };
+                true
+            }
+            Circuit::Open { .. } | Circuit::HalfOpen { .. } => false,
+        }
+    }
+
+    // Count the outcome of a request to `host` at `addr` towards its circuit.
+    pub(super) fn record(&self, addr: &str, host: &str, success: bool) {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(&key(addr, host)) else {
+            return;
+        };
+
+        match circuit {
+            Circuit::Closed {
+                successes,
+                failures,
+                ..
+            } => {
+                if success {
+                    *successes += 1;
+                } else {
+                    *failures += 1;
+                }
+
+                let total = *successes + *failures;
+                if total >= self.config.min_requests
+                    && *failures as f64 / total as f64 >= self.config.failure_ratio
+                {
+                    tracing::warn!(
+                        addr,
+                        host,
+                        "opening circuit after {failures} of {total} requests failed"
+                    );
+                    *circuit = Circuit::Open { since: now };
+                }
+            }
+            Circuit::HalfOpen { .. } if success => {
+                tracing::info!(
+                    addr,
+                    host,
+                    "closing circuit after a successful trial request"
+                );
+                *circuit = Circuit::closed(now);
+            }
+            Circuit::HalfOpen { .. } => *circuit = Circuit::Open { since: now },
+            Circuit::Open { .. } => {}
+        }
+    }
+}
+
+fn key(addr: &str, host: &str) -> (String, String) {
+    (addr.to_string(), host.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: &str = "10.0.0.1:8443";
+    const HOST: &str = "users.internal";
+
+    fn breaker(min_requests: u32) -> CircuitBreaker {
+        CircuitBreaker::new(
+            CircuitBreakerConfig::default()
+                .with_min_requests(min_requests)
+                .with_window(Duration::from_millis(100))
+                .with_cool_down(Duration::from_millis(100)),
+        )
+    }
+
+    // Send a request through `breaker` that succeeds or fails.
+    fn send(breaker: &CircuitBreaker, success: bool) {
+        assert!(
+            breaker.allows(ADDR, HOST),
+            "the circuit lets requests through"
+        );
+        breaker.record(ADDR, HOST, success);
+    }
+
+    fn open(breaker: &CircuitBreaker) {
+        for _ in 0..breaker.config.min_requests() {
+            send(breaker, false);
+        }
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::Open);
+    }
+
+    #[test]
+    fn stays_closed_below_min_requests() {
+        let breaker = breaker(4);
+
+        for _ in 0..3 {
+            send(&breaker, false);
+        }
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::Closed);
+
+        send(&breaker, false);
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::Open);
+        assert!(!breaker.allows(ADDR, HOST));
+    }
+
+    #[test]
+    fn opens_at_the_failure_ratio() {
+        let breaker = breaker(4);
+
+        for success in [true, true, true, false, false] {
+            send(&breaker, success);
+        }
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::Closed);
+
+        send(&breaker, false);
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::Open);
+    }
+
+    #[test]
+    fn starts_counting_over_after_the_window() {
+        let breaker = breaker(2);
+
+        send(&breaker, false);
+        std::thread::sleep(Duration::from_millis(150));
+        send(&breaker, false);
+
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::Closed);
+    }
+
+    #[test]
+    fn lets_a_single_trial_through_after_the_cool_down() {
+        let breaker = breaker(2);
+        open(&breaker);
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(breaker.allows(ADDR, HOST));
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::HalfOpen);
+        assert!(!breaker.allows(ADDR, HOST), "only one trial is let through");
+
+        breaker.record(ADDR, HOST, true);
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::Closed);
+        send(&breaker, true);
+    }
+
+    #[test]
+    fn reopens_after_a_failed_trial() {
+        let breaker = breaker(2);
+        open(&breaker);
+
+        std::thread::sleep(Duration::from_millis(150));
+        send(&breaker, false);
+
+        assert_eq!(breaker.state(ADDR, HOST), CircuitState::Open);
+        assert!(!breaker.allows(ADDR, HOST));
+    }
+
+    #[test]
+    fn keeps_a_circuit_per_upstream() {
+        let breaker = breaker(2);
+        open(&breaker);
+
+        assert!(breaker.allows("10.0.0.2:8443", HOST));
+        assert_eq!(breaker.state("10.0.0.2:8443", HOST), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use rustserve::ServiceRequest;
+
+use super::{parse_seq_response_without_ids, send_request, CertificatePath, QueryParams};
+use crate::SeqApiResponse;
+
+/// How many pages [`fetch_all`] requests at most before giving up.
+pub const DEFAULT_MAX_PAGES: usize = 1000;
+
+/// Fetch every entity of the paginated collection at `base_path` using `controller`, requesting
+/// `page_size` entities at a time.
+///
+/// Pages are requested with `offset` and `limit` query parameters appended to `base_path` until
+/// the offset plus the `count` of a page reaches its `total`.  Gives up after
+/// [`DEFAULT_MAX_PAGES`] pages, see [`fetch_all_with_max_pages`].  Fails on the first page that
+/// fails, with a [`ClientError`](super::ClientError) for statuses outside the `2xx` range.
+///
+/// Entities are parsed as `T`, use [`EntityWithId<T>`](crate::EntityWithId) for collections
+/// returned with their ids.
+pub async fn fetch_all<C, T>(
+    controller: Arc<C>,
+    base_path: &str,
+    page_size: usize,
+) -> anyhow::Result<Vec<T>>
+where
+    C: for<'a> ServiceRequest<'a, (), SeqApiResponse<Vec<T>>>
+        + for<'a> CertificatePath<'a, (), SeqApiResponse<Vec<T>>>,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Send + Unpin + 'static,
+{
+    fetch_all_with_max_pages(controller, base_path, page_size, DEFAULT_MAX_PAGES).await
+}
+
+/// Fetch every entity like [`fetch_all`], giving up after `max_pages` pages.
+///
+/// The cap guards against upstreams whose `total` never stops growing, or which return empty
+/// pages short of their `total`; both fail instead of looping forever.
+pub async fn fetch_all_with_max_pages<C, T>(
+    controller: Arc<C>,
+    base_path: &str,
+    page_size: usize,
+    max_pages: usize,
+) -> anyhow::Result<Vec<T>>
+where
+    C: for<'a> ServiceRequest<'a, (), SeqApiResponse<Vec<T>>>
+        + for<'a> CertificatePath<'a, (), SeqApiResponse<Vec<T>>>,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Send + Unpin + 'static,
+{
+    let mut entities = Vec::new();
+    let mut offset = 0;
+
+    for _ in 0..max_pages {
+        let path = QueryParams::new()
+            .push("offset", offset)
+            .push("limit", page_size)
+            .append_to(base_path);
+
+        let res = send_request(controller.clone(), &path, ()).await?;
+        let page = parse_seq_response_without_ids::<T>(res)?;
+        let (total, count) = (page.total(), page.count());
+        entities.extend(page.into_entities());
+
+        if offset + count >= total {
+            return Ok(entities);
+        }
+        if count == 0 {
+            anyhow::bail!(
+                "upstream returned an empty page of {base_path} at offset {offset} of {total}"
+            );
+        }
+
+        offset += count;
+    }
+
+    anyhow::bail!(
+        "gave up fetching {base_path} after {max_pages} pages, {} entities fetched",
+        entities.len()
+    )
+}
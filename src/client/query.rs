@@ -0,0 +1,64 @@
+/// Query parameters for a request path, percent-encoded when built.
+///
+/// Parameters are kept in the order they were pushed, and a key may be pushed more than once.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::client::QueryParams;
+///
+/// let query = QueryParams::new()
+///     .push("name", "Ada Lovelace")
+///     .push("tag", "a&b");
+///
+/// assert_eq!(query.build(), "name=Ada+Lovelace&tag=a%26b");
+/// assert_eq!(query.append_to("/users?limit=10"), "/users?limit=10&name=Ada+Lovelace&tag=a%26b");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryParams {
+    params: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    /// Create a new QueryParams without any parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the parameter `key` with `value`.
+    pub fn push(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.params.push((key.into(), value.to_string()));
+        self
+    }
+
+    /// Whether no parameters have been pushed.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// Returns the parameters as a percent-encoded query string, without the leading `?`.
+    pub fn build(&self) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&self.params)
+            .finish()
+    }
+
+    /// Returns `path` with the parameters appended to its query, starting one if `path` doesn't
+    /// have a query yet.
+    ///
+    /// [`send_request_with_query`](super::send_request_with_query) sends a request to the
+    /// result directly.
+    pub fn append_to(&self, path: &str) -> String {
+        if self.is_empty() {
+            return path.to_string();
+        }
+
+        let separator = match path.find('?') {
+            None => "?",
+            Some(_) if path.ends_with('?') || path.ends_with('&') => "",
+            Some(_) => "&",
+        };
+
+        format!("{path}{separator}{}", self.build())
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use http::{Method, StatusCode};
+
+use super::{CircuitOpen, ClientError};
+
+/// How many times a request is attempted in total unless configured otherwise.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// The delay before the first retry unless configured otherwise, doubled on every retry after.
+pub const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// The longest delay between two attempts unless configured otherwise.
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// When and how often [`make_and_send_request_with_retry`](super::make_and_send_request_with_retry)
+/// retries a failed request.
+///
+/// Requests are retried when the connection to the upstream fails or times out, or when the
+/// upstream responds with one of the retryable statuses, `502`, `503` and `504` by default.  Only
+/// idempotent methods are retried unless [`RetryPolicy::with_retry_non_idempotent`] is set.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustserve_platform::client::RetryPolicy;
+///
+/// let policy = RetryPolicy::default()
+///     .with_max_attempts(5)
+///     .with_base_delay(Duration::from_millis(50));
+///
+/// assert_eq!(policy.max_attempts(), 5);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    retryable_statuses: Vec<StatusCode>,
+    retry_non_idempotent: bool,
+    idempotency_key: Option<IdempotencyKey>,
+}
+
+#[derive(Clone, Debug)]
+enum IdempotencyKey {
+    Fixed(String),
+    Generated,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            retryable_statuses: vec![
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_non_idempotent: false,
+            idempotency_key: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Attempt a request at most `max_attempts` times, including the first attempt.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Wait around `base_delay` before the first retry, doubling the delay on every retry after.
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Never wait longer than `max_delay` between two attempts.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Retry requests the upstream answers with any of `retryable_statuses`.
+    pub fn with_retryable_statuses(
+        mut self,
+        retryable_statuses: impl IntoIterator<Item = StatusCode>,
+    ) -> Self {
+        self.retryable_statuses = retryable_statuses.into_iter().collect();
+        self
+    }
+
+    /// Retry non-idempotent methods such as POST too.
+    ///
+    /// Only set this when the upstream deduplicates requests, for example because they carry an
+    /// idempotency key.
+    pub fn with_retry_non_idempotent(mut self) -> Self {
+        self.retry_non_idempotent = true;
+        self
+    }
+
+    /// Send every attempt with an `Idempotency-Key` header set to `key`, and retry non-idempotent
+    /// methods such as POST too since the upstream can deduplicate them.
+    ///
+    /// See [`IdempotencyFilter`](crate::filters::IdempotencyFilter) for deduplicating requests on
+    /// the server.
+    pub fn with_idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(IdempotencyKey::Fixed(key.into()));
+        self
+    }
+
+    /// Send every attempt with an `Idempotency-Key` header like
+    /// [`with_idempotency_key`](Self::with_idempotency_key), generating a new random key for
+    /// every request sent with this policy.
+    pub fn with_generated_idempotency_key(mut self) -> Self {
+        self.idempotency_key = Some(IdempotencyKey::Generated);
+        self
+    }
+
+    /// How many times a request is attempted in total.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// The delay before the first retry.
+    pub fn base_delay(&self) -> Duration {
+        self.base_delay
+    }
+
+    /// The longest delay between two attempts.
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    /// The upstream statuses that are retried.
+    pub fn retryable_statuses(&self) -> &[StatusCode] {
+        &self.retryable_statuses
+    }
+
+    pub(super) fn allows(&self, method: &Method) -> bool {
+        self.retry_non_idempotent
+            || self.idempotency_key.is_some()
+            || matches!(
+                *method,
+                Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+            )
+    }
+
+    // The idempotency key to send with every attempt of a request.
+    pub(super) fn idempotency_key(&self) -> Option<String> {
+        match self.idempotency_key.as_ref()? {
+            IdempotencyKey::Fixed(key) => Some(key.clone()),
+            IdempotencyKey::Generated => Some(uuid::Uuid::new_v4().to_string()),
+        }
+    }
+
+    pub(super) fn is_retryable(&self, err: &anyhow::Error) -> bool {
+        // Retrying would only hit the open circuit again.
+        if err.downcast_ref::<CircuitOpen>().is_some() {
+            return false;
+        }
+
+        if let Some(client_error) = err.downcast_ref::<ClientError>() {
+            return self.retryable_statuses.contains(&client_error.status);
+        }
+
+        // Failing to connect, losing the connection and timing out all surface as IO or hyper
+        // errors, anything else such as a bad certificate path won't succeed on a retry.
+        err.chain()
+            .any(|cause| cause.is::<std::io::Error>() || cause.is::<hyper::Error>())
+    }
+
+    // Exponential backoff with jitter, picking a delay between half and all of the backoff for
+    // the given retry so that clients retrying at the same time spread out.
+    pub(super) fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+
+        let jitter = RandomState::new().build_hasher().finish() % 1_000;
+
+        backoff / 2 + (backoff / 2).mul_f64(jitter as f64 / 1_000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_retryable_statuses_and_connection_failures() {
+        let policy = RetryPolicy::default();
+
+        let unavailable = ClientError::new(StatusCode::SERVICE_UNAVAILABLE, b"{}");
+        assert!(policy.is_retryable(&unavailable.into()));
+        let not_found = ClientError::new(StatusCode::NOT_FOUND, b"{}");
+        assert!(!policy.is_retryable(&not_found.into()));
+
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert!(policy.is_retryable(&anyhow::Error::from(refused).context("failed to connect")));
+    }
+
+    #[test]
+    fn never_retries_open_circuits() {
+        let open = CircuitOpen {
+            addr: "10.0.0.1:8443".into(),
+            host: "users.internal".into(),
+        };
+
+        assert!(!RetryPolicy::default().is_retryable(&open.into()));
+    }
+}
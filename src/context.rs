@@ -0,0 +1,39 @@
+use std::future::Future;
+use std::sync::Mutex;
+
+tokio::task_local! {
+    static CONTEXT: Mutex<http::Extensions>;
+}
+
+/// Run `fut` with an empty request context.
+///
+/// The runtime runs every request in its own context, only servers driving the routes themselves
+/// need to call this.  Outside of a context [`insert`] stores nothing and [`get`] finds nothing.
+pub fn scope<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    CONTEXT.scope(Mutex::new(http::Extensions::new()), fut)
+}
+
+/// Store `value` in the context of the current request, returning the value of the same type
+/// stored previously, if any.
+pub fn insert<T: Clone + Send + Sync + 'static>(value: T) -> Option<T> {
+    CONTEXT
+        .try_with(|extensions| extensions.lock().unwrap().insert(value))
+        .ok()
+        .flatten()
+}
+
+/// A copy of the value of type `T` stored in the context of the current request.
+pub fn get<T: Clone + Send + Sync + 'static>() -> Option<T> {
+    CONTEXT
+        .try_with(|extensions| extensions.lock().unwrap().get::<T>().cloned())
+        .ok()
+        .flatten()
+}
+
+/// Take the value of type `T` out of the context of the current request.
+pub fn remove<T: Send + Sync + 'static>() -> Option<T> {
+    CONTEXT
+        .try_with(|extensions| extensions.lock().unwrap().remove::<T>())
+        .ok()
+        .flatten()
+}
@@ -0,0 +1,45 @@
+mod accept;
+mod authorization;
+mod basic_auth;
+mod compression;
+mod concurrency_limit;
+mod content_type;
+mod cors;
+mod etag;
+mod idempotency;
+mod ip;
+mod json_body;
+mod jwt;
+mod logging;
+mod query_guard;
+mod rate_limit;
+mod read_only;
+mod request_id;
+mod security_headers;
+mod timeout;
+
+pub use accept::{AcceptFilter, NegotiatedType};
+pub use authorization::{AuthorizationFilter, AuthorizationRule, ANY_PRINCIPAL};
+pub use basic_auth::{BasicAuthFilter, BasicAuthUser, DEFAULT_MAX_CONCURRENT_VERIFICATIONS};
+pub use compression::{CompressionFilter, Encoding, DEFAULT_MIN_COMPRESS_SIZE};
+pub use concurrency_limit::ConcurrencyLimitFilter;
+pub use content_type::ContentTypeFilter;
+pub use cors::{AllowedOrigins, CorsFilter};
+pub use etag::ETagFilter;
+pub use idempotency::{
+    IdempotencyFilter, DEFAULT_IDEMPOTENCY_MAX_KEYS, DEFAULT_IDEMPOTENCY_TTL,
+    IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER,
+};
+pub use ip::IpFilter;
+pub use json_body::JsonBodyFilter;
+pub use jwt::{JwtAuthFilter, JwtClaims};
+pub use logging::{BodyLoggingFilter, LogFields, RequestLoggingFilter};
+pub use query_guard::{QueryGuardFilter, DEFAULT_MAX_QUERY_LENGTH, DEFAULT_MAX_QUERY_PARAMS};
+pub use rate_limit::RateLimitFilter;
+pub use read_only::ReadOnlyFilter;
+pub use request_id::{RequestId, RequestIdFilter, REQUEST_ID_HEADER, REQUEST_ID_PARAM};
+pub use security_headers::SecurityHeadersFilter;
+pub use timeout::TimeoutFilter;
+
+pub(crate) use ip::parse_cidr;
+pub(crate) use timeout::{deadline_passed, RouteDeadline};
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::header::ACCEPT;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::{context, platform_error, NotAcceptableError};
+
+/// The media type negotiated for the response to the request being handled, stored in the
+/// request [`context`] by [`AcceptFilter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NegotiatedType(pub String);
+
+impl NegotiatedType {
+    /// The media type negotiated for the request currently being handled, if it passed an
+    /// [`AcceptFilter`].
+    pub fn current() -> Option<Self> {
+        context::get()
+    }
+}
+
+/// A filter answering requests whose `Accept` header rules out every media type the service can
+/// produce with `406 Not Acceptable`.
+///
+/// Quality values are respected, and the most specific matching range decides the quality of a
+/// media type, so `text/*;q=0.5, text/csv` prefers `text/csv` over `text/plain`.  Between equally
+/// acceptable types the one listed first in the filter wins, as it does for requests without an
+/// `Accept` header.  Handlers find the winner through [`NegotiatedType::current`].
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::AcceptFilter;
+///
+/// let accept = AcceptFilter::new().with_producible(["application/json", "application/msgpack"]);
+/// ```
+pub struct AcceptFilter {
+    producible: Vec<String>,
+}
+
+impl AcceptFilter {
+    /// Create a new AcceptFilter producing `application/json` only.
+    pub fn new() -> Self {
+        Self {
+            producible: vec!["application/json".into()],
+        }
+    }
+
+    /// Set the media types responses can be produced in, in order of preference.
+    pub fn with_producible(
+        mut self,
+        producible: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.producible = producible
+            .into_iter()
+            .map(|media_type| media_type.into().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    fn negotiate(&self, accept: &[&str]) -> Option<&str> {
+        if accept.is_empty() {
+            return self.producible.first().map(String::as_str);
+        }
+
+        let ranges: Vec<_> = accept
+            .iter()
+            .flat_map(|value| value.split(','))
+            .filter_map(parse_media_range)
+            .collect();
+
+        let mut best: Option<(&str, f32)> = None;
+        for media_type in &self.producible {
+            let quality = ranges
+                .iter()
+                .filter_map(|(range, quality)| Some((specificity(range, media_type)?, *quality)))
+                .max_by_key(|(specificity, _)| *specificity)
+                .map_or(0.0, |(_, quality)| quality);
+
+            if quality > 0.0 && best.is_none_or(|(_, best)| quality > best) {
+                best = Some((media_type, quality));
+            }
+        }
+
+        best.map(|(media_type, _)| media_type)
+    }
+}
+
+// A media range such as `text/*;q=0.5`, lowercased, with its quality.
+fn parse_media_range(media_range: &str) -> Option<(String, f32)> {
+    let mut parts = media_range.split(';');
+    let range = parts.next()?.trim().to_ascii_lowercase();
+    if range.is_empty() {
+        return None;
+    }
+
+    let quality = parts
+        .find_map(|param| param.trim().strip_prefix("q="))
+        .and_then(|q| q.trim().parse::<f32>().ok())
+        .unwrap_or(1.0);
+
+    Some((range, quality))
+}
+
+// How specifically `range` matches `media_type`, or `None` if it doesn't match at all.
+fn specificity(range: &str, media_type: &str) -> Option<u8> {
+    if range == media_type {
+        return Some(2);
+    }
+    if range == "*/*" {
+        return Some(0);
+    }
+
+    let range_type = range.strip_suffix("/*")?;
+    let (media_type, _) = media_type.split_once('/')?;
+    (range_type == media_type).then_some(1)
+}
+
+impl Filter for AcceptFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let accept: Vec<_> = req
+                .headers()
+                .get_all(ACCEPT)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect();
+
+            match self.negotiate(&accept) {
+                Some(media_type) => {
+                    context::insert(NegotiatedType(media_type.to_string()));
+                    Ok(RequestFilterOutcome::Pass(req, params))
+                }
+                None => Ok(RequestFilterOutcome::Fail(platform_error(
+                    NotAcceptableError::new(&self.producible),
+                ))),
+            }
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::testing::filter_request;
+
+    // The media type negotiated for a request with `accept`, or the response rejecting it.
+    async fn negotiate(accept: &str) -> Result<Option<NegotiatedType>, http::Response<Vec<u8>>> {
+        let req = http::Request::get("/users")
+            .header(ACCEPT, accept)
+            .body(&b""[..])
+            .unwrap();
+
+        let filter = Arc::new(
+            AcceptFilter::new().with_producible(["application/json", "application/msgpack"]),
+        );
+        context::scope(async {
+            filter_request(&filter, req, HashMap::new()).await?;
+            Ok(NegotiatedType::current())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn negotiates_requested_types() {
+        let negotiated = negotiate("application/json").await.unwrap();
+        assert_eq!(negotiated, Some(NegotiatedType("application/json".into())));
+
+        let negotiated = negotiate("application/json;q=0.5, application/msgpack")
+            .await
+            .unwrap();
+        assert_eq!(
+            negotiated,
+            Some(NegotiatedType("application/msgpack".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn negotiates_the_preferred_type_for_wildcards() {
+        let negotiated = negotiate("*/*").await.unwrap();
+        assert_eq!(negotiated, Some(NegotiatedType("application/json".into())));
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_types() {
+        let res = negotiate("text/html")
+            .await
+            .expect_err("html can't be produced");
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::Method;
+use serde_json::Value;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use super::JwtClaims;
+use crate::runtime::ClientIdentity;
+use crate::{platform_error, ForbiddenError, UnauthorizedError};
+
+type PrincipalsFn = dyn Fn(&http::Request<&[u8]>) -> Vec<String> + Send + Sync;
+
+/// The principal matching every authenticated caller in an [`AuthorizationRule`].
+pub const ANY_PRINCIPAL: &str = "*";
+
+/// Allows a principal to make requests with the given methods to paths under a prefix.
+///
+/// Prefixes match whole path segments, `/users` matches `/users` and `/users/7` but not
+/// `/userstats`.
+#[derive(Clone, Debug)]
+pub struct AuthorizationRule {
+    principal: String,
+    methods: Vec<Method>,
+    path_prefix: String,
+}
+
+impl AuthorizationRule {
+    /// Create a new AuthorizationRule allowing `principal` to make requests with any method to
+    /// paths under `path_prefix`.  A principal of [`ANY_PRINCIPAL`] matches every authenticated
+    /// caller.
+    pub fn new(principal: impl Into<String>, path_prefix: impl Into<String>) -> Self {
+        Self {
+            principal: principal.into(),
+            methods: Vec::new(),
+            path_prefix: path_prefix.into(),
+        }
+    }
+
+    /// Only allow requests with one of `methods`.
+    pub fn with_methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    fn allows(&self, principals: &[String], method: &Method, path: &str) -> bool {
+        let principal_matches =
+            self.principal == ANY_PRINCIPAL || principals.contains(&self.principal);
+        let method_matches = self.methods.is_empty() || self.methods.contains(method);
+
+        principal_matches && method_matches && self.matches_path(path)
+    }
+
+    fn matches_path(&self, path: &str) -> bool {
+        let prefix = self.path_prefix.trim_end_matches('/');
+
+        match path.strip_prefix(prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+}
+
+/// A filter only allowing requests through that one of its rules permits for the caller, deny by
+/// default.
+///
+/// The caller is identified by the principals extracted from the request, such as the common
+/// name of its client certificate, the subject of its token or the roles it was granted.  Callers
+/// without any principal are answered with `401 Unauthorized`, callers no rule permits with
+/// `403 Forbidden`.  Place the filter after the filter that authenticates the caller, such as
+/// [`JwtAuthFilter`](super::JwtAuthFilter).
+///
+/// # Examples
+///
+/// ```
+/// use http::Method;
+/// use rustserve_platform::filters::{AuthorizationFilter, AuthorizationRule};
+///
+/// let authorization = AuthorizationFilter::by_jwt_claim("roles").with_rules([
+///     AuthorizationRule::new("admin", "/"),
+///     AuthorizationRule::new("reader", "/users").with_methods([Method::GET]),
+/// ]);
+/// ```
+pub struct AuthorizationFilter {
+    principals: Box<PrincipalsFn>,
+    rules: Vec<AuthorizationRule>,
+}
+
+impl AuthorizationFilter {
+    /// Create a new AuthorizationFilter identifying callers by the principals returned by
+    /// `principals`, without any rules.
+    pub fn new(
+        principals: impl Fn(&http::Request<&[u8]>) -> Vec<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            principals: Box::new(principals),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Create a new AuthorizationFilter identifying callers by the common name of their client
+    /// certificate, read from the [`ClientIdentity`] request extension.
+    pub fn by_client_cn() -> Self {
+        Self::new(|req| {
+            req.extensions()
+                .get::<ClientIdentity>()
+                .and_then(|identity| identity.common_name.clone())
+                .into_iter()
+                .collect()
+        })
+    }
+
+    /// Create a new AuthorizationFilter identifying callers by the `sub` claim of their token,
+    /// read from the [`JwtClaims`] request extension.
+    pub fn by_jwt_subject() -> Self {
+        Self::by_jwt_claim("sub")
+    }
+
+    /// Create a new AuthorizationFilter identifying callers by the `claim` of their token, read
+    /// from the [`JwtClaims`] request extension.  The claim may be a string or an array of
+    /// strings, such as a list of roles.
+    pub fn by_jwt_claim(claim: &'static str) -> Self {
+        Self::new(move |req| {
+            let Some(JwtClaims(claims)) = req.extensions().get::<JwtClaims>() else {
+                return Vec::new();
+            };
+
+            match claims.get(claim) {
+                Some(Value::String(principal)) => vec![principal.clone()],
+                Some(Value::Array(principals)) => principals
+                    .iter()
+                    .filter_map(|principal| principal.as_str().map(String::from))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        })
+    }
+
+    /// Permit the requests `rule` allows.
+    pub fn with_rule(mut self, rule: AuthorizationRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Permit the requests any of `rules` allows.
+    pub fn with_rules(mut self, rules: impl IntoIterator<Item = AuthorizationRule>) -> Self {
+        self.rules.extend(rules);
+        self
+    }
+}
+
+impl Filter for AuthorizationFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let principals = (self.principals)(&req);
+            if principals.is_empty() {
+                return Ok(RequestFilterOutcome::Fail(platform_error(
+                    UnauthorizedError::new(),
+                )));
+            }
+
+            let permitted = self
+                .rules
+                .iter()
+                .any(|rule| rule.allows(&principals, req.method(), req.uri().path()));
+
+            if !permitted {
+                return Ok(RequestFilterOutcome::Fail(platform_error(
+                    ForbiddenError::new(),
+                )));
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::testing::filter_request;
+
+    fn authorization() -> Arc<AuthorizationFilter> {
+        Arc::new(AuthorizationFilter::by_jwt_claim("roles").with_rules([
+            AuthorizationRule::new("admin", "/"),
+            AuthorizationRule::new("reader", "/users/").with_methods([Method::GET]),
+        ]))
+    }
+
+    // Filter a request with `method` to `path` from a caller granted `roles`, if authenticated.
+    async fn filter(method: Method, path: &str, roles: Option<&[&str]>) -> Result<(), StatusCode> {
+        let mut req = http::Request::builder()
+            .method(method)
+            .uri(path)
+            .body(&b""[..])
+            .unwrap();
+        if let Some(roles) = roles {
+            let claims = serde_json::json!({ "sub": "alice", "roles": roles });
+            let Value::Object(claims) = claims else {
+                unreachable!()
+            };
+            req.extensions_mut().insert(JwtClaims(claims));
+        }
+
+        filter_request(&authorization(), req, HashMap::new())
+            .await
+            .map(drop)
+            .map_err(|res| res.status())
+    }
+
+    #[tokio::test]
+    async fn allows_permitted_requests() {
+        assert_eq!(
+            filter(Method::GET, "/users", Some(&["reader"])).await,
+            Ok(())
+        );
+        assert_eq!(
+            filter(Method::GET, "/users/7", Some(&["reader"])).await,
+            Ok(())
+        );
+        assert_eq!(
+            filter(Method::DELETE, "/orders/7", Some(&["admin"])).await,
+            Ok(())
+        );
+        assert_eq!(
+            filter(Method::POST, "/users", Some(&["reader", "admin"])).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn denies_requests_no_rule_permits() {
+        let forbidden = Err(StatusCode::FORBIDDEN);
+        assert_eq!(
+            filter(Method::POST, "/users", Some(&["reader"])).await,
+            forbidden
+        );
+        assert_eq!(
+            filter(Method::GET, "/orders", Some(&["reader"])).await,
+            forbidden
+        );
+        assert_eq!(
+            filter(Method::GET, "/users", Some(&[])).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[tokio::test]
+    async fn matches_prefixes_on_segment_boundaries() {
+        let forbidden = Err(StatusCode::FORBIDDEN);
+        assert_eq!(
+            filter(Method::GET, "/userstats", Some(&["reader"])).await,
+            forbidden
+        );
+        assert_eq!(
+            filter(Method::GET, "/users-admin/7", Some(&["reader"])).await,
+            forbidden
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unauthenticated_requests() {
+        assert_eq!(
+            filter(Method::GET, "/users", None).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
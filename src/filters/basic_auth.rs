@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::future::BoxFuture;
+use http::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use tokio::sync::Semaphore;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::{auth_credentials, platform_error, UnauthorizedError};
+
+/// The number of passwords verified at the same time unless configured otherwise.
+pub const DEFAULT_MAX_CONCURRENT_VERIFICATIONS: usize = 4;
+
+/// The name of a user authenticated by [`BasicAuthFilter`], inserted into the request
+/// extensions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicAuthUser(pub String);
+
+/// A filter only allowing requests through that carry the credentials of a configured user in an
+/// `Authorization: Basic <base64>` header, answering all other requests with `401 Unauthorized`
+/// and a `WWW-Authenticate: Basic realm="..."` challenge.
+///
+/// Passwords are configured as salted Argon2 hashes in the PHC string format, as produced by
+/// [`BasicAuthFilter::hash_password`] or `argon2` command line tools, rather than in plaintext.
+/// Verifying a password is deliberately slow, so the filter is best kept to low traffic routes
+/// such as admin endpoints.  Verification runs on the blocking thread pool rather than the async
+/// workers, and at most [`DEFAULT_MAX_CONCURRENT_VERIFICATIONS`] passwords are verified at once,
+/// with further requests waiting their turn, so a burst of login attempts can't starve the
+/// runtime.  The name of authenticated users is made available to handlers as a
+/// [`BasicAuthUser`] request extension.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::BasicAuthFilter;
+///
+/// // Computed once and kept in the configuration, e.g. `$argon2id$v=19$m=19456,t=2,p=1$...`.
+/// let hash = BasicAuthFilter::hash_password("hunter2")?;
+///
+/// let auth = BasicAuthFilter::new("admin").with_user("ops", &hash)?;
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+pub struct BasicAuthFilter {
+    challenge: HeaderValue,
+    users: HashMap<String, String>,
+    verifications: Arc<Semaphore>,
+}
+
+impl BasicAuthFilter {
+    /// Create a new BasicAuthFilter challenging clients for credentials to `realm`, without any
+    /// users yet.
+    pub fn new(realm: &str) -> Self {
+        let realm = realm.replace(['\\', '"'], "");
+        let challenge = HeaderValue::from_str(&format!("Basic realm=\"{realm}\""))
+            .unwrap_or_else(|_| HeaderValue::from_static("Basic"));
+
+        Self {
+            challenge,
+            users: HashMap::new(),
+            verifications: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_VERIFICATIONS)),
+        }
+    }
+
+    /// Allow `username` in with the password whose Argon2 hash, in the PHC string format, is
+    /// `password_hash`.
+    pub fn with_user(
+        mut self,
+        username: impl Into<String>,
+        password_hash: &str,
+    ) -> anyhow::Result<Self> {
+        let username = username.into();
+        let password_hash = password_hash.trim();
+        PasswordHash::new(password_hash)
+            .map_err(|err| anyhow::anyhow!("invalid password hash for user {username}: {err}"))?;
+
+        self.users.insert(username, password_hash.to_string());
+        Ok(self)
+    }
+
+    /// Verify at most `max` passwords at the same time, instead of
+    /// [`DEFAULT_MAX_CONCURRENT_VERIFICATIONS`].
+    pub fn with_max_concurrent_verifications(mut self, max: usize) -> Self {
+        self.verifications = Arc::new(Semaphore::new(max.max(1)));
+        self
+    }
+
+    /// A salted Argon2 hash of `password` in the PHC string format, as expected by
+    /// [`BasicAuthFilter::with_user`].
+    pub fn hash_password(password: &str) -> anyhow::Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|err| anyhow::anyhow!("failed to hash password: {err}"))?;
+
+        Ok(hash.to_string())
+    }
+
+    async fn verify(&self, authorization: Option<&HeaderValue>) -> anyhow::Result<BasicAuthUser> {
+        let encoded = auth_credentials(authorization, "Basic")
+            .ok_or_else(|| anyhow::anyhow!("missing basic credentials"))?;
+
+        let decoded = String::from_utf8(STANDARD.decode(encoded.trim())?)?;
+        let (username, password) = decoded
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("malformed basic credentials"))?;
+
+        let known = self.users.get(username).cloned();
+        let is_known = known.is_some();
+        let password = password.to_string();
+
+        let _permit = self.verifications.acquire().await?;
+        let matches = tokio::task::spawn_blocking(move || {
+            // Unknown users are verified against the hash of a random password, so they take as
+            // long to reject as wrong passwords.
+            let expected = match &known {
+                Some(hash) => hash.as_str(),
+                None => unknown_user_hash()?,
+            };
+            let expected = PasswordHash::new(expected)
+                .map_err(|err| anyhow::anyhow!("invalid password hash: {err}"))?;
+
+            Ok::<_, anyhow::Error>(
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &expected)
+                    .is_ok(),
+            )
+        })
+        .await??;
+
+        if !(matches && is_known) {
+            anyhow::bail!("wrong credentials for user {username}");
+        }
+
+        Ok(BasicAuthUser(username.to_string()))
+    }
+}
+
+// The hash unknown users are verified against, computed on first use.
+fn unknown_user_hash() -> anyhow::Result<&'static str> {
+    static HASH: OnceLock<String> = OnceLock::new();
+
+    if let Some(hash) = HASH.get() {
+        return Ok(hash);
+    }
+
+    let password = SaltString::generate(&mut OsRng);
+    let hash = BasicAuthFilter::hash_password(password.as_str())?;
+    Ok(HASH.get_or_init(|| hash))
+}
+
+impl Filter for BasicAuthFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        mut req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            match self.verify(req.headers().get(AUTHORIZATION)).await {
+                Ok(user) => {
+                    req.extensions_mut().insert(user);
+                    Ok(RequestFilterOutcome::Pass(req, params))
+                }
+                Err(err) => {
+                    tracing::debug!(error = %err, "rejected basic credentials");
+
+                    let mut res = platform_error(UnauthorizedError::new());
+                    res.headers_mut()
+                        .insert(WWW_AUTHENTICATE, self.challenge.clone());
+                    Ok(RequestFilterOutcome::Fail(res))
+                }
+            }
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::testing::filter_request;
+
+    fn filter() -> Arc<BasicAuthFilter> {
+        let hash = BasicAuthFilter::hash_password("hunter2").unwrap();
+        Arc::new(
+            BasicAuthFilter::new("admin")
+                .with_user("ops", &hash)
+                .unwrap(),
+        )
+    }
+
+    fn request(authorization: Option<&str>) -> http::Request<&'static [u8]> {
+        let mut req = http::Request::get("/admin");
+        if let Some(authorization) = authorization {
+            req = req.header(AUTHORIZATION, authorization);
+        }
+        req.body(&b""[..]).unwrap()
+    }
+
+    fn basic(credentials: &str) -> String {
+        format!("Basic {}", STANDARD.encode(credentials))
+    }
+
+    #[tokio::test]
+    async fn lets_in_correct_credentials() {
+        let (req, _) = filter_request(
+            &filter(),
+            request(Some(&basic("ops:hunter2"))),
+            HashMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(req.extensions().get(), Some(&BasicAuthUser("ops".into())));
+
+        let authorization = basic("ops:hunter2").replace("Basic", "basic");
+        assert!(
+            filter_request(&filter(), request(Some(&authorization)), HashMap::new())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_credentials() {
+        for credentials in ["ops:hunter3", "root:hunter2", "ops"] {
+            let res = filter_request(
+                &filter(),
+                request(Some(&basic(credentials))),
+                HashMap::new(),
+            )
+            .await
+            .expect_err("the credentials are wrong");
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    async fn challenges_requests_without_credentials() {
+        let res = filter_request(&filter(), request(None), HashMap::new())
+            .await
+            .expect_err("credentials are required");
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers()[WWW_AUTHENTICATE], r#"Basic realm="admin""#);
+    }
+
+    #[tokio::test]
+    async fn bounds_concurrent_verifications() {
+        let filter = {
+            let hash = BasicAuthFilter::hash_password("hunter2").unwrap();
+            let filter = BasicAuthFilter::new("admin")
+                .with_user("ops", &hash)
+                .unwrap()
+                .with_max_concurrent_verifications(1);
+            Arc::new(filter)
+        };
+
+        // The only permit is taken, so a verification waits until it is handed back.
+        let permit = filter.verifications.clone().try_acquire_owned().unwrap();
+        let pending = tokio::spawn({
+            let filter = filter.clone();
+            async move {
+                filter_request(
+                    &filter,
+                    request(Some(&basic("ops:hunter2"))),
+                    HashMap::new(),
+                )
+                .await
+                .is_ok()
+            }
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!pending.is_finished());
+
+        drop(permit);
+        assert!(pending.await.unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_password_hashes() {
+        let hash = BasicAuthFilter::hash_password("hunter2").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+
+        assert!(BasicAuthFilter::new("admin")
+            .with_user("ops", "hunter2")
+            .is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use futures::future::BoxFuture;
+use http::header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, VARY};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::context;
+
+/// The smallest response body compressed unless configured otherwise, 1 KiB.
+pub const DEFAULT_MIN_COMPRESS_SIZE: usize = 1024;
+
+/// A content coding [`CompressionFilter`] can compress responses with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// `gzip`
+    Gzip,
+    /// `deflate`, the zlib format
+    Deflate,
+}
+
+impl Encoding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    fn encode(&self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            Self::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// A filter compressing response bodies with the first of its configured encodings the client
+/// accepts through `Accept-Encoding`.
+///
+/// Bodies smaller than the minimum size and responses that already have a `Content-Encoding`
+/// are left untouched.  The accepted encodings are carried from the request to the response
+/// through the request [`context`].
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::{CompressionFilter, Encoding};
+///
+/// let compression = CompressionFilter::new()
+///     .with_encodings([Encoding::Gzip, Encoding::Deflate])
+///     .with_min_size(4096);
+/// ```
+pub struct CompressionFilter {
+    encodings: Vec<Encoding>,
+    min_size: usize,
+}
+
+// The encoding picked for the request being filtered.
+#[derive(Clone)]
+struct Negotiated(Encoding);
+
+impl CompressionFilter {
+    /// Create a new CompressionFilter compressing bodies of at least
+    /// [`DEFAULT_MIN_COMPRESS_SIZE`] bytes with gzip.
+    pub fn new() -> Self {
+        Self {
+            encodings: vec![Encoding::Gzip],
+            min_size: DEFAULT_MIN_COMPRESS_SIZE,
+        }
+    }
+
+    /// Set the encodings to compress with, in order of preference.
+    pub fn with_encodings(mut self, encodings: impl IntoIterator<Item = Encoding>) -> Self {
+        self.encodings = encodings.into_iter().collect();
+        self
+    }
+
+    /// Set the smallest body, in bytes, that gets compressed.
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    fn negotiate(&self, accept_encoding: &str) -> Option<Encoding> {
+        let mut accepted = Vec::new();
+        let mut rejected = Vec::new();
+        for coding in accept_encoding.split(',') {
+            let mut parts = coding.split(';');
+            let name = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let is_rejected = parts.any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    == Some(0.0)
+            });
+            if is_rejected {
+                rejected.push(name);
+            } else {
+                accepted.push(name);
+            }
+        }
+
+        // A wildcard only accepts the codings that aren't rejected by name.
+        let accepts = |name: &str| {
+            accepted.iter().any(|accepted| accepted == name)
+                || (accepted.iter().any(|accepted| accepted == "*")
+                    && !rejected.iter().any(|rejected| rejected == name))
+        };
+
+        self.encodings
+            .iter()
+            .copied()
+            .find(|encoding| accepts(encoding.as_str()))
+    }
+}
+
+impl Filter for CompressionFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            // Repeated headers are one list, a coding rejected in one is rejected in all.
+            let accept_encoding = req
+                .headers()
+                .get_all(ACCEPT_ENCODING)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect::<Vec<_>>()
+                .join(",");
+            let negotiated = self.negotiate(&accept_encoding);
+
+            if let Some(encoding) = negotiated {
+                context::insert(Negotiated(encoding));
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            let negotiated = context::remove::<Negotiated>();
+
+            let (mut parts, body) = res.into_parts();
+            parts
+                .headers
+                .append(VARY, HeaderValue::from_static("accept-encoding"));
+
+            let body = match negotiated {
+                Some(Negotiated(encoding))
+                    if body.len() >= self.min_size
+                        && !parts.headers.contains_key(CONTENT_ENCODING) =>
+                {
+                    let body = encoding.encode(&body)?;
+                    parts.headers.insert(
+                        CONTENT_ENCODING,
+                        HeaderValue::from_static(encoding.as_str()),
+                    );
+                    parts.headers.insert(CONTENT_LENGTH, body.len().into());
+                    body
+                }
+                _ => body,
+            };
+
+            Ok(ResponseFilterOutcome::Pass(http::Response::from_parts(
+                parts, body,
+            )))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compression() -> CompressionFilter {
+        CompressionFilter::new().with_encodings([Encoding::Gzip, Encoding::Deflate])
+    }
+
+    #[test]
+    fn prefers_the_configured_order() {
+        assert_eq!(
+            compression().negotiate("deflate, gzip"),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(compression().negotiate("deflate"), Some(Encoding::Deflate));
+        assert_eq!(compression().negotiate("br"), None);
+        assert_eq!(compression().negotiate(""), None);
+    }
+
+    #[test]
+    fn skips_codings_rejected_with_q_zero() {
+        assert_eq!(
+            compression().negotiate("gzip;q=0, deflate"),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(compression().negotiate("gzip;q=0, deflate;q=0"), None);
+    }
+
+    #[test]
+    fn skips_rejected_codings_for_wildcards() {
+        assert_eq!(compression().negotiate("*"), Some(Encoding::Gzip));
+        assert_eq!(
+            compression().negotiate("gzip;q=0, *"),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(compression().negotiate("gzip;q=0, deflate;q=0, *"), None);
+        assert_eq!(compression().negotiate("*;q=0"), None);
+        assert_eq!(
+            compression().negotiate("deflate, *;q=0"),
+            Some(Encoding::Deflate)
+        );
+    }
+}
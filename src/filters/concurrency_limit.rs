@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use http::StatusCode;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::context;
+use crate::{platform_error, ServiceUnavailableError, TooManyRequestsError};
+
+/// A filter bounding how many requests pass through it at once, regardless of who sends them,
+/// answering requests over the limit with `503 Service Unavailable`.
+///
+/// A permit is taken when a request passes and returned once its response passes back through
+/// the filter, or when the request ends without one.  Share one instance between routes to bound
+/// them together.  Permits are kept in the request context, so requests driven outside of
+/// [`context::scope`] are not limited.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::ConcurrencyLimitFilter;
+///
+/// // Generate at most 5 reports at a time.
+/// let reports = ConcurrencyLimitFilter::new(5).with_too_many_requests();
+///
+/// assert_eq!(reports.available(), 5);
+/// ```
+pub struct ConcurrencyLimitFilter {
+    semaphore: Arc<Semaphore>,
+    limit: usize,
+    status: StatusCode,
+}
+
+// The permits held by the current request, one per filter it passed through.
+#[derive(Clone, Default)]
+struct HeldPermits(Arc<Mutex<Vec<(Arc<Semaphore>, OwnedSemaphorePermit)>>>);
+
+impl ConcurrencyLimitFilter {
+    /// Create a new ConcurrencyLimitFilter letting at most `limit` requests through at once.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            limit,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Answer requests over the limit with `429 Too Many Requests` instead.
+    pub fn with_too_many_requests(mut self) -> Self {
+        self.status = StatusCode::TOO_MANY_REQUESTS;
+        self
+    }
+
+    /// The most requests let through at once.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// How many more requests would currently be let through.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    fn reject(&self) -> http::Response<Vec<u8>> {
+        if self.status == StatusCode::TOO_MANY_REQUESTS {
+            platform_error(TooManyRequestsError::new(None))
+        } else {
+            platform_error(ServiceUnavailableError::new())
+        }
+    }
+}
+
+impl Filter for ConcurrencyLimitFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let Ok(permit) = self.semaphore.clone().try_acquire_owned() else {
+                tracing::debug!(limit = self.limit, "concurrency limit reached");
+                return Ok(RequestFilterOutcome::Fail(self.reject()));
+            };
+
+            let held = context::get::<HeldPermits>().unwrap_or_else(|| {
+                let held = HeldPermits::default();
+                context::insert(held.clone());
+                held
+            });
+            held.0
+                .lock()
+                .unwrap()
+                .push((self.semaphore.clone(), permit));
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            if let Some(HeldPermits(held)) = context::get::<HeldPermits>() {
+                held.lock()
+                    .unwrap()
+                    .retain(|(semaphore, _)| !Arc::ptr_eq(semaphore, &self.semaphore));
+            }
+
+            Ok(ResponseFilterOutcome::Pass(res))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{filter_request, filter_response};
+
+    // Pass a request through `filter`, or the status it was answered with.
+    async fn request(filter: &Arc<ConcurrencyLimitFilter>) -> Result<(), StatusCode> {
+        let req = http::Request::get("/reports").body(&b""[..]).unwrap();
+
+        filter_request(filter, req, HashMap::new())
+            .await
+            .map(|_| ())
+            .map_err(|res| res.status())
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_over_the_limit() {
+        for (filter, status) in [
+            (
+                ConcurrencyLimitFilter::new(2),
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            (
+                ConcurrencyLimitFilter::new(2).with_too_many_requests(),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+        ] {
+            let filter = Arc::new(filter);
+
+            // Each scope is a request still in flight while the ones inside it arrive.
+            context::scope(async {
+                assert_eq!(request(&filter).await, Ok(()));
+                context::scope(async {
+                    assert_eq!(request(&filter).await, Ok(()));
+                    context::scope(async {
+                        assert_eq!(request(&filter).await, Err(status));
+                    })
+                    .await;
+                    assert_eq!(filter.available(), 0);
+                })
+                .await;
+            })
+            .await;
+
+            assert_eq!(filter.available(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_the_permit_once_the_response_passes() {
+        let filter = Arc::new(ConcurrencyLimitFilter::new(1));
+
+        context::scope(async {
+            assert_eq!(request(&filter).await, Ok(()));
+            assert_eq!(filter.available(), 0);
+
+            filter_response(&filter, http::Response::new(Vec::new())).await;
+            assert_eq!(filter.available(), 1);
+
+            context::scope(async {
+                assert_eq!(request(&filter).await, Ok(()));
+            })
+            .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn returns_the_permit_when_the_request_ends_without_a_response() {
+        let filter = Arc::new(ConcurrencyLimitFilter::new(1));
+
+        context::scope(async {
+            assert_eq!(request(&filter).await, Ok(()));
+            assert_eq!(filter.available(), 0);
+        })
+        .await;
+
+        assert_eq!(filter.available(), 1);
+    }
+
+    #[tokio::test]
+    async fn limits_each_instance_separately() {
+        let reports = Arc::new(ConcurrencyLimitFilter::new(1));
+        let exports = Arc::new(ConcurrencyLimitFilter::new(1));
+
+        context::scope(async {
+            assert_eq!(request(&reports).await, Ok(()));
+            assert_eq!(request(&exports).await, Ok(()));
+
+            filter_response(&exports, http::Response::new(Vec::new())).await;
+            assert_eq!(reports.available(), 0);
+            assert_eq!(exports.available(), 1);
+        })
+        .await;
+
+        assert_eq!(reports.available(), 1);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::header::CONTENT_TYPE;
+use http::Method;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::{platform_error, UnsupportedMediaTypeError};
+
+/// A filter answering `POST`, `PUT` and `PATCH` requests whose body isn't of one of the allowed
+/// media types with `415 Unsupported Media Type`.
+///
+/// Media types are compared case-insensitively and without their parameters, so
+/// `application/json; charset=utf-8` matches `application/json`.  Requests with an empty body
+/// and requests with other methods are passed through untouched.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::ContentTypeFilter;
+///
+/// let content_type = ContentTypeFilter::new()
+///     .with_allowed(["application/json", "application/msgpack"]);
+/// ```
+pub struct ContentTypeFilter {
+    allowed: Vec<String>,
+}
+
+impl ContentTypeFilter {
+    /// Create a new ContentTypeFilter allowing `application/json` bodies only.
+    pub fn new() -> Self {
+        Self {
+            allowed: vec!["application/json".into()],
+        }
+    }
+
+    /// Set the media types request bodies may have.
+    pub fn with_allowed(mut self, allowed: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed = allowed
+            .into_iter()
+            .map(|media_type| media_type.into().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    fn is_allowed(&self, content_type: &str) -> bool {
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        self.allowed.iter().any(|allowed| *allowed == media_type)
+    }
+}
+
+impl Filter for ContentTypeFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let has_body = matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH)
+                && !req.body().is_empty();
+
+            if has_body {
+                let content_type = req
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .map(|value| value.to_str().unwrap_or_default())
+                    .unwrap_or_default();
+
+                if !self.is_allowed(content_type) {
+                    return Ok(RequestFilterOutcome::Fail(platform_error(
+                        UnsupportedMediaTypeError::new(content_type),
+                    )));
+                }
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::testing::filter_request;
+
+    async fn filter(
+        method: Method,
+        content_type: Option<&str>,
+    ) -> Result<(), http::Response<Vec<u8>>> {
+        let mut req = http::Request::builder().method(method).uri("/users");
+        if let Some(content_type) = content_type {
+            req = req.header(CONTENT_TYPE, content_type);
+        }
+        let req = req.body(&br#"{"name":"alice"}"#[..]).unwrap();
+
+        let filter = Arc::new(ContentTypeFilter::new());
+        filter_request(&filter, req, HashMap::new()).await.map(drop)
+    }
+
+    #[tokio::test]
+    async fn allows_matching_content_types() {
+        assert!(filter(Method::POST, Some("application/json")).await.is_ok());
+        assert!(filter(Method::PUT, Some("Application/JSON; charset=utf-8"))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_mismatching_content_types() {
+        let res = filter(Method::POST, Some("application/x-www-form-urlencoded"))
+            .await
+            .expect_err("form bodies aren't allowed");
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_content_types() {
+        let res = filter(Method::PATCH, None)
+            .await
+            .expect_err("bodies must declare their media type");
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn ignores_methods_without_bodies() {
+        assert!(filter(Method::GET, None).await.is_ok());
+        assert!(filter(Method::DELETE, Some("text/plain")).await.is_ok());
+    }
+}
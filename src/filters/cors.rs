@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use http::header::{
+    HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD,
+    CONTENT_TYPE, ORIGIN, VARY,
+};
+use http::{Method, StatusCode};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::context;
+
+/// The origins a [`CorsFilter`] allows cross-origin requests from.
+#[derive(Clone, Debug)]
+pub enum AllowedOrigins {
+    /// Allow requests from any origin.
+    Any,
+    /// Allow requests from the listed origins, such as `https://app.example.com`, only.
+    Exact(Vec<String>),
+}
+
+/// A filter answering CORS preflight requests and adding the `Access-Control-Allow-*` headers to
+/// responses for cross-origin requests from allowed origins.
+///
+/// Preflight requests are answered with `204 No Content` without being routed.  Requests from
+/// origins that aren't allowed get no CORS headers, so browsers refuse to expose the response.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use http::Method;
+/// use rustserve_platform::filters::{AllowedOrigins, CorsFilter};
+///
+/// let cors = CorsFilter::new(AllowedOrigins::Exact(vec!["https://app.example.com".into()]))
+///     .with_methods([Method::GET, Method::POST])
+///     .with_max_age(Duration::from_secs(600));
+/// ```
+pub struct CorsFilter {
+    origins: AllowedOrigins,
+    methods: Vec<Method>,
+    headers: Vec<HeaderName>,
+    max_age: Option<Duration>,
+}
+
+// The origin of the request being filtered, if it is allowed.
+#[derive(Clone)]
+struct AllowedOrigin(HeaderValue);
+
+impl CorsFilter {
+    /// Create a new CorsFilter allowing `GET`, `HEAD`, `POST`, `PUT` and `DELETE` requests with a
+    /// `content-type` header from `origins`.
+    pub fn new(origins: AllowedOrigins) -> Self {
+        Self {
+            origins,
+            methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::DELETE,
+            ],
+            headers: vec![CONTENT_TYPE],
+            max_age: None,
+        }
+    }
+
+    /// Set the methods cross-origin requests may use.
+    pub fn with_methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Set the request headers cross-origin requests may send.
+    pub fn with_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.headers = headers.into_iter().collect();
+        self
+    }
+
+    /// Let browsers cache preflight responses for `max_age`.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    // The value of `Access-Control-Allow-Origin` for a request from `origin`, if it is allowed.
+    fn allow_origin(&self, origin: Option<&HeaderValue>) -> Option<HeaderValue> {
+        match &self.origins {
+            AllowedOrigins::Any => Some(HeaderValue::from_static("*")),
+            AllowedOrigins::Exact(origins) => {
+                let origin = origin?;
+                origins
+                    .iter()
+                    .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+                    .then(|| origin.clone())
+            }
+        }
+    }
+
+    fn preflight_response(
+        &self,
+        allow_origin: HeaderValue,
+    ) -> anyhow::Result<http::Response<Vec<u8>>> {
+        let methods = self
+            .methods
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let headers = self
+            .headers
+            .iter()
+            .map(HeaderName::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut res = http::Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin)
+            .header(ACCESS_CONTROL_ALLOW_METHODS, methods)
+            .header(ACCESS_CONTROL_ALLOW_HEADERS, headers)
+            .header(VARY, "origin");
+
+        if let Some(max_age) = self.max_age {
+            res = res.header(ACCESS_CONTROL_MAX_AGE, max_age.as_secs());
+        }
+
+        Ok(res.body(Vec::new())?)
+    }
+}
+
+impl Filter for CorsFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let allow_origin = self.allow_origin(req.headers().get(ORIGIN));
+
+            let is_preflight = req.method() == Method::OPTIONS
+                && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+
+            if is_preflight {
+                let res = match allow_origin {
+                    Some(allow_origin) => self.preflight_response(allow_origin)?,
+                    None => http::Response::builder()
+                        .status(StatusCode::NO_CONTENT)
+                        .body(Vec::new())?,
+                };
+                return Ok(RequestFilterOutcome::Fail(res));
+            }
+
+            if let Some(allow_origin) = allow_origin {
+                context::insert(AllowedOrigin(allow_origin));
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        mut res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            let allow_origin = match (&self.origins, context::remove::<AllowedOrigin>()) {
+                (_, Some(AllowedOrigin(allow_origin))) => Some(allow_origin),
+                // Without a request context the origin is unknown, which only matters when the
+                // response depends on it.
+                (AllowedOrigins::Any, None) => Some(HeaderValue::from_static("*")),
+                (AllowedOrigins::Exact(_), None) => None,
+            };
+
+            if let Some(allow_origin) = allow_origin {
+                let headers = res.headers_mut();
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+                if let AllowedOrigins::Exact(_) = self.origins {
+                    headers.append(VARY, HeaderValue::from_static("origin"));
+                }
+            }
+
+            Ok(ResponseFilterOutcome::Pass(res))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{filter_request, filter_response};
+
+    const APP: &str = "https://app.example.com";
+
+    fn cors() -> Arc<CorsFilter> {
+        Arc::new(
+            CorsFilter::new(AllowedOrigins::Exact(vec![APP.into()]))
+                .with_methods([Method::GET, Method::POST])
+                .with_max_age(Duration::from_secs(600)),
+        )
+    }
+
+    #[tokio::test]
+    async fn answers_preflight_requests() {
+        let req = http::Request::options("/users")
+            .header(ORIGIN, APP)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(&b""[..])
+            .unwrap();
+
+        let res = context::scope(filter_request(&cors(), req, HashMap::new()))
+            .await
+            .expect_err("preflight requests are answered by the filter");
+
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], APP);
+        assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_METHODS], "GET, POST");
+        assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_HEADERS], "content-type");
+        assert_eq!(res.headers()[ACCESS_CONTROL_MAX_AGE], "600");
+    }
+
+    #[tokio::test]
+    async fn adds_headers_for_allowed_origins() {
+        let cors = cors();
+
+        let res = context::scope(async {
+            let req = http::Request::get("/users")
+                .header(ORIGIN, APP)
+                .body(&b""[..])
+                .unwrap();
+            assert!(filter_request(&cors, req, HashMap::new()).await.is_ok());
+
+            filter_response(&cors, http::Response::new(Vec::new())).await
+        })
+        .await;
+
+        assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], APP);
+        assert_eq!(res.headers()[VARY], "origin");
+    }
+
+    #[tokio::test]
+    async fn leaves_out_headers_for_other_origins() {
+        let cors = cors();
+
+        let res = context::scope(async {
+            let req = http::Request::get("/users")
+                .header(ORIGIN, "https://evil.example.com")
+                .body(&b""[..])
+                .unwrap();
+            assert!(filter_request(&cors, req, HashMap::new()).await.is_ok());
+
+            filter_response(&cors, http::Response::new(Vec::new())).await
+        })
+        .await;
+
+        assert!(!res.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::header::{HeaderValue, CONTENT_LENGTH, ETAG, IF_NONE_MATCH};
+use http::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::context;
+
+type HashFn = dyn Fn(&[u8]) -> String + Send + Sync;
+
+/// A filter tagging successful `GET` and `HEAD` responses with a weak `ETag` computed from the
+/// body, and answering requests whose `If-None-Match` lists that tag with `304 Not Modified`
+/// and no body.
+///
+/// The tag is the hex encoded SHA-256 digest of the body unless another hash is configured.  It
+/// is weak since it is computed before any content coding, so differently compressed variants
+/// of a body share it.  Handlers still run for conditional requests, since the tag is only known
+/// once the body has been produced, so the filter saves bandwidth rather than work.  Responses
+/// that carry an `ETag` of their own are compared by that tag instead.  The `If-None-Match`
+/// header is carried from the request to the response through the request [`context`].
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::ETagFilter;
+///
+/// let etag = ETagFilter::new();
+///
+/// // A cheaper, non-cryptographic tag.
+/// let etag = ETagFilter::new().with_hasher(|body| format!("{:x}", body.len()));
+/// ```
+pub struct ETagFilter {
+    hasher: Arc<HashFn>,
+}
+
+// The `If-None-Match` header of the request being filtered, if it is a `GET` or `HEAD`.
+#[derive(Clone)]
+struct Conditional(Option<String>);
+
+impl ETagFilter {
+    /// Create a new ETagFilter tagging bodies with their SHA-256 digest.
+    pub fn new() -> Self {
+        Self {
+            hasher: Arc::new(sha256_hex),
+        }
+    }
+
+    /// Tag bodies with the value `hasher` returns for them, which must only contain characters
+    /// allowed in an entity tag.
+    pub fn with_hasher(mut self, hasher: impl Fn(&[u8]) -> String + Send + Sync + 'static) -> Self {
+        self.hasher = Arc::new(hasher);
+        self
+    }
+}
+
+fn sha256_hex(body: &[u8]) -> String {
+    Sha256::digest(body)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+// Whether the `If-None-Match` header value lists `etag`, comparing tags weakly as RFC 9110 asks.
+fn matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+
+    if_none_match.trim() == "*"
+        || if_none_match
+            .split(',')
+            .any(|candidate| opaque(candidate) == opaque(etag))
+}
+
+impl Filter for ETagFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            if matches!(*req.method(), Method::GET | Method::HEAD) {
+                let if_none_match = req
+                    .headers()
+                    .get(IF_NONE_MATCH)
+                    .and_then(|value| value.to_str().ok())
+                    .map(String::from);
+
+                context::insert(Conditional(if_none_match));
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            let Some(Conditional(if_none_match)) = context::remove::<Conditional>() else {
+                return Ok(ResponseFilterOutcome::Pass(res));
+            };
+
+            if res.status() != StatusCode::OK {
+                return Ok(ResponseFilterOutcome::Pass(res));
+            }
+
+            let (mut parts, body) = res.into_parts();
+
+            let etag = match parts.headers.get(ETAG) {
+                Some(etag) => etag.clone(),
+                None => {
+                    let etag = HeaderValue::from_str(&format!("W/\"{}\"", (self.hasher)(&body)))?;
+                    parts.headers.insert(ETAG, etag.clone());
+                    etag
+                }
+            };
+
+            let not_modified = match (if_none_match, etag.to_str()) {
+                (Some(if_none_match), Ok(etag)) => matches(&if_none_match, etag),
+                _ => false,
+            };
+
+            if not_modified {
+                parts.status = StatusCode::NOT_MODIFIED;
+                parts.headers.remove(CONTENT_LENGTH);
+                return Ok(ResponseFilterOutcome::Pass(http::Response::from_parts(
+                    parts,
+                    Vec::new(),
+                )));
+            }
+
+            Ok(ResponseFilterOutcome::Pass(http::Response::from_parts(
+                parts, body,
+            )))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{filter_request, filter_response};
+
+    const BODY: &[u8] = br#"{"id":1}"#;
+
+    // The response `etag` produces for `res` to a request with `method` and `if_none_match`.
+    async fn respond(
+        method: Method,
+        if_none_match: Option<&str>,
+        res: http::Response<Vec<u8>>,
+    ) -> http::Response<Vec<u8>> {
+        let etag = Arc::new(ETagFilter::new().with_hasher(|body| format!("{:x}", body.len())));
+
+        let mut req = http::Request::builder().method(method).uri("/users/1");
+        if let Some(if_none_match) = if_none_match {
+            req = req.header(IF_NONE_MATCH, if_none_match);
+        }
+        let req = req.body(&b""[..]).unwrap();
+
+        context::scope(async {
+            assert!(filter_request(&etag, req, HashMap::new()).await.is_ok());
+            filter_response(&etag, res).await
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn tags_responses_weakly() {
+        let res = respond(Method::GET, None, http::Response::new(BODY.to_vec())).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[ETAG], r#"W/"8""#);
+        assert_eq!(res.body(), BODY);
+
+        let default = Arc::new(ETagFilter::new());
+        let res = context::scope(async {
+            let req = http::Request::get("/users/1").body(&b""[..]).unwrap();
+            assert!(filter_request(&default, req, HashMap::new()).await.is_ok());
+            filter_response(&default, http::Response::new(BODY.to_vec())).await
+        })
+        .await;
+        assert_eq!(
+            res.headers()[ETAG],
+            format!("W/\"{}\"", sha256_hex(BODY)).as_str()
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_matching_requests_with_not_modified() {
+        for if_none_match in [r#"W/"8""#, r#""8""#, r#""1", W/"8""#, "*"] {
+            let res = respond(
+                Method::GET,
+                Some(if_none_match),
+                http::Response::new(BODY.to_vec()),
+            )
+            .await;
+
+            assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "{if_none_match}");
+            assert!(res.body().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_bodies_for_stale_tags() {
+        let res = respond(
+            Method::GET,
+            Some(r#"W/"1""#),
+            http::Response::new(BODY.to_vec()),
+        )
+        .await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.body(), BODY);
+    }
+
+    #[tokio::test]
+    async fn compares_tags_set_by_handlers() {
+        let res = http::Response::builder()
+            .header(ETAG, r#""v2""#)
+            .body(BODY.to_vec())
+            .unwrap();
+
+        let res = respond(Method::GET, Some(r#""v2""#), res).await;
+
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[ETAG], r#""v2""#);
+    }
+
+    #[tokio::test]
+    async fn skips_other_methods() {
+        let res = respond(Method::POST, Some("*"), http::Response::new(BODY.to_vec())).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get(ETAG).is_none());
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use http::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::runtime::{ClientIdentity, PeerAddr};
+use crate::{context, platform_error, ConflictError};
+
+/// The request header carrying the idempotency key.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// The response header set on responses replayed by an [`IdempotencyFilter`].
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long an [`IdempotencyFilter`] replays a response unless configured otherwise.
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How many keys an [`IdempotencyFilter`] remembers at most unless configured otherwise.
+pub const DEFAULT_IDEMPOTENCY_MAX_KEYS: usize = 10_000;
+
+/// A filter that answers repeated POST and PATCH requests carrying the same `Idempotency-Key`
+/// header with the response to the first request, without routing them again.
+///
+/// Keys are scoped to the caller and to the method and path of the request, so clients can't
+/// replay each other's responses.  Callers are told apart by their client certificate, or else by
+/// their `Authorization` header, or else by their IP address.  A repeat arriving while the first
+/// request is still being handled is answered with `409 Conflict`.  Server error responses aren't
+/// remembered, and neither are requests that fail without a response, so the request can be
+/// retried with the same key.  Responses are kept in memory for the TTL, so every instance of a
+/// service remembers only the requests it handled.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustserve_platform::filters::IdempotencyFilter;
+///
+/// let idempotency = IdempotencyFilter::new()
+///     .with_ttl(Duration::from_secs(60 * 60))
+///     .with_max_keys(1_000);
+/// ```
+pub struct IdempotencyFilter {
+    ttl: Duration,
+    max_keys: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+struct Entry {
+    created_at: Instant,
+    // `None` while the first request with the key is being handled.
+    response: Option<CachedResponse>,
+}
+
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl CachedResponse {
+    fn replay(&self) -> http::Response<Vec<u8>> {
+        let mut res = http::Response::new(self.body.clone());
+        *res.status_mut() = self.status;
+        *res.headers_mut() = self.headers.clone();
+        res.headers_mut().insert(
+            HeaderName::from_static(IDEMPOTENT_REPLAYED_HEADER),
+            HeaderValue::from_static("true"),
+        );
+        res
+    }
+}
+
+// The key of the request being filtered, once it has been claimed, as stored in the request
+// context.  Copies share the one guard, so only dropping the context releases the key.
+#[derive(Clone)]
+struct ClaimedKey(Arc<KeyGuard>);
+
+// Releases the key again when dropped if no response was remembered, for example because handling
+// the request failed.
+struct KeyGuard {
+    key: String,
+    filter: Arc<IdempotencyFilter>,
+}
+
+impl Drop for KeyGuard {
+    fn drop(&mut self) {
+        let mut entries = self.filter.entries.lock().unwrap();
+        if entries
+            .get(&self.key)
+            .is_some_and(|entry| entry.response.is_none())
+        {
+            entries.remove(&self.key);
+        }
+    }
+}
+
+enum Claim {
+    Claimed,
+    InProgress,
+    Done(http::Response<Vec<u8>>),
+}
+
+impl IdempotencyFilter {
+    /// Create a new IdempotencyFilter remembering up to [`DEFAULT_IDEMPOTENCY_MAX_KEYS`] keys for
+    /// [`DEFAULT_IDEMPOTENCY_TTL`].
+    pub fn new() -> Self {
+        Self {
+            ttl: DEFAULT_IDEMPOTENCY_TTL,
+            max_keys: DEFAULT_IDEMPOTENCY_MAX_KEYS,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replay responses for `ttl` after the first request with a key was received.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Remember at most `max_keys` keys, forgetting the oldest ones first.
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = max_keys;
+        self
+    }
+
+    fn claim(&self, key: &str) -> Claim {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+
+        if let Some(entry) = entries.get(key) {
+            if now.duration_since(entry.created_at) < self.ttl {
+                return match &entry.response {
+                    Some(response) => Claim::Done(response.replay()),
+                    None => Claim::InProgress,
+                };
+            }
+        }
+
+        if entries.len() >= self.max_keys {
+            entries.retain(|_, entry| now.duration_since(entry.created_at) < self.ttl);
+        }
+
+        if entries.len() >= self.max_keys {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.created_at)
+                .map(|(key, _)| key.clone());
+
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(
+            key.to_string(),
+            Entry {
+                created_at: now,
+                response: None,
+            },
+        );
+
+        Claim::Claimed
+    }
+
+    fn complete(&self, key: &str, res: &http::Response<Vec<u8>>) {
+        if res.status().is_server_error() {
+            return;
+        }
+
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.response = Some(CachedResponse {
+                status: res.status(),
+                headers: res.headers().clone(),
+                body: res.body().clone(),
+            });
+        }
+    }
+}
+
+// Who sent `req`, as precisely as the request tells.  Credentials are hashed rather than kept in
+// memory for the TTL.
+fn caller(req: &http::Request<&[u8]>) -> String {
+    if let Some(identity) = req.extensions().get::<ClientIdentity>() {
+        let names = identity.subject_alt_names.join(",");
+        let common_name = identity.common_name.as_deref().unwrap_or_default();
+        return format!("cert:{common_name}/{names}");
+    }
+
+    if let Some(authorization) = req.headers().get(AUTHORIZATION) {
+        let digest = Sha256::digest(authorization.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        return format!("auth:{digest}");
+    }
+
+    match req.extensions().get::<PeerAddr>() {
+        Some(PeerAddr(addr)) => format!("ip:{}", addr.ip()),
+        None => "anonymous".into(),
+    }
+}
+
+impl Filter for IdempotencyFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            if !matches!(*req.method(), Method::POST | Method::PATCH) {
+                return Ok(RequestFilterOutcome::Pass(req, params));
+            }
+
+            let Some(key) = req
+                .headers()
+                .get(IDEMPOTENCY_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+            else {
+                return Ok(RequestFilterOutcome::Pass(req, params));
+            };
+
+            let key = format!(
+                "{} {} {} {key}",
+                caller(&req),
+                req.method(),
+                req.uri().path()
+            );
+
+            match self.claim(&key) {
+                Claim::Claimed => {
+                    context::insert(ClaimedKey(Arc::new(KeyGuard { key, filter: self })));
+                    Ok(RequestFilterOutcome::Pass(req, params))
+                }
+                Claim::InProgress => Ok(RequestFilterOutcome::Fail(platform_error(
+                    ConflictError::new(IDEMPOTENCY_KEY_HEADER),
+                ))),
+                Claim::Done(res) => Ok(RequestFilterOutcome::Fail(res)),
+            }
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            if let Some(ClaimedKey(guard)) = context::remove::<ClaimedKey>() {
+                self.complete(&guard.key, &res);
+            }
+
+            Ok(ResponseFilterOutcome::Pass(res))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{filter_request, filter_response};
+
+    fn request(key: &str) -> http::Request<&'static [u8]> {
+        http::Request::post("/orders")
+            .header(IDEMPOTENCY_KEY_HEADER, key)
+            .body(&b"{}"[..])
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn replays_the_first_response() {
+        let idempotency = Arc::new(IdempotencyFilter::new());
+
+        context::scope(async {
+            assert!(filter_request(&idempotency, request("a"), HashMap::new())
+                .await
+                .is_ok());
+
+            // Copies of the claim taken out of the context don't release the key.
+            drop(context::get::<ClaimedKey>());
+
+            let res = context::scope(filter_request(&idempotency, request("a"), HashMap::new()))
+                .await
+                .expect_err("the first request is still being handled");
+            assert_eq!(res.status(), StatusCode::CONFLICT);
+
+            let created = http::Response::builder()
+                .status(StatusCode::CREATED)
+                .body(b"created".to_vec())
+                .unwrap();
+            filter_response(&idempotency, created).await;
+        })
+        .await;
+
+        let res = context::scope(filter_request(&idempotency, request("a"), HashMap::new()))
+            .await
+            .expect_err("the response is replayed");
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.body(), b"created");
+        assert_eq!(res.headers()[IDEMPOTENT_REPLAYED_HEADER], "true");
+    }
+
+    #[tokio::test]
+    async fn releases_keys_of_failed_requests() {
+        let idempotency = Arc::new(IdempotencyFilter::new());
+
+        context::scope(async {
+            assert!(filter_request(&idempotency, request("a"), HashMap::new())
+                .await
+                .is_ok());
+        })
+        .await;
+
+        assert!(
+            context::scope(filter_request(&idempotency, request("a"), HashMap::new()))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn scopes_keys_to_the_caller() {
+        let idempotency = Arc::new(IdempotencyFilter::new());
+        let authorized = |authorization: &str| {
+            let mut req = request("a");
+            req.headers_mut()
+                .insert(AUTHORIZATION, HeaderValue::try_from(authorization).unwrap());
+            req
+        };
+
+        context::scope(async {
+            let req = authorized("Bearer alice");
+            assert!(filter_request(&idempotency, req, HashMap::new())
+                .await
+                .is_ok());
+            filter_response(&idempotency, http::Response::new(b"alice's".to_vec())).await;
+        })
+        .await;
+
+        let req = authorized("Bearer mallory");
+        assert!(
+            context::scope(filter_request(&idempotency, req, HashMap::new()))
+                .await
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use ipnet::IpNet;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::runtime::PeerAddr;
+use crate::{platform_error, ForbiddenError};
+
+/// A filter answering requests from client addresses outside the allowed ranges, or inside the
+/// denied ranges, with `403 Forbidden`.
+///
+/// Denied ranges take precedence over allowed ranges, and when no range is allowed explicitly
+/// every address not denied is allowed.  The client address is read from the [`PeerAddr`]
+/// request extension, requests without one are only let through when no range is allowed
+/// explicitly.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::IpFilter;
+///
+/// let internal_only = IpFilter::new()
+///     .allow("10.0.0.0/8")?
+///     .allow("fd00::/8")?
+///     .deny("10.0.13.7")?;
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+pub struct IpFilter {
+    allowed: Vec<IpNet>,
+    denied: Vec<IpNet>,
+}
+
+impl IpFilter {
+    /// Create a new IpFilter allowing every address.
+    pub fn new() -> Self {
+        Self {
+            allowed: Vec::new(),
+            denied: Vec::new(),
+        }
+    }
+
+    /// Allow requests from `cidr`, an IPv4 or IPv6 range such as `10.0.0.0/8` or a single
+    /// address.
+    pub fn allow(mut self, cidr: &str) -> anyhow::Result<Self> {
+        self.allowed.push(parse_cidr(cidr)?);
+        Ok(self)
+    }
+
+    /// Deny requests from `cidr`, an IPv4 or IPv6 range such as `10.0.0.0/8` or a single
+    /// address.
+    pub fn deny(mut self, cidr: &str) -> anyhow::Result<Self> {
+        self.denied.push(parse_cidr(cidr)?);
+        Ok(self)
+    }
+
+    fn is_allowed(&self, addr: Option<IpAddr>) -> bool {
+        let Some(addr) = addr.map(|addr| addr.to_canonical()) else {
+            return self.allowed.is_empty();
+        };
+
+        if self.denied.iter().any(|net| net.contains(&addr)) {
+            return false;
+        }
+
+        self.allowed.is_empty() || self.allowed.iter().any(|net| net.contains(&addr))
+    }
+}
+
+pub(crate) fn parse_cidr(cidr: &str) -> anyhow::Result<IpNet> {
+    cidr.parse::<IpNet>()
+        .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| anyhow::anyhow!("invalid CIDR range {cidr}"))
+}
+
+impl Filter for IpFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let addr = req
+                .extensions()
+                .get::<PeerAddr>()
+                .map(|PeerAddr(addr)| addr.ip());
+
+            if !self.is_allowed(addr) {
+                tracing::debug!(?addr, "rejected request from address");
+                return Ok(RequestFilterOutcome::Fail(platform_error(
+                    ForbiddenError::new(),
+                )));
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::header::CONTENT_TYPE;
+use http::Method;
+use serde::de::DeserializeOwned;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::{platform_error, InvalidPayloadError, UnsupportedMediaTypeError};
+
+/// A filter answering `POST`, `PUT` and `PATCH` requests whose body doesn't deserialize into a
+/// `T` with `400 Bad Request`, carrying the serde error message in an [`InvalidPayloadError`].
+///
+/// Empty bodies are rejected the same way, and bodies declared as anything other than
+/// `application/json` or a `+json` media type are answered with `415 Unsupported Media Type`.
+/// Valid bodies and requests with other methods are passed through untouched, leaving the
+/// controller to deserialize the body itself.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::JsonBodyFilter;
+///
+/// #[derive(serde::Deserialize)]
+/// struct CreateUser {
+///     name: String,
+/// }
+///
+/// let json_body = JsonBodyFilter::<CreateUser>::new();
+/// ```
+pub struct JsonBodyFilter<T> {
+    body: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> JsonBodyFilter<T> {
+    /// Create a new JsonBodyFilter checking request bodies deserialize into a `T`.
+    pub fn new() -> Self {
+        Self { body: PhantomData }
+    }
+}
+
+fn is_json(content_type: &str) -> bool {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    media_type == "application/json" || media_type.ends_with("+json")
+}
+
+impl<T: DeserializeOwned + 'static> Filter for JsonBodyFilter<T> {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            if !matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH) {
+                return Ok(RequestFilterOutcome::Pass(req, params));
+            }
+
+            if let Some(content_type) = req.headers().get(CONTENT_TYPE) {
+                let content_type = content_type.to_str().unwrap_or_default();
+                if !is_json(content_type) {
+                    return Ok(RequestFilterOutcome::Fail(platform_error(
+                        UnsupportedMediaTypeError::new(content_type),
+                    )));
+                }
+            }
+
+            let message = if req.body().is_empty() {
+                "request body is empty".to_string()
+            } else {
+                match serde_json::from_slice::<T>(req.body()) {
+                    Ok(_) => return Ok(RequestFilterOutcome::Pass(req, params)),
+                    Err(err) => err.to_string(),
+                }
+            };
+
+            Ok(RequestFilterOutcome::Fail(platform_error(
+                InvalidPayloadError::new(message),
+            )))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::{auth_credentials, platform_error, UnauthorizedError};
+
+/// The claims of a verified bearer token, inserted into the request extensions by
+/// [`JwtAuthFilter`].
+#[derive(Clone, Debug)]
+pub struct JwtClaims(pub Map<String, Value>);
+
+/// A filter only allowing requests through that carry a valid JWT in an
+/// `Authorization: Bearer <token>` header, answering all other requests with
+/// `401 Unauthorized`.
+///
+/// The signature is verified with the configured key and the `exp` and `nbf` claims are checked,
+/// as are the `iss` and `aud` claims when an issuer or audience is configured.  The claims of
+/// valid tokens are made available to handlers as a [`JwtClaims`] request extension.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::JwtAuthFilter;
+///
+/// let auth = JwtAuthFilter::hs256(b"secret")
+///     .with_issuer("https://auth.example.com")
+///     .with_audience("users");
+/// ```
+pub struct JwtAuthFilter {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtAuthFilter {
+    /// Create a new JwtAuthFilter accepting tokens signed with HS256 using `secret`.
+    pub fn hs256(secret: &[u8]) -> Self {
+        Self::new(DecodingKey::from_secret(secret), Algorithm::HS256)
+    }
+
+    /// Create a new JwtAuthFilter accepting tokens signed with RS256 by the private key matching
+    /// the PEM encoded RSA public key `pem`.
+    pub fn rs256(pem: &[u8]) -> anyhow::Result<Self> {
+        let key = DecodingKey::from_rsa_pem(pem)
+            .map_err(|err| anyhow::anyhow!("invalid RSA public key: {err}"))?;
+
+        Ok(Self::new(key, Algorithm::RS256))
+    }
+
+    fn new(key: DecodingKey, algorithm: Algorithm) -> Self {
+        let mut validation = Validation::new(algorithm);
+        validation.validate_nbf = true;
+        // `aud` is only checked once an audience is configured.
+        validation.validate_aud = false;
+
+        Self { key, validation }
+    }
+
+    /// Only accept tokens issued by `issuer`.
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.validation.set_issuer(&[issuer]);
+        self
+    }
+
+    /// Only accept tokens intended for `audience`.
+    pub fn with_audience(mut self, audience: &str) -> Self {
+        self.validation.set_audience(&[audience]);
+        self.validation.validate_aud = true;
+        self
+    }
+
+    fn verify(&self, authorization: Option<&HeaderValue>) -> anyhow::Result<JwtClaims> {
+        let token = auth_credentials(authorization, "Bearer")
+            .ok_or_else(|| anyhow::anyhow!("missing bearer token"))?;
+
+        let data = jsonwebtoken::decode::<Map<String, Value>>(token, &self.key, &self.validation)?;
+
+        Ok(JwtClaims(data.claims))
+    }
+}
+
+impl Filter for JwtAuthFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        mut req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            match self.verify(req.headers().get(AUTHORIZATION)) {
+                Ok(claims) => {
+                    req.extensions_mut().insert(claims);
+                    Ok(RequestFilterOutcome::Pass(req, params))
+                }
+                Err(err) => {
+                    tracing::debug!(error = %err, "rejected bearer token");
+
+                    let mut res = platform_error(UnauthorizedError::new());
+                    res.headers_mut()
+                        .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+                    Ok(RequestFilterOutcome::Fail(res))
+                }
+            }
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use http::StatusCode;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    use super::*;
+    use crate::testing::filter_request;
+
+    fn token() -> String {
+        let exp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+        let claims = serde_json::json!({ "sub": "alice", "exp": exp });
+        jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap()
+    }
+
+    fn request(authorization: &str) -> http::Request<&'static [u8]> {
+        http::Request::get("/users")
+            .header(AUTHORIZATION, authorization)
+            .body(&b""[..])
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn accepts_the_scheme_in_any_case() {
+        let auth = Arc::new(JwtAuthFilter::hs256(b"secret"));
+
+        for scheme in ["Bearer", "bearer", "BEARER"] {
+            let req = request(&format!("{scheme} {}", token()));
+            let (req, _) = filter_request(&auth, req, HashMap::new())
+                .await
+                .expect("the token is valid");
+            assert_eq!(
+                req.extensions().get::<JwtClaims>().unwrap().0["sub"],
+                "alice"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_other_schemes() {
+        let auth = Arc::new(JwtAuthFilter::hs256(b"secret"));
+
+        let res = filter_request(
+            &auth,
+            request(&format!("Basic {}", token())),
+            HashMap::new(),
+        )
+        .await
+        .expect_err("only bearer tokens are accepted");
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers()[WWW_AUTHENTICATE], "Bearer");
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tracing::Level;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use super::RequestId;
+use crate::context;
+use crate::runtime::PeerAddr;
+
+/// The names the fields of an access log line are logged under by [`RequestLoggingFilter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFields {
+    /// The request method.
+    pub method: &'static str,
+    /// The request path.
+    pub path: &'static str,
+    /// The address of the client, logged when known.
+    pub peer: &'static str,
+    /// The route parameters matched for the request.
+    pub params: &'static str,
+    /// The response status code.
+    pub status: &'static str,
+    /// The time from filtering the request to filtering the response, in milliseconds.
+    pub latency: &'static str,
+    /// The id assigned by [`RequestIdFilter`](super::RequestIdFilter), logged when present.
+    pub request_id: &'static str,
+}
+
+impl Default for LogFields {
+    fn default() -> Self {
+        Self {
+            method: "method",
+            path: "path",
+            peer: "peer",
+            params: "params",
+            status: "status",
+            latency: "latency_ms",
+            request_id: "request_id",
+        }
+    }
+}
+
+/// A filter logging an access log line through `tracing` for every request once its response is
+/// ready, recording the method, path, route parameters, status and latency, and the client
+/// address when known.
+///
+/// Every line is a `tracing` event with the `method`, `path`, `params`, `status` and `latency_ms`
+/// fields, plus `peer` and the `request_id` assigned by
+/// [`RequestIdFilter`](super::RequestIdFilter) when known, so subscribers can index them.
+/// `tracing` needs field names at compile time, so lines with fields renamed through
+/// [`LogFields`] carry them as a single `fields` JSON object under the configured names instead.
+/// The request side is carried to the response through the request [`context`], so nothing is
+/// logged for requests not handled inside one.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::{LogFields, RequestLoggingFilter};
+/// use tracing::Level;
+///
+/// let logging = RequestLoggingFilter::new()
+///     .with_level(Level::DEBUG)
+///     .with_fields(LogFields { latency: "duration_ms", ..LogFields::default() });
+/// ```
+pub struct RequestLoggingFilter {
+    level: Level,
+    fields: LogFields,
+}
+
+#[derive(Clone)]
+struct RequestStart {
+    method: http::Method,
+    path: String,
+    peer: Option<PeerAddr>,
+    params: BTreeMap<String, String>,
+    started_at: Instant,
+}
+
+impl RequestLoggingFilter {
+    /// Create a new RequestLoggingFilter logging at `INFO` level with the default field names.
+    pub fn new() -> Self {
+        Self {
+            level: Level::INFO,
+            fields: LogFields::default(),
+        }
+    }
+
+    /// Set the level access log lines are logged at.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set the names the fields of access log lines are logged under.
+    pub fn with_fields(mut self, fields: LogFields) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    // The fields of an access log line under the configured names.
+    fn renamed_fields(
+        &self,
+        start: &RequestStart,
+        status: u16,
+        latency_ms: f64,
+        request_id: Option<&RequestId>,
+    ) -> Value {
+        let fields = &self.fields;
+        let mut line = serde_json::Map::new();
+        line.insert(fields.method.into(), start.method.as_str().into());
+        line.insert(fields.path.into(), start.path.as_str().into());
+        line.insert(fields.params.into(), serde_json::json!(start.params));
+        line.insert(fields.status.into(), status.into());
+        line.insert(fields.latency.into(), latency_ms.into());
+        if let Some(PeerAddr(peer)) = start.peer {
+            line.insert(fields.peer.into(), peer.to_string().into());
+        }
+        if let Some(RequestId(id)) = request_id {
+            line.insert(fields.request_id.into(), id.as_str().into());
+        }
+
+        Value::Object(line)
+    }
+}
+
+impl Filter for RequestLoggingFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            context::insert(RequestStart {
+                method: req.method().clone(),
+                path: req.uri().path().into(),
+                peer: req.extensions().get::<PeerAddr>().copied(),
+                params: params.clone().into_iter().collect(),
+                started_at: Instant::now(),
+            });
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            if let Some(start) = context::remove::<RequestStart>() {
+                let status = res.status().as_u16();
+                let latency_ms = start.started_at.elapsed().as_secs_f64() * 1000.0;
+                let request_id = RequestId::current();
+
+                // Event levels have to be known at compile time.
+                macro_rules! access_log {
+                    ($($fields:tt)*) => {
+                        match self.level {
+                            Level::ERROR => tracing::event!(Level::ERROR, $($fields)*),
+                            Level::WARN => tracing::event!(Level::WARN, $($fields)*),
+                            Level::INFO => tracing::event!(Level::INFO, $($fields)*),
+                            Level::DEBUG => tracing::event!(Level::DEBUG, $($fields)*),
+                            _ => tracing::event!(Level::TRACE, $($fields)*),
+                        }
+                    };
+                }
+
+                if self.fields == LogFields::default() {
+                    let peer = start
+                        .peer
+                        .map(|PeerAddr(peer)| tracing::field::display(peer));
+                    let request_id = request_id
+                        .as_ref()
+                        .map(|RequestId(id)| tracing::field::display(id));
+
+                    access_log!(
+                        method = %start.method,
+                        path = %start.path,
+                        params = ?start.params,
+                        status,
+                        latency_ms,
+                        peer,
+                        request_id,
+                        "request completed"
+                    );
+                } else {
+                    let fields =
+                        self.renamed_fields(&start, status, latency_ms, request_id.as_ref());
+                    access_log!(fields = %fields, "request completed");
+                }
+            }
+
+            Ok(ResponseFilterOutcome::Pass(res))
+        })
+    }
+}
+
+/// A filter logging request and response bodies at `DEBUG` level while enabled, for diagnosing
+/// serialization mismatches with callers and upstreams.
+///
+/// JSON bodies are logged with the values of the redacted fields, at any depth and compared
+/// case-insensitively, replaced by `"***"`.  Other bodies are logged as their length only.
+/// When disabled the filter does nothing at all, so it can stay in place in production with the
+/// flag turned off.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::BodyLoggingFilter;
+///
+/// let body_logging = BodyLoggingFilter::new(cfg!(debug_assertions))
+///     .with_redacted(["password", "token", "ssn"]);
+/// ```
+pub struct BodyLoggingFilter {
+    enabled: bool,
+    redacted: Vec<String>,
+}
+
+impl BodyLoggingFilter {
+    /// Create a new BodyLoggingFilter logging bodies if `enabled`, without redacting any fields.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            redacted: Vec::new(),
+        }
+    }
+
+    /// Replace the values of fields named any of `redacted` before logging.
+    pub fn with_redacted(mut self, redacted: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.redacted = redacted
+            .into_iter()
+            .map(|field| field.into().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    fn describe(&self, body: &[u8]) -> String {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(mut json) if !body.is_empty() => {
+                self.redact(&mut json);
+                json.to_string()
+            }
+            _ => format!("<{} bytes>", body.len()),
+        }
+    }
+
+    fn redact(&self, json: &mut Value) {
+        match json {
+            Value::Object(fields) => {
+                for (name, value) in fields {
+                    if self.redacted.contains(&name.to_ascii_lowercase()) {
+                        *value = Value::String("***".into());
+                    } else {
+                        self.redact(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact(value)),
+            _ => {}
+        }
+    }
+}
+
+impl Filter for BodyLoggingFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            if self.enabled {
+                tracing::debug!(
+                    method = %req.method(),
+                    path = req.uri().path(),
+                    body = %self.describe(req.body()),
+                    "request body"
+                );
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            if self.enabled {
+                tracing::debug!(
+                    status = res.status().as_u16(),
+                    body = %self.describe(res.body()),
+                    "response body"
+                );
+            }
+
+            Ok(ResponseFilterOutcome::Pass(res))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, filter_request, filter_response};
+
+    // The log lines `logging` writes for a `GET /users/1` answered with `200 OK`.
+    async fn access_log(logging: RequestLoggingFilter) -> String {
+        let logs = testing::capture_logs();
+        let logging = Arc::new(logging);
+
+        context::scope(async {
+            context::insert(RequestId("abc-123".into()));
+
+            let req = http::Request::get("/users/1").body(&b""[..]).unwrap();
+            let params = HashMap::from([("user_id".to_string(), "1".to_string())]);
+            assert!(filter_request(&logging, req, params).await.is_ok());
+
+            filter_response(&logging, http::Response::new(Vec::new())).await;
+        })
+        .await;
+
+        logs.contents()
+    }
+
+    #[tokio::test]
+    async fn logs_requests_as_structured_fields() {
+        let logs = access_log(RequestLoggingFilter::new()).await;
+
+        assert!(logs.contains("request completed"), "{logs}");
+        assert!(logs.contains("method=GET"), "{logs}");
+        assert!(logs.contains("path=/users/1"), "{logs}");
+        assert!(logs.contains(r#"params={"user_id": "1"}"#), "{logs}");
+        assert!(logs.contains("status=200"), "{logs}");
+        assert!(logs.contains("latency_ms="), "{logs}");
+        assert!(logs.contains("request_id=abc-123"), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn logs_renamed_fields() {
+        let fields = LogFields {
+            latency: "duration_ms",
+            request_id: "trace_id",
+            ..LogFields::default()
+        };
+
+        let logs = access_log(RequestLoggingFilter::new().with_fields(fields)).await;
+
+        assert!(logs.contains(r#""method":"GET""#), "{logs}");
+        assert!(logs.contains(r#""path":"/users/1""#), "{logs}");
+        assert!(logs.contains(r#""status":200"#), "{logs}");
+        assert!(logs.contains(r#""duration_ms":"#), "{logs}");
+        assert!(logs.contains(r#""trace_id":"abc-123""#), "{logs}");
+        assert!(!logs.contains("latency_ms"), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn logs_at_the_configured_level() {
+        let logs = access_log(RequestLoggingFilter::new().with_level(Level::TRACE)).await;
+
+        assert!(logs.is_empty(), "{logs}");
+    }
+
+    #[test]
+    fn redacts_fields_at_any_depth() {
+        let logging = BodyLoggingFilter::new(true).with_redacted(["Password", "token"]);
+
+        let mut json = serde_json::json!({
+            "user": { "name": "ops", "PASSWORD": "hunter2" },
+            "sessions": [{ "Token": "abc" }, { "token": { "nested": "def" } }],
+            "token_count": 2,
+        });
+        logging.redact(&mut json);
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "user": { "name": "ops", "PASSWORD": "***" },
+                "sessions": [{ "Token": "***" }, { "token": "***" }],
+                "token_count": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn describes_other_bodies_by_their_length() {
+        let logging = BodyLoggingFilter::new(true).with_redacted(["password"]);
+
+        assert_eq!(logging.describe(b"password=hunter2"), "<16 bytes>");
+        assert_eq!(logging.describe(b""), "<0 bytes>");
+        assert_eq!(
+            logging.describe(br#"{"password":"hunter2"}"#),
+            r#"{"password":"***"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn logs_nothing_while_disabled() {
+        let logs = testing::capture_logs();
+        let logging = Arc::new(BodyLoggingFilter::new(false));
+
+        let req = http::Request::post("/login")
+            .body(&br#"{"password":"hunter2"}"#[..])
+            .unwrap();
+        let (req, _) = filter_request(&logging, req, HashMap::new()).await.unwrap();
+        assert_eq!(req.body(), br#"{"password":"hunter2"}"#);
+        filter_response(&logging, http::Response::new(b"{}".to_vec())).await;
+
+        assert!(logs.contents().is_empty(), "{}", logs.contents());
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::{platform_error, BadRequestError, InvalidParameterError};
+
+/// The longest query string, in bytes, [`QueryGuardFilter`] accepts unless configured otherwise.
+pub const DEFAULT_MAX_QUERY_LENGTH: usize = 2048;
+
+/// The most query parameters [`QueryGuardFilter`] accepts unless configured otherwise.
+pub const DEFAULT_MAX_QUERY_PARAMS: usize = 32;
+
+/// A filter answering requests with a query string that is too long, has too many parameters,
+/// isn't validly encoded or, when an allowlist is configured, has a parameter that isn't on it,
+/// with `400 Bad Request`.
+///
+/// The length is checked against the raw query string before it is parsed.  Allowlists are
+/// configured per filter, so they apply to the routes the filter is applied to.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::QueryGuardFilter;
+///
+/// let query_guard = QueryGuardFilter::new()
+///     .with_max_length(512)
+///     .with_allowed_keys(["offset", "limit", "sort"]);
+/// ```
+pub struct QueryGuardFilter {
+    max_length: usize,
+    max_params: usize,
+    allowed_keys: Option<HashSet<String>>,
+}
+
+impl QueryGuardFilter {
+    /// Create a new QueryGuardFilter accepting query strings of up to
+    /// [`DEFAULT_MAX_QUERY_LENGTH`] bytes with up to [`DEFAULT_MAX_QUERY_PARAMS`] parameters of
+    /// any name.
+    pub fn new() -> Self {
+        Self {
+            max_length: DEFAULT_MAX_QUERY_LENGTH,
+            max_params: DEFAULT_MAX_QUERY_PARAMS,
+            allowed_keys: None,
+        }
+    }
+
+    /// Reject query strings longer than `max_length` bytes.
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Reject query strings with more than `max_params` parameters.
+    pub fn with_max_params(mut self, max_params: usize) -> Self {
+        self.max_params = max_params;
+        self
+    }
+
+    /// Reject query strings with parameters other than `allowed_keys`.
+    pub fn with_allowed_keys(
+        mut self,
+        allowed_keys: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.allowed_keys = Some(allowed_keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn check(&self, query: &str) -> anyhow::Result<Option<http::Response<Vec<u8>>>> {
+        let bad_request = |message: String| Ok(Some(platform_error(BadRequestError::new(message))));
+
+        if query.len() > self.max_length {
+            return bad_request(format!(
+                "query string is longer than {} bytes",
+                self.max_length
+            ));
+        }
+
+        // `form_urlencoded` decodes invalid escapes leniently, so they're rejected up front.
+        if !is_validly_escaped(query) {
+            return bad_request("query string is not validly percent-encoded".into());
+        }
+
+        let params = form_urlencoded::parse(query.as_bytes())
+            .filter(|(key, _)| !key.is_empty())
+            .collect::<Vec<_>>();
+
+        if params.len() > self.max_params {
+            return bad_request(format!(
+                "query string has more than {} parameters",
+                self.max_params
+            ));
+        }
+
+        if let Some(allowed_keys) = &self.allowed_keys {
+            if let Some((key, value)) = params
+                .iter()
+                .find(|(key, _)| !allowed_keys.contains(key.as_ref()))
+            {
+                return Ok(Some(platform_error(InvalidParameterError::new(
+                    key.as_ref(),
+                    value.as_ref(),
+                ))));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn is_validly_escaped(query: &str) -> bool {
+    let bytes = query.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let escape = bytes.get(i + 1..i + 3);
+            if !escape.is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) {
+                return false;
+            }
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    true
+}
+
+impl Filter for QueryGuardFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let query = req.uri().query().unwrap_or_default();
+
+            match self.check(query)? {
+                Some(res) => {
+                    tracing::debug!(query_length = query.len(), "rejected query string");
+                    Ok(RequestFilterOutcome::Fail(res))
+                }
+                None => Ok(RequestFilterOutcome::Pass(req, params)),
+            }
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::testing::filter_request;
+
+    async fn check(
+        filter: QueryGuardFilter,
+        query: &str,
+    ) -> Result<http::Request<&'static [u8]>, http::Response<Vec<u8>>> {
+        let req = http::Request::get(format!("/users?{query}"))
+            .body(&b""[..])
+            .unwrap();
+
+        filter_request(&Arc::new(filter), req, HashMap::new())
+            .await
+            .map(|(req, _)| req)
+    }
+
+    #[tokio::test]
+    async fn rejects_query_strings_over_the_max_length() {
+        let filter = || QueryGuardFilter::new().with_max_length(16);
+
+        let res = check(filter(), "name=alice&page=2")
+            .await
+            .expect_err("the query string is 17 bytes");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        assert!(check(filter(), "name=alice&page2").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_query_strings_over_the_max_params() {
+        let filter = || QueryGuardFilter::new().with_max_params(2);
+
+        let res = check(filter(), "a=1&b=2&c=3")
+            .await
+            .expect_err("the query string has 3 parameters");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        assert!(check(filter(), "a=1&b=2").await.is_ok());
+        assert!(check(filter(), "a=1&&b=2&").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_parameters_off_the_allowlist() {
+        let filter = || QueryGuardFilter::new().with_allowed_keys(["offset", "limit"]);
+
+        let res = check(filter(), "offset=10&sort=name")
+            .await
+            .expect_err("sort is not allowed");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::from_slice::<Value>(res.body()).unwrap(),
+            json!({ "param": "sort", "value": "name", "error": "invalid parameter" }),
+        );
+
+        assert!(check(filter(), "offset=10&limit=5").await.is_ok());
+    }
+
+    #[test]
+    fn checks_percent_escapes() {
+        assert!(is_validly_escaped("name=alice%20smith&page=2"));
+        assert!(is_validly_escaped(""));
+        assert!(!is_validly_escaped("name=alice%2"));
+        assert!(!is_validly_escaped("name=alice%zz"));
+        assert!(!is_validly_escaped("%"));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use http::header::{HeaderName, RETRY_AFTER};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::runtime::PeerAddr;
+use crate::{platform_error, TooManyRequestsError};
+
+type KeyFn = dyn Fn(&http::Request<&[u8]>) -> Option<String> + Send + Sync;
+
+/// A filter limiting how many requests each client may make, answering requests over the limit
+/// with `429 Too Many Requests` and a `Retry-After` header.
+///
+/// Every client gets a token bucket holding up to `burst` tokens that refills at `rate` tokens
+/// per second, and every request takes one token.  Clients are told apart by a key extracted from
+/// the request, requests without a key are not limited.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::filters::RateLimitFilter;
+///
+/// // 10 requests per second, with bursts of up to 20, per API key.
+/// let rate_limit = RateLimitFilter::by_header(10.0, 20, "x-api-key");
+/// ```
+pub struct RateLimitFilter {
+    rate: f64,
+    burst: u32,
+    key: Box<KeyFn>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimitFilter {
+    /// Create a new RateLimitFilter allowing `rate` requests per second and bursts of up to
+    /// `burst` requests for every distinct key returned by `key`.
+    ///
+    /// A `rate` of zero never refills the buckets, allowing each key `burst` requests in total.
+    pub fn new(
+        rate: f64,
+        burst: u32,
+        key: impl Fn(&http::Request<&[u8]>) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            rate,
+            burst,
+            key: Box::new(key),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a new RateLimitFilter keyed by the value of the `header` request header.
+    pub fn by_header(rate: f64, burst: u32, header: &'static str) -> Self {
+        let header = HeaderName::from_static(header);
+        Self::new(rate, burst, move |req| {
+            req.headers()
+                .get(&header)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        })
+    }
+
+    /// Create a new RateLimitFilter keyed by the IP address of the client, read from the
+    /// [`PeerAddr`] request extension.
+    pub fn by_peer_ip(rate: f64, burst: u32) -> Self {
+        Self::new(rate, burst, |req| {
+            req.extensions()
+                .get::<PeerAddr>()
+                .map(|PeerAddr(addr)| addr.ip().to_string())
+        })
+    }
+
+    // Take a token from the bucket for `key`, or return how long until one is available.
+    fn take(&self, key: String) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // Buckets that have filled up again are no different from new ones, dropping them keeps
+        // the map from growing with every client ever seen.
+        if buckets.len() > 1024 {
+            buckets.retain(|_, bucket| !self.refill(bucket, now));
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.burst as f64,
+            refilled_at: now,
+        });
+        self.refill(bucket, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            // A rate of zero, or one small enough, never refills the bucket in any representable
+            // time.
+            let wait = (1.0 - bucket.tokens) / self.rate;
+            Err(Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX))
+        }
+    }
+
+    // Add the tokens accumulated since the bucket was last refilled, returning whether it is full.
+    fn refill(&self, bucket: &mut Bucket, now: Instant) -> bool {
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst as f64);
+        bucket.refilled_at = now;
+
+        bucket.tokens >= self.burst as f64
+    }
+}
+
+impl Filter for RateLimitFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let Some(key) = (self.key)(&req) else {
+                return Ok(RequestFilterOutcome::Pass(req, params));
+            };
+
+            if let Err(wait) = self.take(key) {
+                let retry_after = wait.as_secs_f64().ceil() as u64;
+                let mut res = platform_error(TooManyRequestsError::new(Some(retry_after)));
+                res.headers_mut().insert(RETRY_AFTER, retry_after.into());
+                return Ok(RequestFilterOutcome::Fail(res));
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::testing::filter_request;
+
+    fn request(key: &str) -> http::Request<&'static [u8]> {
+        http::Request::get("/users")
+            .header("x-api-key", key)
+            .body(&b""[..])
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn limits_requests_over_the_burst() {
+        let rate_limit = Arc::new(RateLimitFilter::by_header(1.0, 2, "x-api-key"));
+
+        for _ in 0..2 {
+            assert!(filter_request(&rate_limit, request("a"), HashMap::new())
+                .await
+                .is_ok());
+        }
+        let res = filter_request(&rate_limit, request("a"), HashMap::new())
+            .await
+            .expect_err("the third request is over the limit");
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[RETRY_AFTER], "1");
+
+        assert!(filter_request(&rate_limit, request("b"), HashMap::new())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn never_refills_with_a_rate_of_zero() {
+        let rate_limit = Arc::new(RateLimitFilter::by_header(0.0, 1, "x-api-key"));
+
+        assert!(filter_request(&rate_limit, request("a"), HashMap::new())
+            .await
+            .is_ok());
+        let res = filter_request(&rate_limit, request("a"), HashMap::new())
+            .await
+            .expect_err("the bucket never refills");
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
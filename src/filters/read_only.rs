@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::Method;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::{platform_error, ServiceUnavailableError};
+
+/// A filter answering POST, PUT, PATCH and DELETE requests with `503 Service Unavailable` while
+/// read-only mode is enabled, letting all other requests through.
+///
+/// Read-only mode is toggled through a shared flag, so it can be flipped while the service is
+/// running, for example from a signal handler or an admin endpoint.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// use rustserve_platform::filters::ReadOnlyFilter;
+///
+/// let read_only = Arc::new(AtomicBool::new(false));
+/// let filter = ReadOnlyFilter::new(read_only.clone());
+///
+/// // Entering the maintenance window.
+/// read_only.store(true, Ordering::Relaxed);
+/// assert!(filter.is_enabled());
+/// ```
+pub struct ReadOnlyFilter {
+    enabled: Arc<AtomicBool>,
+}
+
+impl ReadOnlyFilter {
+    /// Create a new ReadOnlyFilter rejecting mutations whenever `enabled` is set.
+    pub fn new(enabled: Arc<AtomicBool>) -> Self {
+        Self { enabled }
+    }
+
+    /// Whether read-only mode is currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
+
+impl Filter for ReadOnlyFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let mutates = matches!(
+                *req.method(),
+                Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+            );
+
+            if mutates && self.is_enabled() {
+                return Ok(RequestFilterOutcome::Fail(platform_error(
+                    ServiceUnavailableError::new(),
+                )));
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::header::{HeaderName, HeaderValue};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::context;
+
+/// The header request ids are read from and echoed back in.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The route parameter [`RequestIdFilter`] stores the request id under.
+pub const REQUEST_ID_PARAM: &str = "__request_id";
+
+// Longer ids supplied by clients are replaced rather than propagated.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The id of the request being handled, stored in the request [`context`] by
+/// [`RequestIdFilter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// The id of the request currently being handled, if [`RequestIdFilter`] assigned one.
+    pub fn current() -> Option<Self> {
+        context::get()
+    }
+}
+
+/// A filter assigning every request a correlation id, taken from the `X-Request-Id` request
+/// header or generated as a random UUID when absent, and echoing it back in the `X-Request-Id`
+/// response header.
+///
+/// Handlers find the id in the route parameters under [`REQUEST_ID_PARAM`], other filters through
+/// [`RequestId::current`].
+pub struct RequestIdFilter {
+    header: HeaderName,
+}
+
+impl RequestIdFilter {
+    /// Create a new RequestIdFilter
+    pub fn new() -> Self {
+        Self {
+            header: HeaderName::from_static(REQUEST_ID_HEADER),
+        }
+    }
+}
+
+impl Filter for RequestIdFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        mut params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            let id = req
+                .headers()
+                .get(&self.header)
+                .and_then(|value| value.to_str().ok())
+                .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+                .map(String::from)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+            context::insert(RequestId(id.clone()));
+            params.insert(REQUEST_ID_PARAM.into(), id);
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        mut res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            if let Some(RequestId(id)) = RequestId::current() {
+                res.headers_mut()
+                    .insert(self.header.clone(), HeaderValue::try_from(id)?);
+            }
+
+            Ok(ResponseFilterOutcome::Pass(res))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{filter_request, filter_response};
+
+    // Run a request sending `id`, if any, through the filter, returning the id handlers see and
+    // the response.
+    async fn round_trip(id: Option<&str>) -> (String, http::Response<Vec<u8>>) {
+        let filter = Arc::new(RequestIdFilter::new());
+
+        let mut req = http::Request::get("/users");
+        if let Some(id) = id {
+            req = req.header(REQUEST_ID_HEADER, id);
+        }
+        let req = req.body(&b""[..]).unwrap();
+
+        context::scope(async {
+            let (_, params) = filter_request(&filter, req, HashMap::new()).await.unwrap();
+            assert_eq!(
+                RequestId::current(),
+                Some(RequestId(params[REQUEST_ID_PARAM].clone()))
+            );
+
+            let res = filter_response(&filter, http::Response::new(Vec::new())).await;
+            (params[REQUEST_ID_PARAM].clone(), res)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn propagates_client_supplied_ids() {
+        let (id, res) = round_trip(Some("abc-123")).await;
+
+        assert_eq!(id, "abc-123");
+        assert_eq!(res.headers()[REQUEST_ID_HEADER], "abc-123");
+    }
+
+    #[tokio::test]
+    async fn generates_missing_ids() {
+        let (id, res) = round_trip(None).await;
+
+        assert!(uuid::Uuid::parse_str(&id).is_ok(), "{id}");
+        assert_eq!(res.headers()[REQUEST_ID_HEADER], id.as_str());
+    }
+
+    #[tokio::test]
+    async fn replaces_overlong_ids() {
+        let long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        let (id, _) = round_trip(Some(&long)).await;
+
+        assert!(uuid::Uuid::parse_str(&id).is_ok(), "{id}");
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use http::header::{
+    HeaderName, HeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+/// A filter adding security headers to every response that doesn't set them itself.
+///
+/// By default responses get:
+///
+/// - `X-Content-Type-Options: nosniff`
+/// - `X-Frame-Options: DENY`
+/// - `Strict-Transport-Security: max-age=31536000; includeSubDomains`
+/// - `Referrer-Policy: no-referrer`
+/// - `Content-Security-Policy: default-src 'none'; frame-ancestors 'none'`
+///
+/// # Examples
+///
+/// ```
+/// use http::header::{HeaderValue, CONTENT_SECURITY_POLICY, X_FRAME_OPTIONS};
+/// use rustserve_platform::filters::SecurityHeadersFilter;
+///
+/// let security_headers = SecurityHeadersFilter::new()
+///     .with_header(CONTENT_SECURITY_POLICY, HeaderValue::from_static("default-src 'self'"))
+///     .without_header(X_FRAME_OPTIONS);
+/// ```
+pub struct SecurityHeadersFilter {
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl SecurityHeadersFilter {
+    /// Create a new SecurityHeadersFilter adding the default headers.
+    pub fn new() -> Self {
+        Self {
+            headers: vec![
+                (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+                (X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+                (
+                    STRICT_TRANSPORT_SECURITY,
+                    HeaderValue::from_static("max-age=31536000; includeSubDomains"),
+                ),
+                (REFERRER_POLICY, HeaderValue::from_static("no-referrer")),
+                (
+                    CONTENT_SECURITY_POLICY,
+                    HeaderValue::from_static("default-src 'none'; frame-ancestors 'none'"),
+                ),
+            ],
+        }
+    }
+
+    /// Add `name: value` to responses, replacing the default value for `name` if there is one.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self = self.without_header(name.clone());
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Stop adding `name` to responses.
+    pub fn without_header(mut self, name: HeaderName) -> Self {
+        self.headers.retain(|(header, _)| *header != name);
+        self
+    }
+}
+
+impl Filter for SecurityHeadersFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move { Ok(RequestFilterOutcome::Pass(req, params)) })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        mut res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move {
+            for (name, value) in &self.headers {
+                if !res.headers().contains_key(name) {
+                    res.headers_mut().insert(name.clone(), value.clone());
+                }
+            }
+
+            Ok(ResponseFilterOutcome::Pass(res))
+        })
+    }
+}
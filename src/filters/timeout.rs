@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use rustserve::Filter;
+use rustserve::RequestFilterOutcome;
+use rustserve::ResponseFilterOutcome;
+
+use crate::context;
+
+/// A filter cutting off the handling of the routes it's applied to after `timeout`, answering
+/// with `504 Gateway Timeout` instead.
+///
+/// Filters only see the request and the response, so the filter hands its deadline to the
+/// runtime, which races the routed request against it and drops the handler once it passes.
+/// The runtime-wide [`request_timeout`](crate::runtime::RuntimeConfigBuilder::with_request_timeout)
+/// still applies as well: whichever of the two expires first wins, so routes that need longer
+/// than the runtime-wide timeout need that raised and stricter timeouts set on every other route
+/// group.  Several timeout filters on one route likewise leave the strictest in effect.
+///
+/// Outside of the runtime, when the request isn't run in a [`context`](crate::context) set up
+/// by it, the filter has no effect.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustserve_platform::filters::TimeoutFilter;
+///
+/// let reports = TimeoutFilter::new(Duration::from_secs(120));
+/// let lookups = TimeoutFilter::new(Duration::from_millis(500));
+/// ```
+pub struct TimeoutFilter {
+    timeout: Duration,
+}
+
+impl TimeoutFilter {
+    /// Create a new TimeoutFilter cutting requests off after `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+
+    /// How long requests are given before they are cut off.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+// The deadline of the request, shared by the runtime and the timeout filters of the route.
+#[derive(Clone)]
+pub(crate) struct RouteDeadline(Arc<watch::Sender<Option<Instant>>>);
+
+impl RouteDeadline {
+    // A deadline not set by any filter yet, and the receiver the runtime watches it through.
+    pub(crate) fn new() -> (Self, watch::Receiver<Option<Instant>>) {
+        let (deadline_tx, deadline_rx) = watch::channel(None);
+        (Self(Arc::new(deadline_tx)), deadline_rx)
+    }
+
+    fn tighten(&self, deadline: Instant) {
+        self.0.send_if_modified(|current| match current {
+            Some(current) if *current <= deadline => false,
+            _ => {
+                *current = Some(deadline);
+                true
+            }
+        });
+    }
+}
+
+// Resolves once the deadline watched through `deadline_rx` passes, and never if none is set.
+pub(crate) async fn deadline_passed(mut deadline_rx: watch::Receiver<Option<Instant>>) {
+    loop {
+        let deadline = *deadline_rx.borrow_and_update();
+
+        let changed = match deadline {
+            Some(deadline) => tokio::select! {
+                () = tokio::time::sleep_until(deadline) => return,
+                changed = deadline_rx.changed() => changed,
+            },
+            None => deadline_rx.changed().await,
+        };
+
+        if changed.is_err() {
+            return futures::future::pending().await;
+        }
+    }
+}
+
+impl Filter for TimeoutFilter {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        Box::pin(async move {
+            if let Some(deadline) = context::get::<RouteDeadline>() {
+                deadline.tighten(Instant::now() + self.timeout);
+            }
+
+            Ok(RequestFilterOutcome::Pass(req, params))
+        })
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+    }
+}
@@ -5,7 +5,8 @@
 //!
 //! A microservice platform library
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::marker::PhantomData;
 use std::sync::Arc;
 
 use futures::future::BoxFuture;
@@ -16,37 +17,72 @@ use rustserve::NotFound;
 use rustserve::RequestFilterOutcome;
 use rustserve::ResponseFilterOutcome;
 
-mod mtls;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+mod pem;
+#[cfg(test)]
+mod testing;
 
 /// Common utility for all clients.
 pub mod client;
 
+/// Per-request state shared between the request and response side of filters.
+pub mod context;
+
+/// Reusable filters for common cross-cutting concerns.
+pub mod filters;
+
+/// TLS connections to upstream services.
+pub mod mtls;
+
 /// Runtime for services built using rustserve.
 pub mod runtime;
 
-/// A filter for POST requests that only allow the requests through if the route parameters contain
-/// the ID param.
-pub struct PostFilter<T> {
+/// Whether requests matched by a [`MethodIdFilter`] must or must not carry the ID param.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdPolicy {
+    /// Only allow the requests through if the route parameters contain the ID param.
+    Required,
+    /// Only allow the requests through if the route parameters dont contain the ID param.
+    Forbidden,
+}
+
+/// A filter for requests with a given method that only allows the requests through if the
+/// presence of the ID param in the route parameters matches an [`IdPolicy`].
+///
+/// Requests with any other method are passed through untouched, rejected requests are answered
+/// with [`NotFound::not_found`].
+pub struct MethodIdFilter<T> {
+    method: http::Method,
+    policy: IdPolicy,
     phantom: std::marker::PhantomData<T>,
 }
 
-impl<T> PostFilter<T> {
-    /// Create a new PostFilter
-    pub fn new() -> Self {
+impl<T> MethodIdFilter<T> {
+    /// Create a new MethodIdFilter applying `policy` to requests with `method`
+    pub fn new(method: http::Method, policy: IdPolicy) -> Self {
         Self {
+            method,
+            policy,
             phantom: std::marker::PhantomData,
         }
     }
 }
 
-impl<T: IdParam + NotFound> Filter for PostFilter<T> {
+impl<T: IdParam + NotFound> Filter for MethodIdFilter<T> {
     fn filter_request<'a>(
         self: Arc<Self>,
         req: http::Request<&'a [u8]>,
         params: HashMap<String, String>,
     ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
         Box::pin(async move {
-            if req.method() == "POST" && params.contains_key(&T::id()) {
+            let has_id = params.contains_key(&T::id());
+            let allowed = match self.policy {
+                IdPolicy::Required => has_id,
+                IdPolicy::Forbidden => !has_id,
+            };
+
+            if req.method() == self.method && !allowed {
                 return Ok(RequestFilterOutcome::Fail(T::not_found()?));
             }
             Ok(RequestFilterOutcome::Pass(req, params))
@@ -61,17 +97,49 @@ impl<T: IdParam + NotFound> Filter for PostFilter<T> {
     }
 }
 
-/// A filter for PUT requests that only allow the requests through if the route parameters dont
+/// A filter for POST requests that only allow the requests through if the route parameters dont
+/// contain the ID param.
+pub struct PostFilter<T> {
+    inner: Arc<MethodIdFilter<T>>,
+}
+
+impl<T> PostFilter<T> {
+    /// Create a new PostFilter
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(MethodIdFilter::new(http::Method::POST, IdPolicy::Forbidden)),
+        }
+    }
+}
+
+impl<T: IdParam + NotFound> Filter for PostFilter<T> {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        self.inner.clone().filter_request(req, params)
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        self.inner.clone().filter_response(res)
+    }
+}
+
+/// A filter for PUT requests that only allow the requests through if the route parameters
 /// contain the ID param.
 pub struct PutFilter<T> {
-    phantom: std::marker::PhantomData<T>,
+    inner: Arc<MethodIdFilter<T>>,
 }
 
 impl<T> PutFilter<T> {
     /// Create a new PutFilter
     pub fn new() -> Self {
         Self {
-            phantom: std::marker::PhantomData,
+            inner: Arc::new(MethodIdFilter::new(http::Method::PUT, IdPolicy::Required)),
         }
     }
 }
@@ -82,19 +150,81 @@ impl<T: IdParam + NotFound> Filter for PutFilter<T> {
         req: http::Request<&'a [u8]>,
         params: HashMap<String, String>,
     ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
-        Box::pin(async move {
-            if req.method() == "PUT" && !params.contains_key(&T::id()) {
-                return Ok(RequestFilterOutcome::Fail(T::not_found()?));
-            }
-            Ok(RequestFilterOutcome::Pass(req, params))
-        })
+        self.inner.clone().filter_request(req, params)
     }
 
     fn filter_response<'a>(
         self: Arc<Self>,
         res: http::Response<Vec<u8>>,
     ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
-        Box::pin(async move { Ok(ResponseFilterOutcome::Pass(res)) })
+        self.inner.clone().filter_response(res)
+    }
+}
+
+/// A filter for PATCH requests that only allow the requests through if the route parameters
+/// contain the ID param.
+pub struct PatchFilter<T> {
+    inner: Arc<MethodIdFilter<T>>,
+}
+
+impl<T> PatchFilter<T> {
+    /// Create a new PatchFilter
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(MethodIdFilter::new(http::Method::PATCH, IdPolicy::Required)),
+        }
+    }
+}
+
+impl<T: IdParam + NotFound> Filter for PatchFilter<T> {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        self.inner.clone().filter_request(req, params)
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        self.inner.clone().filter_response(res)
+    }
+}
+
+/// A filter for DELETE requests that only allow the requests through if the route parameters
+/// contain the ID param.
+pub struct DeleteFilter<T> {
+    inner: Arc<MethodIdFilter<T>>,
+}
+
+impl<T> DeleteFilter<T> {
+    /// Create a new DeleteFilter
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(MethodIdFilter::new(
+                http::Method::DELETE,
+                IdPolicy::Required,
+            )),
+        }
+    }
+}
+
+impl<T: IdParam + NotFound> Filter for DeleteFilter<T> {
+    fn filter_request<'a>(
+        self: Arc<Self>,
+        req: http::Request<&'a [u8]>,
+        params: HashMap<String, String>,
+    ) -> BoxFuture<'a, anyhow::Result<RequestFilterOutcome<'a>>> {
+        self.inner.clone().filter_request(req, params)
+    }
+
+    fn filter_response<'a>(
+        self: Arc<Self>,
+        res: http::Response<Vec<u8>>,
+    ) -> BoxFuture<'a, anyhow::Result<ResponseFilterOutcome>> {
+        self.inner.clone().filter_response(res)
     }
 }
 
@@ -106,10 +236,32 @@ pub fn default_filters<T: IdParam + NotFound + 'static>() -> Vec<Arc<dyn Filter>
     ]
 }
 
+/// Default filters for controllers that also support partial updates through PATCH
+pub fn full_crud_filters<T: IdParam + NotFound + 'static>() -> Vec<Arc<dyn Filter>> {
+    vec![
+        Arc::new(PutFilter::<T>::new()),
+        Arc::new(PostFilter::<T>::new()),
+        Arc::new(PatchFilter::<T>::new()),
+    ]
+}
+
+/// Filters enforcing REST semantics on the ID param for every method of a CRUD controller
+///
+/// POST must not carry the ID param, PUT, PATCH and DELETE must carry it, and GET is let through
+/// either way to serve both the collection and a single item.
+pub fn rest_filters<T: IdParam + NotFound + 'static>() -> Vec<Arc<dyn Filter>> {
+    vec![
+        Arc::new(PutFilter::<T>::new()),
+        Arc::new(PostFilter::<T>::new()),
+        Arc::new(PatchFilter::<T>::new()),
+        Arc::new(DeleteFilter::<T>::new()),
+    ]
+}
+
 // -------------------
 
 /// Generic reusable wrapper with an id field around an entity.
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct EntityWithId<T: serde::Serialize> {
     /// The id of the entity
     pub id: u64,
@@ -125,6 +277,7 @@ impl<T: serde::Serialize + for<'de> serde::Deserialize<'de>> EntityWithId<T> {
     /// ```
     /// use rustserve_platform::EntityWithId;
     ///
+    /// #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
     /// struct Test {
     ///     name: String,
     /// }
@@ -132,7 +285,10 @@ impl<T: serde::Serialize + for<'de> serde::Deserialize<'de>> EntityWithId<T> {
     /// let id = 1;
     /// let entity = Test { name: String::new() };
     ///
-    /// assert_eq!(EntityWithId::new(id, entity), EntityWithId { id: 1, entity });
+    /// assert_eq!(
+    ///     EntityWithId::new(id, entity),
+    ///     EntityWithId { id: 1, entity: Test { name: String::new() } },
+    /// );
     /// ```
     pub fn new(id: u64, entity: T) -> Self {
         Self { id, entity }
@@ -142,13 +298,17 @@ impl<T: serde::Serialize + for<'de> serde::Deserialize<'de>> EntityWithId<T> {
 // -------------------
 
 /// General reusable paginated entity response.
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct SeqApiResponse<T: serde::Serialize> {
     total: usize,
     count: usize,
     offset: usize,
     entity_name: String,
     entities: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prev: Option<String>,
 }
 
 impl<'a, T: serde::Serialize> SeqApiResponse<Vec<T>> {
@@ -161,23 +321,30 @@ impl<'a, T: serde::Serialize> SeqApiResponse<Vec<T>> {
     /// ```
     /// use rustserve_platform::SeqApiResponse;
     ///
+    /// #[derive(Debug, PartialEq, serde::Serialize)]
     /// struct TestEntity {
     ///     id: u64,
     /// }
-    /// let entity_name = "users".into();
     /// let offset = 0;
-    /// let total = 0;
+    /// let total = 2;
     /// let entities = vec![TestEntity { id: 1 }, TestEntity { id: 2 }];
     ///
-    /// let result = SeqApiResponse::new(entity_name, offset, total, entities);
+    /// let result = SeqApiResponse::new("users", offset, total, entities);
     ///
-    /// assert_eq!(result, SeqApiResponse {
-    ///     total: 0,
-    ///     count: 2,
-    ///     offset: 0,
-    ///     entity_name,
-    ///     entities,
-    /// });
+    /// assert_eq!(
+    ///     result,
+    ///     SeqApiResponse::new("users", 0, 2, vec![TestEntity { id: 1 }, TestEntity { id: 2 }]),
+    /// );
+    /// assert_eq!(
+    ///     serde_json::to_value(&result).unwrap(),
+    ///     serde_json::json!({
+    ///         "total": 2,
+    ///         "count": 2,
+    ///         "offset": 0,
+    ///         "entity_name": "users",
+    ///         "entities": [{ "id": 1 }, { "id": 2 }],
+    ///     }),
+    /// );
     /// ```
     pub fn new(
         entity_name: impl Into<String>,
@@ -185,155 +352,1598 @@ impl<'a, T: serde::Serialize> SeqApiResponse<Vec<T>> {
         total: usize,
         entities: Vec<T>,
     ) -> Self {
-        Self {
-            total,
-            count: entities.len(),
-            offset,
-            entity_name: entity_name.into(),
-            entities,
-        }
+        Self::with_count(entity_name, offset, total, entities.len(), entities)
     }
-}
-
-/// Generic reusable entity response.
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct ApiResponse<T: serde::Serialize> {
-    /// Name of entity type
-    pub entity_name: String,
-    /// Inner entity stored within the response
-    pub entity: T,
-}
 
-impl<'a, T: serde::Serialize> ApiResponse<T> {
-    /// Creates a new [`ApiResponse<T>`].
+    /// Creates a new [`SeqApiResponse<T>`] by collecting `entities`, such as the results of an
+    /// iterator adapter.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustserve_platform::ApiResponse;
+    /// use rustserve_platform::SeqApiResponse;
     ///
-    /// struct TestEntity {
-    ///     id: u64,
-    /// }
-    /// let entity_name = "tests".into();
-    /// let entity = TestEntity { id: 1 };
+    /// let names = ["ada", "alan", "grace"];
+    /// let result = SeqApiResponse::from_iter("users", 0, 3, names.iter().map(|name| name.len()));
     ///
-    /// assert_eq!(ApiResponse::new(entity_name, entity), ApiResponse { entity_name, entity });
+    /// assert_eq!(result.entities(), &vec![3, 4, 5]);
     /// ```
-    pub fn new(entity_name: impl Into<String>, entity: T) -> Self {
-        Self {
-            entity_name: entity_name.into(),
-            entity,
-        }
+    pub fn from_iter(
+        entity_name: impl Into<String>,
+        offset: usize,
+        total: usize,
+        entities: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self::new(entity_name, offset, total, entities.into_iter().collect())
     }
 }
 
-// -------------------
-
-/// General reusable invalid parameter error
-#[derive(serde::Serialize)]
-pub struct InvalidParameterError {
-    param: String,
-    value: String,
-    error: String,
+impl<'a, T: serde::Serialize> SeqApiResponse<&'a [T]> {
+    /// Creates a new [`SeqApiResponse<T>`] borrowing `entities`, avoiding a copy when the page is
+    /// already held in a slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::SeqApiResponse;
+    ///
+    /// let ids = vec![1, 2, 3, 4];
+    /// let result = SeqApiResponse::from_slice("users", 2, 4, &ids[2..]);
+    ///
+    /// assert_eq!(result.count(), 2);
+    /// assert_eq!(
+    ///     serde_json::to_value(&result)?,
+    ///     serde_json::json!({
+    ///         "total": 4,
+    ///         "count": 2,
+    ///         "offset": 2,
+    ///         "entity_name": "users",
+    ///         "entities": [3, 4],
+    ///     }),
+    /// );
+    /// # Ok::<_, serde_json::Error>(())
+    /// ```
+    pub fn from_slice(
+        entity_name: impl Into<String>,
+        offset: usize,
+        total: usize,
+        entities: &'a [T],
+    ) -> Self {
+        Self::with_count(entity_name, offset, total, entities.len(), entities)
+    }
 }
 
-impl InvalidParameterError {
-    /// Construct a new instance of the InvalidParameterError struct with a predefined error
-    /// message.
-    pub fn new(param: impl Into<String>, value: impl Into<String>) -> Self {
-        Self {
-            param: param.into(),
-            value: value.into(),
-            error: "invalid parameter".into(),
-        }
+impl<T: serde::Serialize> SeqApiResponse<VecDeque<T>> {
+    /// Creates a new [`SeqApiResponse<T>`] of the entities in a [`VecDeque`], such as a buffer
+    /// entities are popped from as they are paged through.
+    pub fn from_deque(
+        entity_name: impl Into<String>,
+        offset: usize,
+        total: usize,
+        entities: VecDeque<T>,
+    ) -> Self {
+        Self::with_count(entity_name, offset, total, entities.len(), entities)
     }
 }
 
-/// General reusable invalid payload error
-#[derive(serde::Serialize)]
-pub struct InvalidPayloadError {
-    message: String,
-    error: String,
-}
+impl<T: serde::Serialize> SeqApiResponse<Vec<EntityWithId<T>>> {
+    /// Creates a new [`SeqApiResponse<T>`] of entities with their ids, such as the result of a
+    /// bulk create, from `(id, entity)` pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::SeqApiResponse;
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct User {
+    ///     name: String,
+    /// }
+    ///
+    /// let created = vec![(7, User { name: "ada".into() }), (8, User { name: "alan".into() })];
+    /// let result = SeqApiResponse::from_entities_with_ids("users", 0, 2, created);
+    ///
+    /// assert_eq!(
+    ///     serde_json::to_value(&result).unwrap(),
+    ///     serde_json::json!({
+    ///         "total": 2,
+    ///         "count": 2,
+    ///         "offset": 0,
+    ///         "entity_name": "users",
+    ///         "entities": [{ "id": 7, "name": "ada" }, { "id": 8, "name": "alan" }],
+    ///     }),
+    /// );
+    /// ```
+    pub fn from_entities_with_ids(
+        entity_name: impl Into<String>,
+        offset: usize,
+        total: usize,
+        entities: impl IntoIterator<Item = (u64, T)>,
+    ) -> Self {
+        let entities = entities
+            .into_iter()
+            .map(|(id, entity)| EntityWithId { id, entity })
+            .collect();
 
-impl InvalidPayloadError {
-    /// Construct a new instance of the InvalidPayloadError struct with a predefined error
-    /// message.
-    pub fn new(message: impl Into<String>) -> Self {
-        Self {
-            message: message.into(),
-            error: "invalid payload".into(),
-        }
+        Self::new(entity_name, offset, total, entities)
     }
 }
 
-/// General reusable missing parameter error
-#[derive(serde::Serialize)]
-pub struct MissingParameterError {
-    param: String,
-    error: String,
-}
-
-impl MissingParameterError {
-    /// Construct a new instance of the MissingParameterError struct with a predefined error
-    /// message.
-    pub fn new(param: impl Into<String>) -> Self {
+impl<T: serde::Serialize> SeqApiResponse<T> {
+    fn with_count(
+        entity_name: impl Into<String>,
+        offset: usize,
+        total: usize,
+        count: usize,
+        entities: T,
+    ) -> Self {
         Self {
-            param: param.into(),
-            error: "missing parameter".into(),
+            total,
+            count,
+            offset,
+            entity_name: entity_name.into(),
+            entities,
+            next: None,
+            prev: None,
         }
     }
-}
 
-/// General reusable service unavailable error
-#[derive(serde::Serialize)]
-pub struct ServiceUnavailableError {
-    error: String,
-}
+    /// The total number of entities across all pages.
+    pub fn total(&self) -> usize {
+        self.total
+    }
 
-impl ServiceUnavailableError {
-    /// Construct a new instance of the ServiceUnavailableError struct with a predefined error
-    /// message.
-    pub fn new() -> Self {
-        Self {
-            error: "service unavailable".into(),
-        }
+    /// The number of entities in this page.
+    pub fn count(&self) -> usize {
+        self.count
     }
-}
 
-/// General reusable entity not found error
-#[derive(serde::Serialize)]
-pub struct EntityNotFoundError {
-    id: u64,
-    entity: String,
-    error: String,
-}
+    /// The position of the first entity of this page among all entities.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 
-impl EntityNotFoundError {
-    /// Construct a new instance of the EntityNotFound struct with a predefined error
-    /// message.
-    pub fn new(entity: impl Into<String>, id: u64) -> Self {
-        Self {
-            id,
-            entity: entity.into(),
-            error: "entity not found".into(),
-        }
+    /// Name of entity type
+    pub fn entity_name(&self) -> &str {
+        &self.entity_name
     }
-}
 
-/// General reusable internal server error
-#[derive(serde::Serialize)]
-pub struct InternalServerError {
-    error: String,
-}
+    /// The entities in this page.
+    pub fn entities(&self) -> &T {
+        &self.entities
+    }
 
-impl InternalServerError {
-    /// Construct a new instance of the InternalServerError struct with a predefined error
+    /// Take the entities in this page out of the response.
+    pub fn into_entities(self) -> T {
+        self.entities
+    }
+
+    /// Whether there are more entities after this page.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::SeqApiResponse;
+    ///
+    /// let page = SeqApiResponse::new("users", 0, 3, vec![1, 2]);
+    ///
+    /// assert!(page.has_more());
+    /// assert!(!SeqApiResponse::new("users", 2, 3, vec![3]).has_more());
+    /// ```
+    pub fn has_more(&self) -> bool {
+        self.offset + self.count < self.total
+    }
+
+    /// The offset of the next page, or `None` at the last page.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::SeqApiResponse;
+    ///
+    /// assert_eq!(SeqApiResponse::new("users", 0, 3, vec![1, 2]).next_offset(), Some(2));
+    /// assert_eq!(SeqApiResponse::new("users", 2, 3, vec![3]).next_offset(), None);
+    /// ```
+    pub fn next_offset(&self) -> Option<usize> {
+        self.has_more().then(|| self.offset + self.count)
+    }
+
+    /// Fill in the URLs of the next and previous pages of `limit` entities under `base_path`,
+    /// leaving them out at the first and last page.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::SeqApiResponse;
+    ///
+    /// let page = SeqApiResponse::new("users", 10, 25, vec![0; 10]).with_links("/users", 10);
+    ///
+    /// assert_eq!(page.next(), Some("/users?offset=20&limit=10"));
+    /// assert_eq!(page.prev(), Some("/users?offset=0&limit=10"));
+    /// ```
+    pub fn with_links(mut self, base_path: &str, limit: usize) -> Self {
+        let separator = if base_path.contains('?') { '&' } else { '?' };
+        let page_url =
+            |offset: usize| format!("{base_path}{separator}offset={offset}&limit={limit}");
+
+        self.next =
+            (limit > 0 && self.offset + limit < self.total).then(|| page_url(self.offset + limit));
+        self.prev =
+            (limit > 0 && self.offset > 0).then(|| page_url(self.offset.saturating_sub(limit)));
+        self
+    }
+
+    /// The URL of the next page, if filled in by [`SeqApiResponse::with_links`].
+    pub fn next(&self) -> Option<&str> {
+        self.next.as_deref()
+    }
+
+    /// The URL of the previous page, if filled in by [`SeqApiResponse::with_links`].
+    pub fn prev(&self) -> Option<&str> {
+        self.prev.as_deref()
+    }
+
+    /// Serialize the response as JSON into a `200 OK` response with `Content-Type` and
+    /// `Content-Length` set, see [`ApiResponse::into_http_response`].
+    pub fn into_http_response(self) -> anyhow::Result<http::Response<Vec<u8>>> {
+        ok_json_response(self)
+    }
+}
+
+/// The page size [`SeqApiResponseBuilder`] uses when the request doesn't ask for one, unless
+/// configured otherwise.
+pub const DEFAULT_PAGE_LIMIT: usize = 20;
+
+/// The largest page size [`SeqApiResponseBuilder`] accepts unless configured otherwise.
+pub const DEFAULT_MAX_PAGE_LIMIT: usize = 100;
+
+/// Builds a [`SeqApiResponse<T>`] for the page requested through the `offset` and `limit` query
+/// parameters, see [`SeqApiResponse::builder`].
+pub struct SeqApiResponseBuilder<T> {
+    entity_name: String,
+    offset: usize,
+    limit: Option<usize>,
+    default_limit: usize,
+    max_limit: usize,
+    entities: PhantomData<fn() -> T>,
+}
+
+impl<T: serde::Serialize> SeqApiResponse<Vec<T>> {
+    /// Start building a page of `entity_name` entities, starting at offset `0` with
+    /// [`DEFAULT_PAGE_LIMIT`] entities unless the query asks otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use rustserve_platform::SeqApiResponse;
+    ///
+    /// let query = HashMap::from([("offset".to_string(), "40".to_string())]);
+    /// let page = SeqApiResponse::builder("users").with_query(&query).unwrap();
+    ///
+    /// assert_eq!((page.offset(), page.limit()), (40, 20));
+    ///
+    /// // Fetch `page.limit()` users starting at `page.offset()`.
+    /// let users = vec!["ada", "alan"];
+    /// let response = page.build(42, users);
+    ///
+    /// assert_eq!(response.count(), 2);
+    /// assert!(!response.has_more());
+    /// ```
+    pub fn builder(entity_name: impl Into<String>) -> SeqApiResponseBuilder<T> {
+        SeqApiResponseBuilder {
+            entity_name: entity_name.into(),
+            offset: 0,
+            limit: None,
+            default_limit: DEFAULT_PAGE_LIMIT,
+            max_limit: DEFAULT_MAX_PAGE_LIMIT,
+            entities: PhantomData,
+        }
+    }
+}
+
+impl<T: serde::Serialize> SeqApiResponseBuilder<T> {
+    /// Use `default_limit` when the query doesn't set a limit.
+    pub fn with_default_limit(mut self, default_limit: usize) -> Self {
+        self.default_limit = default_limit;
+        self
+    }
+
+    /// Reject limits above `max_limit`, which also caps the default limit.
+    pub fn with_max_limit(mut self, max_limit: usize) -> Self {
+        self.max_limit = max_limit;
+        self
+    }
+
+    /// Read the page from the `offset` and `limit` parameters in `query`.
+    ///
+    /// Offsets that aren't a non-negative integer and limits that aren't between `1` and the max
+    /// limit are rejected naming the parameter.
+    pub fn with_query(
+        mut self,
+        query: &HashMap<String, String>,
+    ) -> Result<Self, InvalidParameterError> {
+        let parse = |param: &str| {
+            query
+                .get(param)
+                .map(|value| {
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| InvalidParameterError::new(param, value.as_str()))
+                })
+                .transpose()
+        };
+
+        if let Some(offset) = parse("offset")? {
+            self.offset = offset;
+        }
+
+        if let Some(limit) = parse("limit")? {
+            if limit == 0 || limit > self.max_limit {
+                return Err(InvalidParameterError::new("limit", limit.to_string()));
+            }
+            self.limit = Some(limit);
+        }
+
+        Ok(self)
+    }
+
+    /// The position of the first entity of the page among all entities.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// How many entities the page holds at most.
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(self.default_limit).min(self.max_limit)
+    }
+
+    /// Finish the page with its `entities` out of `total` entities overall.
+    pub fn build(self, total: usize, entities: Vec<T>) -> SeqApiResponse<Vec<T>> {
+        SeqApiResponse::new(self.entity_name, self.offset, total, entities)
+    }
+}
+
+/// General reusable cursor paginated entity response.
+///
+/// Like a [`SeqApiResponse<T>`] but paginated by opaque cursors, such as the key of the last
+/// entity in the page, instead of offsets.
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CursorApiResponse<T: serde::Serialize> {
+    entity_name: String,
+    entities: T,
+    next_cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prev_cursor: Option<String>,
+}
+
+impl<T: serde::Serialize> CursorApiResponse<Vec<T>> {
+    /// Creates a new [`CursorApiResponse<T>`].
+    ///
+    /// `next_cursor` is `None` on the last page.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::CursorApiResponse;
+    ///
+    /// #[derive(Debug, PartialEq, serde::Serialize)]
+    /// struct TestEntity {
+    ///     id: u64,
+    /// }
+    /// let entities = vec![TestEntity { id: 1 }, TestEntity { id: 2 }];
+    ///
+    /// let result = CursorApiResponse::new("users", entities, Some("2".into()));
+    ///
+    /// assert_eq!(
+    ///     serde_json::to_value(&result).unwrap(),
+    ///     serde_json::json!({
+    ///         "entity_name": "users",
+    ///         "entities": [{ "id": 1 }, { "id": 2 }],
+    ///         "next_cursor": "2",
+    ///     }),
+    /// );
+    /// ```
+    pub fn new(
+        entity_name: impl Into<String>,
+        entities: Vec<T>,
+        next_cursor: Option<String>,
+    ) -> Self {
+        Self {
+            entity_name: entity_name.into(),
+            entities,
+            next_cursor,
+            prev_cursor: None,
+        }
+    }
+}
+
+impl<T: serde::Serialize> CursorApiResponse<T> {
+    /// Set the cursor of the previous page.
+    pub fn with_prev_cursor(mut self, prev_cursor: impl Into<String>) -> Self {
+        self.prev_cursor = Some(prev_cursor.into());
+        self
+    }
+
+    /// Name of entity type
+    pub fn entity_name(&self) -> &str {
+        &self.entity_name
+    }
+
+    /// The entities in this page.
+    pub fn entities(&self) -> &T {
+        &self.entities
+    }
+
+    /// Take the entities in this page out of the response.
+    pub fn into_entities(self) -> T {
+        self.entities
+    }
+
+    /// The cursor of the next page, `None` on the last page.
+    pub fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_deref()
+    }
+
+    /// The cursor of the previous page, if known.
+    pub fn prev_cursor(&self) -> Option<&str> {
+        self.prev_cursor.as_deref()
+    }
+
+    /// Serialize the response as JSON into a `200 OK` response with `Content-Type` and
+    /// `Content-Length` set, see [`ApiResponse::into_http_response`].
+    pub fn into_http_response(self) -> anyhow::Result<http::Response<Vec<u8>>> {
+        ok_json_response(self)
+    }
+}
+
+/// Generic reusable entity response.
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ApiResponse<T: serde::Serialize> {
+    /// Name of entity type
+    pub entity_name: String,
+    /// Inner entity stored within the response
+    pub entity: T,
+    /// Hypermedia links to the entity and related resources by relation name, serialized as
+    /// `_links` when not empty
+    #[serde(rename = "_links", default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub links: BTreeMap<String, String>,
+}
+
+impl<'a, T: serde::Serialize> ApiResponse<T> {
+    /// Creates a new [`ApiResponse<T>`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::ApiResponse;
+    ///
+    /// #[derive(Debug, PartialEq, serde::Serialize)]
+    /// struct TestEntity {
+    ///     id: u64,
+    /// }
+    /// let entity = TestEntity { id: 1 };
+    ///
+    /// assert_eq!(
+    ///     ApiResponse::new("tests", entity),
+    ///     ApiResponse {
+    ///         entity_name: "tests".into(),
+    ///         entity: TestEntity { id: 1 },
+    ///         links: Default::default(),
+    ///     },
+    /// );
+    /// ```
+    pub fn new(entity_name: impl Into<String>, entity: T) -> Self {
+        Self {
+            entity_name: entity_name.into(),
+            entity,
+            links: BTreeMap::new(),
+        }
+    }
+
+    /// Add a link to `href` for the relation `rel`, replacing an earlier link for `rel`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::ApiResponse;
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Post {
+    ///     id: u64,
+    /// }
+    ///
+    /// let response = ApiResponse::new("posts", Post { id: 1 })
+    ///     .with_link("self", "/posts/1")
+    ///     .with_link("author", "/users/7");
+    ///
+    /// assert_eq!(
+    ///     serde_json::to_value(&response).unwrap(),
+    ///     serde_json::json!({
+    ///         "entity_name": "posts",
+    ///         "entity": { "id": 1 },
+    ///         "_links": { "author": "/users/7", "self": "/posts/1" },
+    ///     }),
+    /// );
+    /// ```
+    pub fn with_link(mut self, rel: impl Into<String>, href: impl Into<String>) -> Self {
+        self.links.insert(rel.into(), href.into());
+        self
+    }
+
+    /// Serialize the response as JSON into a `200 OK` response with `Content-Type` and
+    /// `Content-Length` set.
+    ///
+    /// Use [`serialize_response`] instead to answer clients preferring MessagePack, and
+    /// [`PlatformError::into_response`] for error responses.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::ApiResponse;
+    ///
+    /// let res = ApiResponse::new("users", 1).into_http_response().unwrap();
+    ///
+    /// assert_eq!(res.status(), http::StatusCode::OK);
+    /// assert_eq!(res.headers()["content-type"], "application/json");
+    /// assert_eq!(res.headers()["content-length"], res.body().len().to_string().as_str());
+    /// ```
+    pub fn into_http_response(self) -> anyhow::Result<http::Response<Vec<u8>>> {
+        ok_json_response(self)
+    }
+}
+
+/// The content type of JSON response bodies.
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// The content type of JSON error responses built by [`PlatformError::into_response`], stating
+/// the charset explicitly for clients that don't default JSON to UTF-8.
+pub const JSON_UTF8_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+
+/// The content type of MessagePack response bodies.
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// Serialize `entity` as MessagePack if the `Accept` header in `headers` prefers it, or as JSON
+/// otherwise, returning the body and its content type.
+///
+/// JSON is used when there is no `Accept` header or it accepts any type.
+///
+/// # Examples
+///
+/// ```
+/// use http::header::{HeaderMap, HeaderValue, ACCEPT};
+/// use rustserve_platform::{serialize_response, ApiResponse, MSGPACK_CONTENT_TYPE};
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert(ACCEPT, HeaderValue::from_static("application/msgpack"));
+///
+/// let (_, content_type) = serialize_response(&ApiResponse::new("users", 1), &headers).unwrap();
+///
+/// assert_eq!(content_type, MSGPACK_CONTENT_TYPE);
+/// ```
+pub fn serialize_response<T: serde::Serialize>(
+    entity: &T,
+    headers: &http::HeaderMap,
+) -> anyhow::Result<(Vec<u8>, &'static str)> {
+    if prefers_msgpack(headers) {
+        Ok((rmp_serde::to_vec_named(entity)?, MSGPACK_CONTENT_TYPE))
+    } else {
+        Ok((serde_json::to_vec(entity)?, JSON_CONTENT_TYPE))
+    }
+}
+
+// Whether the first media type accepted, in order of quality, is MessagePack rather than JSON.
+fn prefers_msgpack(headers: &http::HeaderMap) -> bool {
+    let mut accepted = headers
+        .get_all(http::header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_range| {
+            let mut parts = media_range.split(';');
+            let media_type = parts.next()?.trim().to_ascii_lowercase();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((media_type, quality))
+        })
+        .filter(|(media_type, quality)| {
+            *quality > 0.0
+                && matches!(
+                    media_type.as_str(),
+                    "application/json" | "application/msgpack" | "application/x-msgpack"
+                )
+        })
+        .collect::<Vec<_>>();
+
+    // Stable, so equally preferred types keep the order the client listed them in.
+    accepted.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    accepted
+        .first()
+        .is_some_and(|(media_type, _)| media_type.ends_with("msgpack"))
+}
+
+// -------------------
+
+/// General reusable invalid parameter error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InvalidParameterError {
+    param: String,
+    value: String,
+    error: String,
+}
+
+impl InvalidParameterError {
+    /// Construct a new instance of the InvalidParameterError struct with a predefined error
+    /// message.
+    pub fn new(param: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            param: param.into(),
+            value: value.into(),
+            error: "invalid parameter".into(),
+        }
+    }
+
+    /// The name of the invalid parameter.
+    pub fn param(&self) -> &str {
+        &self.param
+    }
+
+    /// The value the parameter was given.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable invalid payload error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InvalidPayloadError {
+    message: String,
+    error: String,
+}
+
+impl InvalidPayloadError {
+    /// Construct a new instance of the InvalidPayloadError struct with a predefined error
+    /// message.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            error: "invalid payload".into(),
+        }
+    }
+
+    /// What is wrong with the payload.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable payload too large error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PayloadTooLargeError {
+    limit: usize,
+    error: String,
+}
+
+impl PayloadTooLargeError {
+    /// Construct a new instance of the PayloadTooLargeError struct with a predefined error
+    /// message.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            error: "payload too large".into(),
+        }
+    }
+
+    /// The largest payload accepted, in bytes.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable missing parameter error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MissingParameterError {
+    param: String,
+    error: String,
+}
+
+impl MissingParameterError {
+    /// Construct a new instance of the MissingParameterError struct with a predefined error
+    /// message.
+    pub fn new(param: impl Into<String>) -> Self {
+        Self {
+            param: param.into(),
+            error: "missing parameter".into(),
+        }
+    }
+
+    /// The name of the missing parameter.
+    pub fn param(&self) -> &str {
+        &self.param
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable service unavailable error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceUnavailableError {
+    error: String,
+}
+
+impl ServiceUnavailableError {
+    /// Construct a new instance of the ServiceUnavailableError struct with a predefined error
+    /// message.
+    pub fn new() -> Self {
+        Self {
+            error: "service unavailable".into(),
+        }
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable gateway timeout error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GatewayTimeoutError {
+    error: String,
+}
+
+impl GatewayTimeoutError {
+    /// Construct a new instance of the GatewayTimeoutError struct with a predefined error
+    /// message.
+    pub fn new() -> Self {
+        Self {
+            error: "gateway timeout".into(),
+        }
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable unauthorized error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnauthorizedError {
+    error: String,
+}
+
+impl UnauthorizedError {
+    /// Construct a new instance of the UnauthorizedError struct with a predefined error
+    /// message.
+    pub fn new() -> Self {
+        Self {
+            error: "unauthorized".into(),
+        }
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// A single field that failed validation, reported as part of a [`ValidationError`].
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldError {
+    /// The name of the field
+    pub field: String,
+    /// The value the field was given
+    pub value: String,
+    /// What is wrong with the value
+    pub message: String,
+}
+
+/// General reusable validation error, reporting every field that failed validation at once
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::ValidationError;
+///
+/// let error = ValidationError::new()
+///     .add_field("email", "bob", "must be an email address")
+///     .add_field("age", "-1", "must not be negative");
+///
+/// assert_eq!(
+///     serde_json::to_value(&error).unwrap()["fields"][1],
+///     serde_json::json!({ "field": "age", "value": "-1", "message": "must not be negative" }),
+/// );
+/// ```
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidationError {
+    error: String,
+    fields: Vec<FieldError>,
+}
+
+impl ValidationError {
+    /// Construct a new instance of the ValidationError struct with a predefined error message
+    /// and no failed fields.
+    pub fn new() -> Self {
+        Self::from_fields(Vec::new())
+    }
+
+    /// Construct a new instance of the ValidationError struct with a predefined error message
+    /// reporting `fields`.
+    pub fn from_fields(fields: Vec<FieldError>) -> Self {
+        Self {
+            error: "validation failed".into(),
+            fields,
+        }
+    }
+
+    /// Report that `field` with `value` failed validation because of `message`.
+    pub fn add_field(
+        mut self,
+        field: impl Into<String>,
+        value: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.fields.push(FieldError {
+            field: field.into(),
+            value: value.into(),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Whether no field failed validation.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The fields that failed validation.
+    pub fn fields(&self) -> &[FieldError] {
+        &self.fields
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable bad request error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BadRequestError {
+    message: String,
+    error: String,
+}
+
+impl BadRequestError {
+    /// Construct a new instance of the BadRequestError struct with a predefined error
+    /// message.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            error: "bad request".into(),
+        }
+    }
+
+    /// What is wrong with the request.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable forbidden error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForbiddenError {
+    error: String,
+}
+
+impl ForbiddenError {
+    /// Construct a new instance of the ForbiddenError struct with a predefined error
+    /// message.
+    pub fn new() -> Self {
+        Self {
+            error: "forbidden".into(),
+        }
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable not found error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotFoundError {
+    path: String,
+    error: String,
+}
+
+impl NotFoundError {
+    /// Construct a new instance of the NotFoundError struct with a predefined error
+    /// message.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            error: "not found".into(),
+        }
+    }
+
+    /// The path that was not found.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable method not allowed error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MethodNotAllowedError {
+    method: String,
+    error: String,
+}
+
+impl MethodNotAllowedError {
+    /// Construct a new instance of the MethodNotAllowedError struct with a predefined error
+    /// message.
+    pub fn new(method: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            error: "method not allowed".into(),
+        }
+    }
+
+    /// The method that is not allowed.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable conflict error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConflictError {
+    resource: String,
+    error: String,
+}
+
+impl ConflictError {
+    /// Construct a new instance of the ConflictError struct with a predefined error
+    /// message.
+    pub fn new(resource: impl Into<String>) -> Self {
+        Self {
+            resource: resource.into(),
+            error: "conflict".into(),
+        }
+    }
+
+    /// The resource the request conflicts with.
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable unsupported media type error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnsupportedMediaTypeError {
+    content_type: String,
+    error: String,
+}
+
+impl UnsupportedMediaTypeError {
+    /// Construct a new instance of the UnsupportedMediaTypeError struct with a predefined error
+    /// message.
+    pub fn new(content_type: impl Into<String>) -> Self {
+        Self {
+            content_type: content_type.into(),
+            error: "unsupported media type".into(),
+        }
+    }
+
+    /// The media type that is not supported.
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable not acceptable error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotAcceptableError {
+    acceptable: Vec<String>,
+    error: String,
+}
+
+impl NotAcceptableError {
+    /// Construct a new instance of the NotAcceptableError struct with a predefined error
+    /// message.
+    pub fn new(acceptable: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            acceptable: acceptable.into_iter().map(Into::into).collect(),
+            error: "not acceptable".into(),
+        }
+    }
+
+    /// The media types the response can be produced in.
+    pub fn acceptable(&self) -> &[String] {
+        &self.acceptable
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable too many requests error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TooManyRequestsError {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
+    error: String,
+}
+
+impl TooManyRequestsError {
+    /// Construct a new instance of the TooManyRequestsError struct with a predefined error
+    /// message, telling the client how many seconds to wait before retrying if known.
+    pub fn new(retry_after: Option<u64>) -> Self {
+        Self {
+            retry_after,
+            error: "too many requests".into(),
+        }
+    }
+
+    /// How many seconds the client should wait before retrying, if known.
+    pub fn retry_after(&self) -> Option<u64> {
+        self.retry_after
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable entity not found error
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityNotFoundError {
+    id: u64,
+    entity: String,
+    error: String,
+}
+
+impl EntityNotFoundError {
+    /// Construct a new instance of the EntityNotFound struct with a predefined error
     /// message.
-    pub fn new(error: impl Into<String>) -> Self {
+    pub fn new(entity: impl Into<String>, id: u64) -> Self {
+        Self {
+            id,
+            entity: entity.into(),
+            error: "entity not found".into(),
+        }
+    }
+
+    /// The kind of entity that was not found.
+    pub fn entity(&self) -> &str {
+        &self.entity
+    }
+
+    /// The id of the entity that was not found.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// General reusable internal server error
+///
+/// Only a generic message is sent to the client, the detail passed to
+/// [`InternalServerError::new`] is kept for logging on the server so internals such as queries or
+/// hostnames don't leak.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::InternalServerError;
+///
+/// let error = InternalServerError::new("connection to db-3.internal refused");
+///
+/// assert_eq!(error.detail(), "connection to db-3.internal refused");
+/// assert_eq!(
+///     serde_json::to_value(&error).unwrap(),
+///     serde_json::json!({ "error": "internal server error" }),
+/// );
+/// ```
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InternalServerError {
+    #[serde(skip)]
+    detail: String,
+    error: String,
+}
+
+impl InternalServerError {
+    /// Construct a new instance of the InternalServerError struct with a predefined error
+    /// message, keeping `detail` for the server only.
+    pub fn new(detail: impl Into<String>) -> Self {
         Self {
-            error: error.into(),
+            detail: detail.into(),
+            error: "internal server error".into(),
+        }
+    }
+
+    /// Send `message` to the client instead of the predefined error message.
+    pub fn with_public_message(mut self, message: impl Into<String>) -> Self {
+        self.error = message.into();
+        self
+    }
+
+    /// The detail of the error, never sent to the client.
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+
+    /// The error message sent to the client.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+// -------------------
+
+macro_rules! platform_error {
+    ($($(#[$doc:meta])* $variant:ident($error:ident) => $status:ident,)*) => {
+        /// Any of the general reusable errors, knowing which status code it is answered with.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use rustserve_platform::{EntityNotFoundError, PlatformError};
+        ///
+        /// let res = PlatformError::from(EntityNotFoundError::new("users", 1)).into_response();
+        ///
+        /// assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+        /// ```
+        pub enum PlatformError {
+            $($(#[$doc])* $variant($error),)*
+        }
+
+        impl PlatformError {
+            /// The status code the error is answered with.
+            pub fn status(&self) -> http::StatusCode {
+                match self {
+                    $(Self::$variant(_) => http::StatusCode::$status,)*
+                }
+            }
+
+            fn to_json(&self) -> serde_json::Result<Vec<u8>> {
+                match self {
+                    $(Self::$variant(error) => serde_json::to_vec(error),)*
+                }
+            }
+
+            fn body(&self) -> serde_json::Value {
+                match self {
+                    $(Self::$variant(error) => serde_json::to_value(error).unwrap_or_default(),)*
+                }
+            }
+        }
+
+        $(
+            impl From<$error> for PlatformError {
+                fn from(error: $error) -> Self {
+                    Self::$variant(error)
+                }
+            }
+
+            impl client::TypedError for $error {
+                fn expects_status(status: http::StatusCode) -> bool {
+                    status == http::StatusCode::$status
+                }
+            }
+        )*
+    };
+}
+
+platform_error! {
+    /// `400 Bad Request`
+    BadRequest(BadRequestError) => BAD_REQUEST,
+    /// `400 Bad Request`
+    InvalidParameter(InvalidParameterError) => BAD_REQUEST,
+    /// `400 Bad Request`
+    InvalidPayload(InvalidPayloadError) => BAD_REQUEST,
+    /// `400 Bad Request`
+    MissingParameter(MissingParameterError) => BAD_REQUEST,
+    /// `400 Bad Request`
+    Validation(ValidationError) => BAD_REQUEST,
+    /// `401 Unauthorized`
+    Unauthorized(UnauthorizedError) => UNAUTHORIZED,
+    /// `403 Forbidden`
+    Forbidden(ForbiddenError) => FORBIDDEN,
+    /// `404 Not Found`
+    EntityNotFound(EntityNotFoundError) => NOT_FOUND,
+    /// `404 Not Found`
+    NotFound(NotFoundError) => NOT_FOUND,
+    /// `405 Method Not Allowed`
+    MethodNotAllowed(MethodNotAllowedError) => METHOD_NOT_ALLOWED,
+    /// `406 Not Acceptable`
+    NotAcceptable(NotAcceptableError) => NOT_ACCEPTABLE,
+    /// `409 Conflict`
+    Conflict(ConflictError) => CONFLICT,
+    /// `413 Payload Too Large`
+    PayloadTooLarge(PayloadTooLargeError) => PAYLOAD_TOO_LARGE,
+    /// `415 Unsupported Media Type`
+    UnsupportedMediaType(UnsupportedMediaTypeError) => UNSUPPORTED_MEDIA_TYPE,
+    /// `429 Too Many Requests`
+    TooManyRequests(TooManyRequestsError) => TOO_MANY_REQUESTS,
+    /// `500 Internal Server Error`
+    InternalServer(InternalServerError) => INTERNAL_SERVER_ERROR,
+    /// `503 Service Unavailable`
+    ServiceUnavailable(ServiceUnavailableError) => SERVICE_UNAVAILABLE,
+    /// `504 Gateway Timeout`
+    GatewayTimeout(GatewayTimeoutError) => GATEWAY_TIMEOUT,
+}
+
+impl PlatformError {
+    /// Turn the error into a response with its status code and the error serialized as the JSON
+    /// body, sent as [`JSON_UTF8_CONTENT_TYPE`] along with its `Content-Length`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::{EntityNotFoundError, PlatformError};
+    ///
+    /// let res = PlatformError::from(EntityNotFoundError::new("users", 1)).into_response();
+    ///
+    /// assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+    /// assert_eq!(res.headers()["content-type"], "application/json; charset=utf-8");
+    /// assert_eq!(res.headers()["content-length"], res.body().len().to_string().as_str());
+    /// ```
+    pub fn into_response(self) -> http::Response<Vec<u8>> {
+        self.into_response_with_content_type(http::HeaderValue::from_static(JSON_UTF8_CONTENT_TYPE))
+    }
+
+    /// Turn the error into a response like [`PlatformError::into_response`], sent as
+    /// `content_type` instead.
+    pub fn into_response_with_content_type(
+        self,
+        content_type: http::HeaderValue,
+    ) -> http::Response<Vec<u8>> {
+        // The error structs only hold strings and numbers, which always serialize.
+        let body = self.to_json().expect("error serializes to JSON");
+
+        error_response(self.status(), body, content_type)
+    }
+
+    /// The error as an RFC 7807 problem details object.
+    ///
+    /// The predefined error message becomes the `detail` and the remaining fields of the error
+    /// are kept as extension members, `instance` identifies the occurrence of the problem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustserve_platform::{EntityNotFoundError, PlatformError};
+    ///
+    /// let error = PlatformError::from(EntityNotFoundError::new("users", 1));
+    ///
+    /// assert_eq!(
+    ///     serde_json::to_value(error.to_problem_json(Some("/users/1"))).unwrap(),
+    ///     serde_json::json!({
+    ///         "type": "about:blank",
+    ///         "title": "Not Found",
+    ///         "status": 404,
+    ///         "detail": "entity not found",
+    ///         "instance": "/users/1",
+    ///         "entity": "users",
+    ///         "id": 1,
+    ///     }),
+    /// );
+    /// ```
+    pub fn to_problem_json(&self, instance: Option<&str>) -> ProblemJson {
+        let status = self.status();
+
+        let mut extensions = match self.body() {
+            serde_json::Value::Object(fields) => fields,
+            _ => serde_json::Map::new(),
+        };
+        let detail = match extensions.remove("error") {
+            Some(serde_json::Value::String(detail)) => Some(detail),
+            _ => None,
+        };
+
+        ProblemJson {
+            problem_type: "about:blank".into(),
+            title: status.canonical_reason().unwrap_or_default().into(),
+            status: status.as_u16(),
+            detail,
+            instance: instance.map(String::from),
+            extensions,
+        }
+    }
+
+    /// Turn the error into a response like [`PlatformError::into_response`] with the body as
+    /// [`PROBLEM_JSON_CONTENT_TYPE`] instead, which is always UTF-8.
+    pub fn into_problem_response(self, instance: Option<&str>) -> http::Response<Vec<u8>> {
+        let problem = self.to_problem_json(instance);
+        let body = serde_json::to_vec(&problem).expect("problem details serialize to JSON");
+
+        error_response(
+            self.status(),
+            body,
+            http::HeaderValue::from_static(PROBLEM_JSON_CONTENT_TYPE),
+        )
+    }
+}
+
+// Respond with `status` and the serialized error in `body`, telling the client its length up
+// front.
+fn error_response(
+    status: http::StatusCode,
+    body: Vec<u8>,
+    content_type: http::HeaderValue,
+) -> http::Response<Vec<u8>> {
+    let content_length = body.len();
+
+    let mut res = http::Response::new(body);
+    *res.status_mut() = status;
+    res.headers_mut()
+        .insert(http::header::CONTENT_TYPE, content_type);
+    res.headers_mut()
+        .insert(http::header::CONTENT_LENGTH, content_length.into());
+    res
+}
+
+/// The content type of RFC 7807 problem details bodies.
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+
+/// An RFC 7807 problem details object, see [`PlatformError::to_problem_json`].
+#[derive(Debug, serde::Serialize)]
+pub struct ProblemJson {
+    /// A URI identifying the problem type
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// A short summary of the problem type
+    pub title: String,
+    /// The status code of the response
+    pub status: u16,
+    /// An explanation specific to this occurrence of the problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// A URI identifying this occurrence of the problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Additional members describing the problem
+    #[serde(flatten)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+// Respond with `status` and `body` serialized as JSON.
+pub(crate) fn json_response(
+    status: http::StatusCode,
+    body: impl serde::Serialize,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    Ok(http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, JSON_CONTENT_TYPE)
+        .body(serde_json::to_vec(&body)?)?)
+}
+
+// Respond with `error` and its status code like [`PlatformError::into_response`], for the errors
+// the runtime and the filters answer requests with.
+pub(crate) fn platform_error(error: impl Into<PlatformError>) -> http::Response<Vec<u8>> {
+    error.into().into_response()
+}
+
+// Respond with `200 OK` and `body` serialized as JSON, telling the client the length up front.
+fn ok_json_response(body: impl serde::Serialize) -> anyhow::Result<http::Response<Vec<u8>>> {
+    let mut res = json_response(http::StatusCode::OK, body)?;
+    let content_length = res.body().len();
+    res.headers_mut()
+        .insert(http::header::CONTENT_LENGTH, content_length.into());
+    Ok(res)
+}
+
+// Compare secrets without leaking how long a matching prefix is through timing.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// The credentials of an `Authorization` header using `scheme`, which is matched
+// case-insensitively.
+pub(crate) fn auth_credentials<'a>(
+    authorization: Option<&'a http::HeaderValue>,
+    scheme: &str,
+) -> Option<&'a str> {
+    let (sent, credentials) = authorization?.to_str().ok()?.split_once(' ')?;
+    sent.eq_ignore_ascii_case(scheme)
+        .then(|| credentials.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{filter_request, User};
+
+    fn request(method: http::Method) -> http::Request<&'static [u8]> {
+        http::Request::builder()
+            .method(method)
+            .uri("/users")
+            .body(&b""[..])
+            .unwrap()
+    }
+
+    fn with_id() -> HashMap<String, String> {
+        HashMap::from([(User::id(), "1".into())])
+    }
+
+    #[tokio::test]
+    async fn rejects_patch_without_an_id() {
+        let filter = Arc::new(PatchFilter::<User>::new());
+
+        let res = filter_request(&filter, request(http::Method::PATCH), HashMap::new())
+            .await
+            .expect_err("PATCH must target a single resource");
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn allows_patch_with_an_id() {
+        let filter = Arc::new(PatchFilter::<User>::new());
+
+        let (_, params) = filter_request(&filter, request(http::Method::PATCH), with_id())
+            .await
+            .expect("PATCH with an id is allowed");
+        assert_eq!(params[&User::id()], "1");
+
+        assert!(
+            filter_request(&filter, request(http::Method::GET), HashMap::new())
+                .await
+                .is_ok()
+        );
+    }
+
+    // Run a request with `method`, carrying the id param if `with_id`, through `rest_filters`,
+    // returning the status it was rejected with.
+    async fn rest(method: http::Method, with_id: bool) -> Result<(), http::StatusCode> {
+        let mut req = request(method);
+        let mut params = if with_id {
+            self::with_id()
+        } else {
+            HashMap::new()
+        };
+
+        for filter in rest_filters::<User>() {
+            match filter.filter_request(req, params).await.unwrap() {
+                RequestFilterOutcome::Pass(passed, passed_params) => {
+                    req = passed;
+                    params = passed_params;
+                }
+                RequestFilterOutcome::Fail(res) => return Err(res.status()),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rest_filters_enforce_the_id_param_per_method() {
+        use http::Method;
+
+        let not_found = Err(http::StatusCode::NOT_FOUND);
+        let matrix = [
+            (Method::GET, false, Ok(())),
+            (Method::GET, true, Ok(())),
+            (Method::POST, false, Ok(())),
+            (Method::POST, true, not_found),
+            (Method::PUT, false, not_found),
+            (Method::PUT, true, Ok(())),
+            (Method::PATCH, false, not_found),
+            (Method::PATCH, true, Ok(())),
+            (Method::DELETE, false, not_found),
+            (Method::DELETE, true, Ok(())),
+        ];
+
+        for (method, with_id, expected) in matrix {
+            assert_eq!(
+                rest(method.clone(), with_id).await,
+                expected,
+                "{method} {with_id}"
+            );
+        }
+    }
+
+    #[test]
+    fn error_responses_declare_charset_and_length() {
+        for res in [
+            PlatformError::from(EntityNotFoundError::new("users", 1)).into_response(),
+            platform_error(ForbiddenError::new()),
+            platform_error(PayloadTooLargeError::new(16)),
+        ] {
+            let headers = res.headers();
+            assert_eq!(headers[http::header::CONTENT_TYPE], JSON_UTF8_CONTENT_TYPE);
+            assert_eq!(
+                headers[http::header::CONTENT_LENGTH],
+                res.body().len().to_string().as_str()
+            );
         }
     }
+
+    #[test]
+    fn problem_responses_use_the_problem_content_type() {
+        let res = PlatformError::from(EntityNotFoundError::new("users", 1))
+            .into_problem_response(Some("/users/1"));
+
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+        assert_eq!(
+            res.headers()[http::header::CONTENT_TYPE],
+            PROBLEM_JSON_CONTENT_TYPE
+        );
+    }
 }
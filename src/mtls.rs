@@ -1,108 +1,1127 @@
+use std::any::Any;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
-use std::sync::Arc;
+use std::future::Future;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
 
 use rustls_pemfile::certs;
 
 use tokio_rustls::rustls;
 
 use tokio_rustls::client::TlsStream;
-use tokio_rustls::rustls::OwnedTrustAnchor;
+use tokio_rustls::rustls::{Certificate, OwnedTrustAnchor, PrivateKey};
 use tokio_rustls::{webpki, TlsConnector};
 
 use tokio::io::AsyncRead;
 use tokio::io::AsyncWrite;
 use tokio::net::TcpStream;
 
-use bytes::Buf;
-use http_body_util::BodyExt;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
 
-use hyper::client::conn::http1::Connection;
-use hyper::client::conn::http1::SendRequest;
+use futures::future::BoxFuture;
 
+use hyper::body::{Body, Frame, Incoming, SizeHint};
+use hyper::client::conn::{http1, http2};
+
+use sha2::{Digest, Sha256};
+use tracing::Instrument;
+
+use crate::pem::{load_certs, load_keys};
+use crate::runtime::TokioExecutor;
+
+const H2_ALPN: &[u8] = b"h2";
+
+/// A TLS client for a single upstream, trusting only the CA chain it was constructed with.
 pub struct Mtls {
     addr: String,
     root_cert_store: rustls::RootCertStore,
+    // Identifies the trusted CAs, so only clients trusting the same CAs share pooled connections.
+    roots_fingerprint: [u8; 32],
     host: String,
+    client_auth: Option<(Vec<Certificate>, PrivateKey)>,
+    pool: Option<Arc<Pool>>,
+    connect_timeout: Duration,
+    prefer_h2: bool,
+    tls_versions: (TlsVersion, TlsVersion),
+    max_response_size: usize,
+    reconnect_attempts: u32,
+    socket_options: SocketOptions,
+    health_path: Option<String>,
+    ping_timeout: Duration,
+}
+
+/// A TLS protocol version, used to bound the versions negotiated by [`Mtls`] and the runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TlsVersion {
+    /// TLS 1.2
+    Tls12,
+    /// TLS 1.3
+    Tls13,
+}
+
+// The rustls protocol versions from `min` up to and including `max`.
+pub(crate) fn protocol_versions(
+    (min, max): (TlsVersion, TlsVersion),
+) -> anyhow::Result<Vec<&'static rustls::SupportedProtocolVersion>> {
+    if min > max {
+        anyhow::bail!("minimum TLS version {min:?} is above the maximum {max:?}");
+    }
+
+    Ok([
+        (TlsVersion::Tls12, &rustls::version::TLS12),
+        (TlsVersion::Tls13, &rustls::version::TLS13),
+    ]
+    .into_iter()
+    .filter(|(version, _)| (min..=max).contains(version))
+    .map(|(_, supported)| supported)
+    .collect())
+}
+
+/// Options set on the TCP sockets dialed by [`Mtls`] and accepted by the runtime.
+///
+/// By default `TCP_NODELAY` is set, so small requests and responses aren't held back by Nagle's
+/// algorithm, while keepalive and the buffer sizes are left to the operating system.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustserve_platform::mtls::SocketOptions;
+///
+/// let socket_options = SocketOptions::default()
+///     .with_keepalive(Duration::from_secs(60))
+///     .with_recv_buffer_size(256 * 1024);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SocketOptions {
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        }
+    }
+}
+
+impl SocketOptions {
+    /// Set `TCP_NODELAY`, disabling Nagle's algorithm, when `nodelay` is true.
+    pub fn with_nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Enable TCP keepalive, probing idle connections after `idle`.
+    pub fn with_keepalive(mut self, idle: Duration) -> Self {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// Set the size of the socket send buffer, `SO_SNDBUF`, to `size` bytes.
+    pub fn with_send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Set the size of the socket receive buffer, `SO_RCVBUF`, to `size` bytes.
+    pub fn with_recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Whether `TCP_NODELAY` is set.
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
+    /// How long connections are idle before keepalive probes are sent, if enabled.
+    pub fn keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
+
+    /// The size of the socket send buffer, if set explicitly.
+    pub fn send_buffer_size(&self) -> Option<usize> {
+        self.send_buffer_size
+    }
+
+    /// The size of the socket receive buffer, if set explicitly.
+    pub fn recv_buffer_size(&self) -> Option<usize> {
+        self.recv_buffer_size
+    }
+
+    pub(crate) fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+
+        let socket = socket2::SockRef::from(stream);
+        if let Some(idle) = self.keepalive {
+            socket.set_tcp_keepalive(&socket2::TcpKeepalive::new().with_time(idle))?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        Ok(())
+    }
 }
 
+/// How long [`Mtls`] waits for a connection to be established unless configured otherwise.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The largest response body [`Mtls`] reads into memory unless configured otherwise, 16 MiB.
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+/// How many times [`Mtls::send_pooled`] tries to replace a dead pooled connection unless
+/// configured otherwise.
+pub const DEFAULT_RECONNECT_ATTEMPTS: u32 = 3;
+
+/// How long [`Mtls::ping`] waits for the upstream unless configured otherwise.
+pub const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The backoff between reconnect attempts starts at the base delay and doubles up to the max delay.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(50);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(1);
+
 impl Mtls {
+    /// Create a client connecting to `addr`, verifying that the server presents a certificate for
+    /// `host` signed by one of the CAs in the PEM file at `full_path`.
     pub fn new(
         addr: impl Into<String>,
         full_path: impl Into<String>,
         host: impl Into<String>,
     ) -> anyhow::Result<Self> {
         let full_path = full_path.into();
-        let chain_file = &mut BufReader::new(File::open(&full_path)?);
-        let chain = certs(chain_file).unwrap();
+        let chain_file =
+            &mut BufReader::new(File::open(&full_path).map_err(|err| {
+                anyhow::anyhow!("failed to open cert chain at {full_path}: {err}")
+            })?);
+
+        Self::from_reader(addr, chain_file, &full_path, host)
+    }
+
+    /// Create a client like [`Mtls::new`] with the trusted CAs read from the PEM encoded `pem`
+    /// bytes instead of a file.
+    pub fn from_pem(
+        addr: impl Into<String>,
+        pem: &[u8],
+        host: impl Into<String>,
+    ) -> anyhow::Result<Self> {
+        Self::from_reader(addr, &mut &pem[..], "in-memory PEM", host)
+    }
+
+    fn from_reader(
+        addr: impl Into<String>,
+        reader: &mut dyn BufRead,
+        source: &str,
+        host: impl Into<String>,
+    ) -> anyhow::Result<Self> {
+        let chain = certs(reader)
+            .map_err(|err| anyhow::anyhow!("failed to parse cert chain at {source}: {err}"))?;
+
+        if chain.is_empty() {
+            anyhow::bail!("failed to parse cert chain at {source}: no certificates found");
+        }
+
+        let trust_anchors = chain
+            .iter()
+            .map(|cert| {
+                let ta = webpki::TrustAnchor::try_from_cert_der(&cert[..])
+                    .map_err(|err| anyhow::anyhow!("invalid trust anchor in {source}: {err}"))?;
+                Ok(OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
         let mut root_cert_store = rustls::RootCertStore::empty();
+        root_cert_store.add_server_trust_anchors(trust_anchors.into_iter());
+        let roots_fingerprint = fingerprint(chain.iter().map(Vec::as_slice));
 
-        root_cert_store.add_server_trust_anchors(chain.iter().map(|cert| {
-            let ta = webpki::TrustAnchor::try_from_cert_der(&cert[..]).unwrap();
-            OwnedTrustAnchor::from_subject_spki_name_constraints(
-                ta.subject,
-                ta.spki,
-                ta.name_constraints,
-            )
-        }));
+        Ok(Self::with_root_cert_store(
+            addr,
+            root_cert_store,
+            roots_fingerprint,
+            host,
+        ))
+    }
+
+    /// Create a client connecting to `addr`, verifying that the server presents a certificate for
+    /// `host` signed by one of the CAs trusted by the operating system.
+    ///
+    /// Use this for public HTTPS endpoints rather than services with certificates issued by a
+    /// private CA.
+    pub fn with_native_roots(
+        addr: impl Into<String>,
+        host: impl Into<String>,
+    ) -> anyhow::Result<Self> {
+        let native_certs = rustls_native_certs::load_native_certs()
+            .map_err(|err| anyhow::anyhow!("failed to load native root certificates: {err}"))?;
+
+        let mut root_cert_store = rustls::RootCertStore::empty();
+        let der_certs = native_certs
+            .into_iter()
+            .map(|cert| cert.0)
+            .collect::<Vec<_>>();
+        let (added, ignored) = root_cert_store.add_parsable_certificates(&der_certs);
+
+        if ignored > 0 {
+            tracing::debug!(ignored, "ignored unparsable native root certificates");
+        }
+        if added == 0 {
+            anyhow::bail!("no native root certificates found");
+        }
 
-        Ok(Self {
+        let roots_fingerprint = fingerprint(der_certs.iter().map(Vec::as_slice));
+
+        Ok(Self::with_root_cert_store(
+            addr,
+            root_cert_store,
+            roots_fingerprint,
+            host,
+        ))
+    }
+
+    fn with_root_cert_store(
+        addr: impl Into<String>,
+        root_cert_store: rustls::RootCertStore,
+        roots_fingerprint: [u8; 32],
+        host: impl Into<String>,
+    ) -> Self {
+        Self {
             addr: addr.into(),
             host: host.into(),
             root_cert_store,
-        })
+            roots_fingerprint,
+            client_auth: None,
+            pool: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            prefer_h2: false,
+            tls_versions: (TlsVersion::Tls12, TlsVersion::Tls13),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            reconnect_attempts: DEFAULT_RECONNECT_ATTEMPTS,
+            socket_options: SocketOptions::default(),
+            health_path: None,
+            ping_timeout: DEFAULT_PING_TIMEOUT,
+        }
+    }
+
+    /// Present the certificate chain at `chain_path`, signed with the private key at `key_path`,
+    /// to servers requesting client authentication.
+    pub fn with_client_cert(
+        mut self,
+        chain_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
+        let chain_path = chain_path.as_ref();
+        let key_path = key_path.as_ref();
+
+        let chain = load_certs(chain_path).map_err(|err| {
+            anyhow::anyhow!(
+                "failed to load client cert chain at {}: {err}",
+                chain_path.display()
+            )
+        })?;
+        if chain.is_empty() {
+            anyhow::bail!("no client certificates found in {}", chain_path.display());
+        }
+
+        let mut keys = load_keys(key_path)?;
+
+        self.client_auth = Some((chain, keys.remove(0)));
+
+        Ok(self)
     }
 
+    /// Establish a TLS connection to the upstream and perform the HTTP handshake over it, using
+    /// HTTP/2 when it is preferred and the upstream agrees to it during ALPN.
+    ///
+    /// The returned connection must be polled, usually on its own task, for requests sent through
+    /// the returned sender to make progress.
     pub async fn connect<B>(
         &self,
-    ) -> anyhow::Result<(
-        SendRequest<B>,
-        Connection<TlsStream<impl AsyncRead + AsyncWrite + Send + 'static>, B>,
-    )>
+    ) -> anyhow::Result<(Sender<B>, BoxFuture<'static, Result<(), hyper::Error>>)>
     where
-        B: hyper::body::Body + Send + 'static,
+        B: hyper::body::Body + Send + Unpin + 'static,
         B::Data: Send,
-        B::Error: Send + Sync + std::error::Error,
+        B::Error: Send + Sync + std::error::Error + 'static,
     {
-        let config = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(self.root_cert_store.clone())
-            .with_no_client_auth();
+        let builder = rustls::ClientConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&protocol_versions(self.tls_versions)?)?
+            .with_root_certificates(self.root_cert_store.clone());
 
-        let connector = TlsConnector::from(Arc::new(config));
+        let mut config = match &self.client_auth {
+            Some((chain, key)) => builder.with_single_cert(chain.clone(), key.clone())?,
+            None => builder.with_no_client_auth(),
+        };
+
+        if self.prefer_h2 {
+            config.alpn_protocols = vec![H2_ALPN.to_vec(), b"http/1.1".to_vec()];
+        }
 
-        let tcp_stream = TcpStream::connect(self.addr.clone()).await?;
+        let connector = TlsConnector::from(Arc::new(config));
 
         let domain = rustls::ServerName::try_from(&self.host.clone()[..])?;
 
-        let tls_stream = connector.connect(domain, tcp_stream).await?;
+        let connect = async {
+            let tcp_stream = TcpStream::connect(self.addr.clone()).await?;
+            self.socket_options.apply(&tcp_stream)?;
+
+            let tls_stream = connector.connect(domain, tcp_stream).await?;
+
+            Ok::<_, anyhow::Error>(handshake(tls_stream).await?)
+        };
+
+        tokio::time::timeout(self.connect_timeout, connect)
+            .await
+            .map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!(
+                        "connect to {} timed out after {:?}",
+                        self.addr, self.connect_timeout
+                    ),
+                )
+            })?
+    }
+
+    /// Give up connecting, including the TLS handshake, after `connect_timeout`.
+    ///
+    /// Defaults to [`DEFAULT_CONNECT_TIMEOUT`].
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Offer HTTP/2 to the upstream during the TLS handshake, falling back to HTTP/1.1 when the
+    /// upstream doesn't support it.
+    ///
+    /// HTTP/2 connections multiplex requests, so a single pooled connection serves every
+    /// concurrent [`Mtls::send_pooled`] to the same upstream.
+    pub fn with_prefer_h2(mut self, prefer_h2: bool) -> Self {
+        self.prefer_h2 = prefer_h2;
+        self
+    }
+
+    /// Only negotiate TLS versions from `min` up to and including `max`, such as TLS 1.3 only
+    /// with `(TlsVersion::Tls13, TlsVersion::Tls13)`.
+    ///
+    /// Defaults to TLS 1.2 up to TLS 1.3.  Connecting fails if `min` is above `max`.
+    pub fn with_tls_versions(mut self, min: TlsVersion, max: TlsVersion) -> Self {
+        self.tls_versions = (min, max);
+        self
+    }
+
+    /// Fail requests whose response body is larger than `max_response_size` bytes instead of
+    /// reading it into memory.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_SIZE`].  Doesn't apply to [`Mtls::send_streaming`].
+    pub fn with_max_response_size(mut self, max_response_size: usize) -> Self {
+        self.max_response_size = max_response_size;
+        self
+    }
+
+    /// Try up to `reconnect_attempts` times to replace a pooled connection found dead by
+    /// [`Mtls::send_pooled`], backing off exponentially between attempts.
+    ///
+    /// Defaults to [`DEFAULT_RECONNECT_ATTEMPTS`].  Doesn't apply to connections that aren't
+    /// pooled, which fail on the first error.
+    pub fn with_reconnect_attempts(mut self, reconnect_attempts: u32) -> Self {
+        self.reconnect_attempts = reconnect_attempts.max(1);
+        self
+    }
 
-        Ok(hyper::client::conn::http1::handshake(tls_stream).await?)
+    /// Set `socket_options` on the sockets connected to the upstream.
+    ///
+    /// Defaults to [`SocketOptions::default`], which sets `TCP_NODELAY`.
+    pub fn with_socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.socket_options = socket_options;
+        self
     }
 
+    /// Have [`Mtls::ping`] also send a `GET` request to `health_path`, such as `/readyz`, and
+    /// require a `2xx` response.
+    pub fn with_health_path(mut self, health_path: impl Into<String>) -> Self {
+        self.health_path = Some(health_path.into());
+        self
+    }
+
+    /// Fail [`Mtls::ping`] if it takes longer than `ping_timeout`.
+    ///
+    /// Defaults to [`DEFAULT_PING_TIMEOUT`].
+    pub fn with_ping_timeout(mut self, ping_timeout: Duration) -> Self {
+        self.ping_timeout = ping_timeout;
+        self
+    }
+
+    /// Check that the upstream is reachable, to reflect the health of critical dependencies in a
+    /// readiness check.
+    ///
+    /// An idle connection from the pool used by [`Mtls::send_pooled`] is reused when there is
+    /// one, otherwise a new connection is established, TLS and HTTP handshake included, and
+    /// closed again.  With a [`Mtls::with_health_path`] the health path must respond with a `2xx`
+    /// status too.  Fails with an error describing what went wrong after at most the
+    /// [`Mtls::with_ping_timeout`].
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        tokio::time::timeout(self.ping_timeout, self.probe())
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "ping to {} timed out after {:?}",
+                    self.addr,
+                    self.ping_timeout
+                )
+            })?
+            .map_err(|err| err.context(format!("upstream {} is unhealthy", self.addr)))
+    }
+
+    async fn probe(&self) -> anyhow::Result<()> {
+        let pool = self.pool.clone().unwrap_or_else(Pool::global);
+        let key = self.pool_key();
+
+        let pooled = match pool.checkout(&key) {
+            Some(mut request_sender) => match request_sender.ready().await {
+                Ok(()) => Some(request_sender),
+                Err(_) => None,
+            },
+            None => None,
+        };
+        let reused = pooled.is_some();
+        let mut request_sender = match pooled {
+            Some(request_sender) => request_sender,
+            None => {
+                let (mut request_sender, connection) = self.connect().await?;
+                self.spawn_connection(connection);
+                request_sender.ready().await?;
+                request_sender
+            }
+        };
+
+        if let Some(health_path) = &self.health_path {
+            let req = hyper::Request::get(health_path.as_str())
+                .header(http::header::HOST, self.host.as_str())
+                .body(Full::new(Bytes::new()))?;
+            let res = request_sender
+                .send_request(self.for_sender(&request_sender, req)?)
+                .await?;
+            let status = res.status();
+            read_response(res, self.max_response_size).await?;
+
+            if !status.is_success() {
+                anyhow::bail!("health check {health_path} responded with {status}");
+            }
+        }
+
+        if reused {
+            pool.checkin(key, request_sender);
+        }
+
+        Ok(())
+    }
+
+    /// Send `req` over a new connection and read the whole response body into memory.
+    ///
+    /// Responses without a body, such as `204 No Content`, are returned with an empty body rather
+    /// than an error, leaving it to the caller whether that is acceptable.
     pub async fn send<B>(&self, req: hyper::Request<B>) -> anyhow::Result<hyper::Response<Vec<u8>>>
     where
-        B: hyper::body::Body + Send + std::fmt::Debug + 'static,
+        B: hyper::body::Body + Send + Unpin + std::fmt::Debug + 'static,
         B::Data: Send,
-        B::Error: Send + Sync + std::error::Error,
+        B::Error: Send + Sync + std::error::Error + 'static,
     {
         let (mut request_sender, connection) = self.connect().await?;
+        self.spawn_connection(connection);
+
+        let req = self.for_sender(&request_sender, req)?;
+        let res = request_sender.send_request(req).await?;
 
-        // spawn a task to poll the connection and drive the HTTP state
+        read_response(res, self.max_response_size).await
+    }
+
+    /// Send `req` over a new connection, returning the response as soon as its head has been
+    /// received so the body can be read incrementally.
+    ///
+    /// The connection stays open until the returned body has been read to the end or dropped.
+    pub async fn send_streaming<B>(
+        &self,
+        req: hyper::Request<B>,
+    ) -> anyhow::Result<hyper::Response<StreamingBody>>
+    where
+        B: hyper::body::Body + Send + Unpin + 'static,
+        B::Data: Send,
+        B::Error: Send + Sync + std::error::Error + 'static,
+    {
+        let (mut request_sender, connection) = self.connect().await?;
+        self.spawn_connection(connection);
+
+        let req = self.for_sender(&request_sender, req)?;
+        let res = request_sender.send_request(req).await?;
+
+        Ok(res.map(|body| StreamingBody {
+            body,
+            _request_sender: Box::new(request_sender),
+        }))
+    }
+
+    /// Use `pool` instead of the process wide pool for [`Mtls::send_pooled`].
+    pub fn with_pool(mut self, pool: Arc<Pool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Send `req` over an idle connection to the same upstream if there is one, establishing a new
+    /// connection otherwise.  The connection is returned to the pool once the response has been
+    /// read.
+    ///
+    /// A pooled connection the upstream closed before `req` could be sent over it is replaced
+    /// transparently, see [`Mtls::with_reconnect_attempts`].
+    pub async fn send_pooled(
+        &self,
+        req: hyper::Request<Full<Bytes>>,
+    ) -> anyhow::Result<hyper::Response<Vec<u8>>> {
+        let pool = self.pool.clone().unwrap_or_else(Pool::global);
+        let key = self.pool_key();
+
+        let mut request_sender = match pool.checkout(&key) {
+            Some(mut request_sender) => match request_sender.ready().await {
+                Ok(()) => request_sender,
+                Err(err) => {
+                    tracing::debug!(addr = %self.addr, error = %err, "pooled connection closed");
+                    self.reconnect().await?
+                }
+            },
+            None => {
+                let (mut request_sender, connection) = self.connect().await?;
+                self.spawn_connection(connection);
+                request_sender.ready().await?;
+                request_sender
+            }
+        };
+
+        let res = match request_sender
+            .send_request(self.for_sender(&request_sender, copy_request(&req))?)
+            .await
+        {
+            // The connection closed between becoming ready and sending, so `req` never left.
+            Err(err) if err.is_canceled() => {
+                tracing::debug!(addr = %self.addr, error = %err, "pooled connection closed");
+                request_sender = self.reconnect().await?;
+                let req = self.for_sender(&request_sender, req)?;
+                request_sender.send_request(req).await?
+            }
+            res => res?,
+        };
+        let res = read_response(res, self.max_response_size).await?;
+
+        pool.checkin(key, request_sender);
+
+        Ok(res)
+    }
+
+    // The key of the connections this client can share, which is everything `connect` sets up
+    // the connection with.
+    fn pool_key(&self) -> PoolKey {
+        PoolKey {
+            addr: self.addr.clone(),
+            host: self.host.clone(),
+            roots: self.roots_fingerprint,
+            client_cert: self.client_auth.as_ref().map(|(chain, key)| {
+                fingerprint(chain.iter().map(|cert| &cert.0[..]).chain([&key.0[..]]))
+            }),
+            prefer_h2: self.prefer_h2,
+            tls_versions: self.tls_versions,
+        }
+    }
+
+    // Establish a connection replacing a dead pooled one, retrying with capped exponential backoff.
+    async fn reconnect(&self) -> anyhow::Result<Sender<Full<Bytes>>> {
+        let mut delay = RECONNECT_BASE_DELAY;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            tracing::debug!(addr = %self.addr, attempt, "reconnecting to upstream");
+
+            let err = match self.connect().await {
+                Ok((mut request_sender, connection)) => {
+                    self.spawn_connection(connection);
+                    match request_sender.ready().await {
+                        Ok(()) => return Ok(request_sender),
+                        Err(err) => anyhow::Error::from(err),
+                    }
+                }
+                Err(err) => err,
+            };
+
+            if attempt >= self.reconnect_attempts {
+                return Err(err.context(format!(
+                    "failed to reconnect to {} after {attempt} attempts",
+                    self.addr
+                )));
+            }
+
+            tracing::debug!(addr = %self.addr, error = %err, ?delay, "reconnect failed");
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+    }
+
+    // HTTP/2 carries the target in the `:scheme` and `:authority` pseudo headers, which hyper takes
+    // from the URI, so relative URIs are made absolute before sending over HTTP/2.
+    fn for_sender<B>(
+        &self,
+        request_sender: &Sender<B>,
+        mut req: hyper::Request<B>,
+    ) -> anyhow::Result<hyper::Request<B>>
+    where
+        B: hyper::body::Body + 'static,
+    {
+        if request_sender.is_http2() && req.uri().authority().is_none() {
+            let mut parts = req.uri().clone().into_parts();
+            parts.scheme = Some(http::uri::Scheme::HTTPS);
+            parts.authority = Some(self.host.parse()?);
+            if parts.path_and_query.is_none() {
+                parts.path_and_query = Some("/".parse()?);
+            }
+            *req.uri_mut() = http::Uri::from_parts(parts)?;
+        }
+
+        Ok(req)
+    }
+
+    // spawn a task to poll the connection and drive the HTTP state
+    fn spawn_connection<C>(&self, connection: C)
+    where
+        C: Future<Output = Result<(), hyper::Error>> + Send + 'static,
+    {
+        let span = tracing::debug_span!("mtls_connection", addr = %self.addr, host = %self.host);
+        tokio::spawn(
+            async move {
+                tracing::debug!("connection established");
+                match connection.await {
+                    Ok(()) => tracing::debug!("connection closed"),
+                    Err(err) => tracing::error!(error = %err, "error in connection"),
+                }
+            }
+            .instrument(span),
+        );
+    }
+}
+
+// A copy of `req` made before sending it, so it can be sent again when the connection turns out
+// to be closed.  Bodies are reference counted, so this doesn't copy the payload.
+fn copy_request(req: &hyper::Request<Full<Bytes>>) -> hyper::Request<Full<Bytes>> {
+    let mut copy = hyper::Request::new(req.body().clone());
+    *copy.method_mut() = req.method().clone();
+    *copy.uri_mut() = req.uri().clone();
+    *copy.version_mut() = req.version();
+    *copy.headers_mut() = req.headers().clone();
+    copy
+}
+
+// Perform the handshake for whichever protocol was negotiated during ALPN.
+async fn handshake<T, B>(
+    tls_stream: TlsStream<T>,
+) -> Result<(Sender<B>, BoxFuture<'static, Result<(), hyper::Error>>), hyper::Error>
+where
+    T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    B: hyper::body::Body + Send + Unpin + 'static,
+    B::Data: Send,
+    B::Error: Send + Sync + std::error::Error + 'static,
+{
+    if tls_stream.get_ref().1.alpn_protocol() == Some(H2_ALPN) {
+        let (request_sender, connection) = http2::handshake(TokioExecutor, tls_stream).await?;
+        Ok((Sender::Http2(request_sender), Box::pin(connection)))
+    } else {
+        let (request_sender, connection) = http1::handshake(tls_stream).await?;
+        Ok((Sender::Http1(request_sender), Box::pin(connection)))
+    }
+}
+
+/// Sends requests over a connection established by [`Mtls::connect`] using the HTTP version
+/// negotiated with the upstream.
+pub enum Sender<B> {
+    /// An HTTP/1.1 connection, serving one request at a time.
+    Http1(http1::SendRequest<B>),
+    /// An HTTP/2 connection, multiplexing concurrent requests.
+    Http2(http2::SendRequest<B>),
+}
+
+impl<B> Sender<B>
+where
+    B: hyper::body::Body + 'static,
+{
+    /// Wait until the connection is able to send another request.
+    pub async fn ready(&mut self) -> Result<(), hyper::Error> {
+        match self {
+            Self::Http1(request_sender) => request_sender.ready().await,
+            Self::Http2(request_sender) => request_sender.ready().await,
+        }
+    }
+
+    /// Send `req` over the connection, resolving once the response head has been received.
+    pub async fn send_request(
+        &mut self,
+        req: hyper::Request<B>,
+    ) -> Result<hyper::Response<Incoming>, hyper::Error> {
+        match self {
+            Self::Http1(request_sender) => request_sender.send_request(req).await,
+            Self::Http2(request_sender) => request_sender.send_request(req).await,
+        }
+    }
+
+    /// Whether the connection has been closed.
+    pub fn is_closed(&self) -> bool {
+        match self {
+            Self::Http1(request_sender) => request_sender.is_closed(),
+            Self::Http2(request_sender) => request_sender.is_closed(),
+        }
+    }
+
+    /// Whether HTTP/2 was negotiated for the connection.
+    pub fn is_http2(&self) -> bool {
+        matches!(self, Self::Http2(_))
+    }
+}
+
+/// The body of a response returned by [`Mtls::send_streaming`], read as it arrives.
+pub struct StreamingBody {
+    body: Incoming,
+    // Keeps the connection from shutting down while the body is being read.
+    _request_sender: Box<dyn Any + Send>,
+}
+
+impl Body for StreamingBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.get_mut().body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+async fn read_response(
+    res: hyper::Response<Incoming>,
+    max_response_size: usize,
+) -> anyhow::Result<hyper::Response<Vec<u8>>> {
+    let (parts, body) = res.into_parts();
+
+    let too_large = || anyhow::anyhow!("upstream response exceeded {max_response_size} bytes");
+
+    if body.size_hint().exact().unwrap_or(0) > max_response_size as u64 {
+        return Err(too_large());
+    }
+
+    let bytes = match Limited::new(body, max_response_size).collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(err) if err.is::<LengthLimitError>() => return Err(too_large()),
+        Err(err) => return Err(anyhow::anyhow!(err)),
+    };
+
+    // Reuses the buffer of a body that arrived in a single, unshared frame instead of copying it.
+    Ok(hyper::Response::from_parts(parts, Vec::from(bytes)))
+}
+
+// The SHA-256 digest of `ders`, each prefixed with its length so adjacent ones can't run together.
+fn fingerprint<'a>(ders: impl IntoIterator<Item = &'a [u8]>) -> [u8; 32] {
+    let mut digest = Sha256::new();
+    for der in ders {
+        digest.update((der.len() as u64).to_be_bytes());
+        digest.update(der);
+    }
+    digest.finalize().into()
+}
+
+/// How many idle connections per upstream the process wide [`Pool`] keeps.
+pub const DEFAULT_MAX_IDLE_PER_UPSTREAM: usize = 8;
+
+// Clients only share connections when they would have established identical ones, so no client
+// sends requests with another's certificate or over a connection verified by another's CAs.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PoolKey {
+    addr: String,
+    host: String,
+    roots: [u8; 32],
+    // Covers the client certificate chain and its private key.
+    client_cert: Option<[u8; 32]>,
+    prefer_h2: bool,
+    tls_versions: (TlsVersion, TlsVersion),
+}
+
+/// Idle connections kept alive for reuse by [`Mtls::send_pooled`], keyed by the upstream address
+/// and host along with the TLS settings of the client, such as its trusted CAs, client
+/// certificate and TLS versions.
+pub struct Pool {
+    idle: Mutex<HashMap<PoolKey, Vec<Sender<Full<Bytes>>>>>,
+    max_idle_per_upstream: usize,
+}
+
+impl Pool {
+    /// Create a pool keeping at most `max_idle_per_upstream` idle connections per upstream.
+    pub fn new(max_idle_per_upstream: usize) -> Self {
+        Self {
+            idle: Mutex::new(HashMap::new()),
+            max_idle_per_upstream,
+        }
+    }
+
+    /// The pool shared by every [`Mtls`] that wasn't given a pool of its own.
+    pub fn global() -> Arc<Pool> {
+        static GLOBAL: OnceLock<Arc<Pool>> = OnceLock::new();
+        GLOBAL
+            .get_or_init(|| Arc::new(Pool::new(DEFAULT_MAX_IDLE_PER_UPSTREAM)))
+            .clone()
+    }
+
+    fn checkout(&self, key: &PoolKey) -> Option<Sender<Full<Bytes>>> {
+        let mut idle = self.idle.lock().unwrap();
+        let senders = idle.get_mut(key)?;
+
+        while let Some(request_sender) = senders.pop() {
+            if request_sender.is_closed() {
+                continue;
+            }
+
+            // HTTP/2 connections stay in the pool while in use so concurrent requests share them.
+            if let Sender::Http2(h2) = &request_sender {
+                senders.push(Sender::Http2(h2.clone()));
+            }
+
+            return Some(request_sender);
+        }
+
+        None
+    }
+
+    fn checkin(&self, key: PoolKey, request_sender: Sender<Full<Bytes>>) {
+        if request_sender.is_closed() {
+            return;
+        }
+
+        let mut idle = self.idle.lock().unwrap();
+        let senders = idle.entry(key).or_default();
+
+        // A handle to an HTTP/2 connection is already pooled unless it was evicted as closed.
+        if request_sender.is_http2() && senders.iter().any(Sender::is_http2) {
+            return;
+        }
+
+        if senders.len() < self.max_idle_per_upstream {
+            senders.push(request_sender);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use http::StatusCode;
+    use hyper::server::conn::http1 as server_http1;
+    use hyper::service::service_fn;
+    use tokio_rustls::TlsAcceptor;
+
+    use super::*;
+    use crate::testing::{self, TempDir, TestCa};
+
+    // Serve `localhost` over TLS, answering every request with `status` and `body`, and count
+    // the connections accepted.
+    async fn serve(ca: &TestCa, status: StatusCode, body: Bytes) -> (SocketAddr, Arc<AtomicUsize>) {
+        let acceptor = TlsAcceptor::from(Arc::new(ca.server_config(&["localhost"])));
+        let listener = tokio::net::TcpListener::bind(testing::localhost())
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let accepted = connections.clone();
         tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("Error in connection: {}", e);
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::SeqCst);
+
+                let acceptor = acceptor.clone();
+                let body = body.clone();
+                tokio::spawn(async move {
+                    let Ok(stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let service = service_fn(move |_| {
+                        let body = body.clone();
+                        async move {
+                            let res = hyper::Response::builder()
+                                .status(status)
+                                .body(Full::new(body))
+                                .unwrap();
+                            Ok::<_, Infallible>(res)
+                        }
+                    });
+                    let _ = server_http1::Builder::new()
+                        .serve_connection(stream, service)
+                        .await;
+                });
             }
         });
 
-        let res = request_sender.send_request(req).await?;
+        (addr, connections)
+    }
+
+    #[tokio::test]
+    async fn returns_no_content_with_an_empty_body() {
+        let ca = TestCa::new();
+        let (addr, _) = serve(&ca, StatusCode::NO_CONTENT, Bytes::new()).await;
+
+        let res = ca
+            .client(addr, "localhost")
+            .send(testing::get("/users/1"))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(res.body().is_empty());
+    }
+
+    #[tokio::test]
+    async fn returns_empty_successful_bodies() {
+        let ca = TestCa::new();
+        let (addr, _) = serve(&ca, StatusCode::OK, Bytes::new()).await;
+
+        let res = ca
+            .client(addr, "localhost")
+            .send(testing::get("/users/1"))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.body().is_empty());
+    }
+
+    #[test]
+    fn rejects_files_without_certificates() {
+        let err = Mtls::from_pem("127.0.0.1:443", b"not a certificate", "localhost")
+            .err()
+            .expect("a file without certificates is rejected");
+
+        assert!(err.to_string().contains("no certificates found"), "{err}");
+    }
+
+    #[test]
+    fn rejects_garbage_certificates() {
+        let pem = b"-----BEGIN CERTIFICATE-----\nZ2FyYmFnZQ==\n-----END CERTIFICATE-----\n";
+        let err = Mtls::from_pem("127.0.0.1:443", pem, "localhost")
+            .err()
+            .expect("a garbage certificate is rejected");
+
+        assert!(err.to_string().contains("invalid trust anchor"), "{err}");
+    }
+
+    #[test]
+    fn rejects_missing_files() {
+        let err = Mtls::new("127.0.0.1:443", "/nonexistent/ca.cert", "localhost")
+            .err()
+            .expect("a missing file is rejected");
+
+        assert!(
+            err.to_string().contains("failed to open cert chain"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn accepts_responses_up_to_the_max_size() {
+        let ca = TestCa::new();
+        let (addr, _) = serve(&ca, StatusCode::OK, Bytes::from(vec![b'a'; 16])).await;
+
+        let res = ca
+            .client(addr, "localhost")
+            .with_max_response_size(16)
+            .send(testing::get("/users/1"))
+            .await
+            .unwrap();
+
+        assert_eq!(res.body().len(), 16);
+    }
+
+    #[tokio::test]
+    async fn rejects_responses_over_the_max_size() {
+        let ca = TestCa::new();
+        let (addr, _) = serve(&ca, StatusCode::OK, Bytes::from(vec![b'a'; 17])).await;
+
+        let err = ca
+            .client(addr, "localhost")
+            .with_max_response_size(16)
+            .send(testing::get("/users/1"))
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("exceeded 16 bytes"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn pools_connections_per_client_certificate() {
+        let ca = TestCa::new();
+        let (addr, connections) = serve(&ca, StatusCode::OK, Bytes::new()).await;
+        let pool = Arc::new(Pool::new(DEFAULT_MAX_IDLE_PER_UPSTREAM));
+
+        let client = |name: &str, dir: &TempDir| {
+            ca.issue(dir.path(), &[name]);
+            ca.client(addr, "localhost")
+                .with_client_cert(dir.path().join("end.cert"), dir.path().join("end.key"))
+                .unwrap()
+                .with_pool(pool.clone())
+        };
+        let (orders_dir, billing_dir) = (TempDir::new(), TempDir::new());
+        let orders = client("orders", &orders_dir);
+        let billing = client("billing", &billing_dir);
+
+        let get = || {
+            hyper::Request::get("/users/1")
+                .header(http::header::HOST, "localhost")
+                .body(Full::new(Bytes::new()))
+                .unwrap()
+        };
 
-        let (parts, body) = res.into_parts();
+        orders.send_pooled(get()).await.unwrap();
+        orders.send_pooled(get()).await.unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
 
-        let mut buf = body.collect().await?.aggregate();
-        let bytes = buf.copy_to_bytes(buf.remaining());
+        billing.send_pooled(get()).await.unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
 
-        Ok(hyper::Response::from_parts(parts, bytes.to_vec()))
+        orders.send_pooled(get()).await.unwrap();
+        billing.send_pooled(get()).await.unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
     }
 }
@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use rustls_pemfile::{certs, ec_private_keys, pkcs8_private_keys, rsa_private_keys};
+
+use tokio_rustls::rustls::{Certificate, PrivateKey};
+
+pub(crate) fn load_certs(path: &Path) -> io::Result<Vec<Certificate>> {
+    certs(&mut BufReader::new(File::open(path)?))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid cert"))
+        .map(|mut certs| certs.drain(..).map(Certificate).collect())
+}
+
+// Load the certificate chain and private key stored in the PKCS#12 bundle at `path`.  The
+// certificates are returned in the order they're stored in, which is leaf first for bundles
+// exported by `openssl pkcs12 -export`.
+pub(crate) fn load_pkcs12(
+    path: &Path,
+    passphrase: &str,
+) -> io::Result<(Vec<Certificate>, PrivateKey)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    let pfx = p12::PFX::parse(&std::fs::read(path)?)
+        .map_err(|err| invalid(format!("invalid PKCS#12 bundle {}: {err}", path.display())))?;
+
+    if !pfx.verify_mac(passphrase) {
+        return Err(invalid(format!(
+            "wrong passphrase for PKCS#12 bundle {}",
+            path.display()
+        )));
+    }
+
+    let certs = pfx
+        .cert_x509_bags(passphrase)
+        .map_err(|err| invalid(format!("invalid certificate in {}: {err}", path.display())))?;
+
+    let key = pfx
+        .key_bags(passphrase)
+        .map_err(|err| invalid(format!("invalid private key in {}: {err}", path.display())))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid(format!("no private key found in {}", path.display())))?;
+
+    Ok((
+        certs.into_iter().map(Certificate).collect(),
+        PrivateKey(key),
+    ))
+}
+
+type KeyParser = fn(&mut dyn io::BufRead) -> io::Result<Vec<Vec<u8>>>;
+
+const KEY_FORMATS: [(&str, KeyParser); 3] = [
+    ("PKCS#8", pkcs8_private_keys),
+    ("SEC1 EC", ec_private_keys),
+    ("PKCS#1 RSA", rsa_private_keys),
+];
+
+pub(crate) fn load_keys(path: &Path) -> io::Result<Vec<PrivateKey>> {
+    for (format, parse) in KEY_FORMATS {
+        let keys = parse(&mut BufReader::new(File::open(path)?)).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("invalid {format} key"))
+        })?;
+
+        if !keys.is_empty() {
+            return Ok(keys.into_iter().map(PrivateKey).collect());
+        }
+    }
+
+    let attempted: Vec<_> = KEY_FORMATS.iter().map(|(format, _)| *format).collect();
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "no private key found in {}, attempted formats: {}",
+            path.display(),
+            attempted.join(", ")
+        ),
+    ))
+}
@@ -1,136 +1,1411 @@
 use rustserve::Route;
 
-use std::fs::File;
-use std::io::{self, BufReader};
+use std::future::Future;
+use std::io;
 use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+#[cfg(unix)]
 use std::path::Path;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
-use rustls_pemfile::{certs, pkcs8_private_keys};
-
-use tokio_rustls::rustls::{self, Certificate, PrivateKey};
+use tokio_rustls::rustls::Certificate;
 use tokio_rustls::TlsAcceptor;
 
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
-use tokio::net::TcpStream;
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tokio::task::{JoinError, JoinSet};
+
+use tracing::Instrument;
+
+use futures::FutureExt;
 
-use bytes::Buf;
 use bytes::Bytes;
-use http_body_util::{BodyExt, Full};
+use http::header::{HeaderValue, CONNECTION, CONTENT_LENGTH, RETRY_AFTER};
+use http::Version;
+use http_body::Body;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::server::conn::{http1, http2};
 use hyper::Request;
-use hyper::{body::Incoming, server::conn::http1, service::service_fn};
+use hyper::{body::Incoming, service::service_fn};
 
-fn load_certs(path: &Path) -> io::Result<Vec<Certificate>> {
-    certs(&mut BufReader::new(File::open(path)?))
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid cert"))
-        .map(|mut certs| certs.drain(..).map(Certificate).collect())
-}
+mod config;
+mod decompress;
+mod health;
+mod identity;
+mod listener;
+mod maintenance;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod static_files;
+mod tls;
+
+pub use config::{
+    Listen, RuntimeConfig, RuntimeConfigBuilder, SaturationPolicy, SniFallback,
+    DEFAULT_GRACE_PERIOD, DEFAULT_HEADER_READ_TIMEOUT, DEFAULT_MAX_BODY_SIZE,
+    DEFAULT_REQUEST_TIMEOUT, PKCS12_PASSPHRASE_VAR,
+};
+
+use decompress::{decompress, DecompressError};
+pub use health::{HealthChecks, DEFAULT_LIVENESS_PATH, DEFAULT_READINESS_PATH};
+pub use identity::ClientIdentity;
+use listener::Accept;
+pub use maintenance::{maintenance_routes, MaintenanceRoutes, DEFAULT_MAINTENANCE_PATH};
+#[cfg(feature = "metrics")]
+pub use metrics::{metrics_registry, DEFAULT_METRICS_PATH};
+pub use static_files::{static_routes, StaticFiles, DEFAULT_MAX_FILE_SIZE};
+use tls::{handshake_failure, H2_ALPN};
+
+pub use tls::CertReloader;
+
+use crate::context;
+use crate::filters::{deadline_passed, RouteDeadline};
+use crate::{
+    platform_error, GatewayTimeoutError, InternalServerError, InvalidPayloadError,
+    PayloadTooLargeError, ServiceUnavailableError, UnsupportedMediaTypeError,
+};
+
+/// The certificate chain a client presented during the TLS handshake.
+///
+/// When client auth is configured the server only accepts clients with a certificate signed by one
+/// of the CAs in that bundle, and every request on such a connection carries the verified chain,
+/// leaf first, in its extensions.
+#[derive(Clone, Debug)]
+pub struct PeerCertificates(pub Arc<Vec<Certificate>>);
+
+/// The address of the client a request was received from, carried in the request extensions.
+///
+/// Only set for connections over TCP, requests received over a Unix domain socket have no peer
+/// address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
 
-fn load_keys(path: &Path) -> io::Result<Vec<PrivateKey>> {
-    pkcs8_private_keys(&mut BufReader::new(File::open(path)?))
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid key"))
-        .map(|mut keys| keys.drain(..).map(PrivateKey).collect())
+// Per-connection state handed to every request served on that connection.
+#[derive(Clone, Default)]
+struct ConnectionInfo {
+    peer_addr: Option<PeerAddr>,
+    peer_certificates: Option<PeerCertificates>,
+    client_identity: Option<ClientIdentity>,
 }
 
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
 /// Run the server at `server_addr` serving `routes` with name `service_name` with or without tls
 /// support.
+///
+/// This is a thin wrapper around [`serve`], prefer building a [`RuntimeConfig`] for new services.
 pub async fn drive(
     server_addr: SocketAddr,
     routes: Arc<Vec<Route>>,
     use_tls: bool,
     service_name: impl Into<String>,
 ) -> anyhow::Result<()> {
-    let name = service_name.into();
-    let listener = TcpListener::bind(server_addr).await?;
+    drive_with_shutdown(
+        server_addr,
+        routes,
+        use_tls,
+        service_name,
+        futures::future::pending(),
+        DEFAULT_GRACE_PERIOD,
+    )
+    .await
+}
+
+/// Run the server like [`drive`] until `shutdown` resolves, see [`serve_with_shutdown`].
+///
+/// When `use_tls` is set and the `CLIENT_CA_BUNDLE` environment variable points at a CA bundle,
+/// clients must present a certificate signed by one of those CAs.
+pub async fn drive_with_shutdown(
+    server_addr: SocketAddr,
+    routes: Arc<Vec<Route>>,
+    use_tls: bool,
+    service_name: impl Into<String>,
+    shutdown: impl Future<Output = ()>,
+    grace_period: Duration,
+) -> anyhow::Result<()> {
+    let mut builder = RuntimeConfig::builder(server_addr)
+        .with_service_name(service_name)
+        .with_grace_period(grace_period);
 
     if use_tls {
-        let cert_root_path = std::env::var("CERTIFICATE_ROOT").unwrap_or(".".into());
-        let certs = load_certs(Path::new(&format!("{cert_root_path}/{name}/rsa/end.cert")))?;
-        let mut keys = load_keys(Path::new(&format!("{cert_root_path}/{name}/rsa/end.key")))?;
-        let config = rustls::ServerConfig::builder()
-            .with_safe_defaults()
-            .with_no_client_auth()
-            .with_single_cert(certs, keys.remove(0))
-            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        builder = builder.with_tls();
+
+        if let Some(client_ca_path) = std::env::var_os("CLIENT_CA_BUNDLE") {
+            builder = builder.with_client_ca(client_ca_path);
+        }
+    }
+
+    serve_with_shutdown(builder.build(), routes, shutdown).await
+}
+
+/// Run the service described by `config` serving `routes`.
+pub async fn serve(config: RuntimeConfig, routes: Arc<Vec<Route>>) -> anyhow::Result<()> {
+    serve_with_shutdown(config, routes, futures::future::pending()).await
+}
+
+/// Run the server on the Unix domain socket at `path` serving `routes` with name `service_name`.
+///
+/// Connections over the socket are served without TLS.  A stale socket file left behind at `path`
+/// is removed before binding, and the socket file is removed again once the server stops.
+#[cfg(unix)]
+pub async fn drive_uds(
+    path: impl AsRef<Path>,
+    routes: Arc<Vec<Route>>,
+    service_name: impl Into<String>,
+) -> anyhow::Result<()> {
+    let config = RuntimeConfig::builder(path.as_ref().to_path_buf())
+        .with_service_name(service_name)
+        .with_unlink_stale_socket()
+        .build();
+
+    serve(config, routes).await
+}
+
+/// Run the service described by `config` serving `routes` until `shutdown` resolves.
+///
+/// Once `shutdown` resolves the listener stops accepting new connections and every open
+/// connection is asked to finish its in-flight request and close.  Connections that are still
+/// open after the configured grace period are aborted.
+pub async fn serve_with_shutdown(
+    config: RuntimeConfig,
+    routes: Arc<Vec<Route>>,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    bind(config)
+        .await?
+        .serve_with_shutdown(routes, shutdown)
+        .await
+}
+
+/// Run every service in `services` concurrently, each described by its own config and serving
+/// its own routes, see [`serve_all_with_shutdown`].
+pub async fn serve_all(
+    services: impl IntoIterator<Item = (RuntimeConfig, Arc<Vec<Route>>)>,
+) -> anyhow::Result<()> {
+    serve_all_with_shutdown(services, futures::future::pending()).await
+}
+
+/// Run every service in `services` concurrently until `shutdown` resolves, for example to serve
+/// the routes of a service over mTLS on an internal address and admin routes in plaintext on
+/// localhost.
+///
+/// All listeners are bound before any connection is accepted, so a listener failing to bind
+/// fails the call without serving anything.  Once `shutdown` resolves, or a listener fails while
+/// serving, every listener shuts down like in [`serve_with_shutdown`] and the first error is
+/// returned.
+///
+/// ```no_run
+/// # async fn run(
+/// #     routes: std::sync::Arc<Vec<rustserve::Route>>,
+/// #     admin_routes: std::sync::Arc<Vec<rustserve::Route>>,
+/// # ) -> anyhow::Result<()> {
+/// use std::net::SocketAddr;
+///
+/// use rustserve_platform::runtime::{self, RuntimeConfig};
+///
+/// let internal: SocketAddr = "0.0.0.0:8443".parse()?;
+/// let admin: SocketAddr = "127.0.0.1:9000".parse()?;
+///
+/// runtime::serve_all([
+///     (RuntimeConfig::builder(internal).with_service_name("users").with_tls().build(), routes),
+///     (RuntimeConfig::builder(admin).with_service_name("users").build(), admin_routes),
+/// ])
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn serve_all_with_shutdown(
+    services: impl IntoIterator<Item = (RuntimeConfig, Arc<Vec<Route>>)>,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let mut servers = Vec::new();
+    for (config, routes) in services {
+        servers.push((bind(config).await?, routes));
+    }
+
+    let shutdown = shutdown.shared();
+    let (failed_tx, failed_rx) = watch::channel(());
 
-        //config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    let results = futures::future::join_all(servers.into_iter().map(|(server, routes)| {
+        let shutdown = shutdown.clone();
+        let mut failed_rx = failed_rx.clone();
+        let failed_tx = &failed_tx;
 
-        let acceptor = TlsAcceptor::from(Arc::new(config));
+        async move {
+            let stop = async move {
+                tokio::select! {
+                    _ = shutdown => {},
+                    _ = failed_rx.changed() => {},
+                }
+            };
 
-        loop {
-            let (tcp_stream, _) = listener.accept().await?;
-            serve_tls_connection(tcp_stream, &acceptor, routes.clone()).await?;
+            let res = server.serve_with_shutdown(routes, stop).await;
+            if res.is_err() {
+                let _ = failed_tx.send(());
+            }
+            res
         }
-    } else {
-        loop {
-            let (tcp_stream, _) = listener.accept().await?;
-            serve_connection(tcp_stream, routes.clone()).await?;
+    }))
+    .await;
+
+    results.into_iter().collect()
+}
+
+/// Bind the listener described by `config` without accepting any connections yet.
+///
+/// This lets callers binding to port `0` learn the port the OS assigned before serving.
+///
+/// ```no_run
+/// # async fn run(routes: std::sync::Arc<Vec<rustserve::Route>>) -> anyhow::Result<()> {
+/// use std::net::SocketAddr;
+///
+/// use rustserve_platform::runtime::{self, RuntimeConfig};
+///
+/// let addr: SocketAddr = "127.0.0.1:0".parse()?;
+/// let server = runtime::bind(RuntimeConfig::builder(addr).build()).await?;
+/// let addr = server.local_addr().expect("bound to a TCP address");
+///
+/// tokio::spawn(server.serve(routes));
+/// println!("listening on {addr}");
+/// # Ok(())
+/// # }
+/// ```
+pub async fn bind(config: RuntimeConfig) -> anyhow::Result<BoundServer> {
+    let listener = match &config.listen {
+        Listen::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).await?;
+
+            let tls = if config.use_tls {
+                Some(tls::tls_acceptor(&config)?)
+            } else {
+                None
+            };
+
+            BoundListener::Tcp(listener, tls)
+        }
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            let listener = listener::bind_unix(path, config.unlink_stale_socket)?;
+            BoundListener::Unix(listener, listener::SocketFile(path.clone()))
+        }
+    };
+
+    Ok(BoundServer {
+        listener,
+        config: Arc::new(config),
+    })
+}
+
+/// A service whose listener is bound, returned by [`bind`].
+pub struct BoundServer {
+    listener: BoundListener,
+    config: Arc<RuntimeConfig>,
+}
+
+enum BoundListener {
+    Tcp(TcpListener, Option<(TlsAcceptor, CertReloader)>),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, listener::SocketFile),
+}
+
+impl BoundServer {
+    /// The address the listener is bound to, or `None` for a Unix domain socket.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.listener {
+            BoundListener::Tcp(listener, _) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            BoundListener::Unix(..) => None,
+        }
+    }
+
+    /// A handle to reload the TLS certificate while serving, or `None` when TLS is disabled.
+    pub fn cert_reloader(&self) -> Option<CertReloader> {
+        match &self.listener {
+            BoundListener::Tcp(_, tls) => tls.as_ref().map(|(_, reloader)| reloader.clone()),
+            #[cfg(unix)]
+            BoundListener::Unix(..) => None,
+        }
+    }
+
+    /// Start accepting connections, serving `routes`.
+    pub async fn serve(self, routes: Arc<Vec<Route>>) -> anyhow::Result<()> {
+        self.serve_with_shutdown(routes, futures::future::pending())
+            .await
+    }
+
+    /// Start accepting connections, serving `routes` until `shutdown` resolves, see
+    /// [`serve_with_shutdown`](crate::runtime::serve_with_shutdown).
+    pub async fn serve_with_shutdown(
+        self,
+        routes: Arc<Vec<Route>>,
+        shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<()> {
+        match self.listener {
+            BoundListener::Tcp(listener, None) => {
+                accept_loop(listener, None, self.config, routes, shutdown).await
+            }
+            BoundListener::Tcp(listener, Some((acceptor, reloader))) => {
+                #[cfg(unix)]
+                let sighup = if self.config.reload_on_sighup {
+                    Some(tls::reload_on_sighup(reloader)?)
+                } else {
+                    None
+                };
+                #[cfg(not(unix))]
+                let _ = reloader;
+
+                let res =
+                    accept_loop(listener, Some(acceptor), self.config, routes, shutdown).await;
+
+                #[cfg(unix)]
+                if let Some(sighup) = sighup {
+                    sighup.abort();
+                }
+
+                res
+            }
+            #[cfg(unix)]
+            BoundListener::Unix(listener, _socket_file) => {
+                accept_loop(listener, None, self.config, routes, shutdown).await
+            }
         }
     }
 }
 
-async fn serve_tls_connection(
-    tcp_stream: TcpStream,
-    acceptor: &TlsAcceptor,
+async fn accept_loop<L: Accept>(
+    listener: L,
+    acceptor: Option<TlsAcceptor>,
+    config: Arc<RuntimeConfig>,
     routes: Arc<Vec<Route>>,
+    shutdown: impl Future<Output = ()>,
 ) -> anyhow::Result<()> {
-    let acceptor = acceptor.clone();
-    let routes = routes.clone();
-    tokio::spawn(async move {
-        let routes = routes.clone();
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let mut connections = JoinSet::new();
+    let connection_limit = config
+        .max_connections
+        .map(|max_connections| Arc::new(Semaphore::new(max_connections)));
 
-        let tls_stream = acceptor.accept(tcp_stream).await?;
+    tokio::pin!(shutdown);
 
-        let service = service_fn(move |req: Request<Incoming>| {
-            let routes = routes.clone();
-            async move { Ok::<_, anyhow::Error>(handle_request(req, routes).await?) }
-        });
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            Some(joined) = connections.join_next(), if !connections.is_empty() => {
+                log_connection_result(joined);
+            }
+            accepted = next_connection(
+                &listener,
+                connection_limit.as_ref(),
+                config.saturation_policy,
+            ) => {
+                let (stream, peer_addr, admission) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(err) if is_fatal_accept_error(&err) => return Err(err.into()),
+                    Err(err) => {
+                        tracing::warn!(error = %err, "failed to accept connection");
+                        if !is_connection_error(&err) {
+                            // Resource exhaustion (e.g. too many open files) clears up on its
+                            // own, back off instead of spinning on accept.
+                            tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                        }
+                        continue;
+                    }
+                };
+                if let Err(err) = L::configure(&stream, &config.socket_options) {
+                    tracing::warn!(error = %err, "failed to set socket options");
+                }
+                let config = config.clone();
+                let routes = routes.clone();
+                let shutdown_rx = shutdown_rx.clone();
+                let acceptor = acceptor.clone();
+                let span = tracing::debug_span!(
+                    "connection",
+                    service = %config.service_name,
+                    peer = tracing::field::Empty,
+                );
+                if let Some(peer_addr) = peer_addr {
+                    span.record("peer", tracing::field::display(peer_addr));
+                }
 
-        if let Err(err) = http1::Builder::new()
-            .serve_connection(tls_stream, service)
-            .await
-        {
-            println!("Error serving connection: {:?}", err);
+                let permit = match admission {
+                    Admission::Admitted(permit) => permit,
+                    Admission::Saturated { retry_after } => {
+                        let timeout = config.header_read_timeout;
+                        connections.spawn(
+                            serve_saturated(stream, acceptor, retry_after, timeout)
+                                .instrument(span),
+                        );
+                        continue;
+                    }
+                };
+
+                connections.spawn(
+                    async move {
+                        // Held for as long as the connection is open.
+                        let _permit = permit;
+                        #[cfg(feature = "metrics")]
+                        let _open_connection = config
+                            .metrics_path
+                            .is_some()
+                            .then(|| metrics::metrics().connection_opened());
+
+                        tracing::debug!("connection accepted");
+
+                        let info = ConnectionInfo {
+                            peer_addr: peer_addr.map(PeerAddr),
+                            ..ConnectionInfo::default()
+                        };
+
+                        let res = match acceptor {
+                            Some(acceptor) => {
+                                serve_tls_connection(
+                                    stream,
+                                    acceptor,
+                                    config,
+                                    routes,
+                                    shutdown_rx,
+                                    info,
+                                )
+                                .await
+                            }
+                            None => {
+                                serve_connection(
+                                    stream,
+                                    config,
+                                    routes,
+                                    shutdown_rx,
+                                    false,
+                                    info,
+                                )
+                                .await
+                            }
+                        };
+
+                        match res {
+                            Ok(()) => tracing::debug!("connection closed"),
+                            Err(err) => tracing::error!(error = %err, "error serving connection"),
+                        }
+                    }
+                    .instrument(span),
+                );
+            }
         }
+    }
 
-        Ok::<_, anyhow::Error>(())
-    });
+    drop(listener);
+    let _ = shutdown_tx.send(());
+
+    drain(connections, config.grace_period).await;
 
     Ok(())
 }
 
-async fn serve_connection(tcp_stream: TcpStream, routes: Arc<Vec<Route>>) -> anyhow::Result<()> {
-    let routes = routes.clone();
+// Whether an accepted connection is served.
+enum Admission {
+    // Served, holding a permit from the connection limit if there is one.
+    Admitted(Option<OwnedSemaphorePermit>),
+    // Over the connection limit, only answered with `503 Service Unavailable`.
+    Saturated { retry_after: Duration },
+}
 
-    tokio::spawn(async move {
-        let service = service_fn(move |req: Request<Incoming>| {
-            let routes = routes.clone();
-            async move { Ok::<_, anyhow::Error>(handle_request(req, routes).await?) }
-        });
+// Accept the next connection, along with a permit from `connection_limit` if there is one.
+async fn next_connection<L: Accept>(
+    listener: &L,
+    connection_limit: Option<&Arc<Semaphore>>,
+    saturation_policy: SaturationPolicy,
+) -> io::Result<(L::Io, Option<SocketAddr>, Admission)> {
+    let connection_limit = match connection_limit {
+        Some(connection_limit) => connection_limit,
+        None => {
+            let (stream, peer_addr) = listener.accept().await?;
+            return Ok((stream, peer_addr, Admission::Admitted(None)));
+        }
+    };
 
-        if let Err(err) = http1::Builder::new()
-            .serve_connection(tcp_stream, service)
-            .await
-        {
-            println!("Error serving connection: {:?}", err);
+    match saturation_policy {
+        SaturationPolicy::Wait => {
+            let permit = connection_limit
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("connection limit semaphore is never closed");
+            let (stream, peer_addr) = listener.accept().await?;
+            Ok((stream, peer_addr, Admission::Admitted(Some(permit))))
         }
+        SaturationPolicy::Close => loop {
+            let (stream, peer_addr) = listener.accept().await?;
+            match connection_limit.clone().try_acquire_owned() {
+                Ok(permit) => return Ok((stream, peer_addr, Admission::Admitted(Some(permit)))),
+                Err(_) => {
+                    tracing::debug!(?peer_addr, "connection limit reached, closing connection");
+                    drop(stream);
+                }
+            }
+        },
+        SaturationPolicy::ServiceUnavailable { retry_after } => {
+            let (stream, peer_addr) = listener.accept().await?;
+            match connection_limit.clone().try_acquire_owned() {
+                Ok(permit) => Ok((stream, peer_addr, Admission::Admitted(Some(permit)))),
+                Err(_) => {
+                    tracing::debug!(?peer_addr, "connection limit reached, rejecting connection");
+                    Ok((stream, peer_addr, Admission::Saturated { retry_after }))
+                }
+            }
+        }
+    }
+}
+
+// Answer the first request on a connection over the connection limit with `503 Service
+// Unavailable`, then close it.  Gives up on clients that take longer than `timeout`, TLS
+// handshake included, so rejected connections can't pile up.
+async fn serve_saturated<I>(
+    stream: I,
+    acceptor: Option<TlsAcceptor>,
+    retry_after: Duration,
+    timeout: Duration,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let res = tokio::time::timeout(timeout, async move {
+        match acceptor {
+            Some(acceptor) => {
+                let tls_stream = acceptor.accept(stream).await?;
+                let use_h2 = tls_stream.get_ref().1.alpn_protocol() == Some(H2_ALPN);
+                serve_unavailable(tls_stream, use_h2, retry_after).await
+            }
+            None => serve_unavailable(stream, false, retry_after).await,
+        }
+    })
+    .await;
+
+    match res {
+        Ok(Ok(())) => tracing::debug!("rejected connection closed"),
+        Ok(Err(err)) => tracing::debug!(error = %err, "error rejecting connection"),
+        Err(_) => tracing::debug!("timed out rejecting connection"),
+    }
+}
+
+async fn serve_unavailable<I>(io: I, use_h2: bool, retry_after: Duration) -> anyhow::Result<()>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // Shut the connection down gracefully as soon as a request is answered.
+    let (answered_tx, mut answered) = watch::channel(());
+    let retry_after = retry_after.as_secs_f64().ceil() as u64;
 
-        Ok::<_, anyhow::Error>(())
+    let service = service_fn(move |req: Request<Incoming>| {
+        let _ = answered_tx.send(());
+        async move {
+            let mut res = platform_error(ServiceUnavailableError::new());
+            res.headers_mut().insert(RETRY_AFTER, retry_after.into());
+            if req.version() < Version::HTTP_2 {
+                res.headers_mut()
+                    .insert(CONNECTION, HeaderValue::from_static("close"));
+            }
+            Ok::<_, anyhow::Error>(res.map(|body| Full::new(Bytes::from(body))))
+        }
     });
 
-    Ok(())
+    let res = if use_h2 {
+        let connection = http2::Builder::new(TokioExecutor).serve_connection(io, service);
+        serve_until_shutdown(connection, &mut answered, |conn| conn.graceful_shutdown()).await
+    } else {
+        let connection = http1::Builder::new()
+            .keep_alive(false)
+            .serve_connection(io, service);
+        serve_until_shutdown(connection, &mut answered, |conn| conn.graceful_shutdown()).await
+    };
+
+    Ok(res?)
+}
+
+// Errors that only affect the connection being accepted.
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+    )
+}
+
+// Errors that mean the listener itself is no longer usable.
+fn is_fatal_accept_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::InvalidInput | io::ErrorKind::NotConnected | io::ErrorKind::Unsupported
+    )
+}
+
+fn log_connection_result(joined: Result<(), JoinError>) {
+    if let Err(err) = joined {
+        if err.is_panic() {
+            tracing::error!(error = %err, "connection task panicked");
+        }
+    }
+}
+
+async fn drain(mut connections: JoinSet<()>, grace_period: Duration) {
+    let drained = tokio::time::timeout(grace_period, async {
+        while let Some(joined) = connections.join_next().await {
+            log_connection_result(joined);
+        }
+    })
+    .await;
+
+    if drained.is_err() {
+        tracing::warn!(
+            remaining = connections.len(),
+            "grace period elapsed, aborting remaining connections"
+        );
+        connections.shutdown().await;
+    }
+}
+
+async fn serve_tls_connection<I>(
+    stream: I,
+    acceptor: TlsAcceptor,
+    config: Arc<RuntimeConfig>,
+    routes: Arc<Vec<Route>>,
+    shutdown: watch::Receiver<()>,
+    mut info: ConnectionInfo,
+) -> anyhow::Result<()>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let peer = info.peer_addr.map(|PeerAddr(addr)| addr);
+    let handshake = tokio::time::timeout(config.header_read_timeout, acceptor.accept(stream));
+
+    let tls_stream = match handshake.await {
+        Ok(Ok(tls_stream)) => tls_stream,
+        Ok(Err(err)) => {
+            // Misconfigured clients are worth a warning, peers hanging up mid handshake such as
+            // load balancer TCP checks are not.
+            match handshake_failure(&err) {
+                Some(kind) => {
+                    tracing::warn!(?peer, kind = %kind, error = %err, "TLS handshake failed")
+                }
+                None => tracing::debug!(?peer, error = %err, "TLS handshake aborted"),
+            }
+            return Ok(());
+        }
+        Err(_) => {
+            let timeout = config.header_read_timeout;
+            tracing::debug!(?peer, ?timeout, "TLS handshake timed out");
+            return Ok(());
+        }
+    };
+    let (_, session) = tls_stream.get_ref();
+
+    let use_h2 = session.alpn_protocol() == Some(H2_ALPN);
+    info.peer_certificates = session
+        .peer_certificates()
+        .map(|certs| PeerCertificates(Arc::new(certs.to_vec())));
+    info.client_identity = session
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .and_then(ClientIdentity::from_certificate);
+
+    serve_connection(tls_stream, config, routes, shutdown, use_h2, info).await
+}
+
+async fn serve_connection<I>(
+    io: I,
+    config: Arc<RuntimeConfig>,
+    routes: Arc<Vec<Route>>,
+    mut shutdown: watch::Receiver<()>,
+    use_h2: bool,
+    info: ConnectionInfo,
+) -> anyhow::Result<()>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut http1_builder = http1::Builder::new();
+    http1_builder
+        .keep_alive(config.keep_alive)
+        .header_read_timeout(config.header_read_timeout)
+        .preserve_header_case(config.preserve_header_case);
+    if let Some(max_buf_size) = config.max_buf_size {
+        http1_builder.max_buf_size(max_buf_size);
+    }
+
+    let service = service_fn(move |req: Request<Incoming>| {
+        let config = config.clone();
+        let routes = routes.clone();
+        let info = info.clone();
+        async move { Ok::<_, anyhow::Error>(handle_request(req, config, routes, info).await?) }
+    });
+
+    let res = if use_h2 {
+        let connection = http2::Builder::new(TokioExecutor).serve_connection(io, service);
+        serve_until_shutdown(connection, &mut shutdown, |conn| conn.graceful_shutdown()).await
+    } else {
+        let connection = http1_builder.serve_connection(io, service);
+        serve_until_shutdown(connection, &mut shutdown, |conn| conn.graceful_shutdown()).await
+    };
+
+    Ok(res?)
+}
+
+// Drive `connection` to completion, asking it to finish up gracefully once `shutdown` fires.
+async fn serve_until_shutdown<C>(
+    connection: C,
+    shutdown: &mut watch::Receiver<()>,
+    graceful_shutdown: impl FnOnce(Pin<&mut C>),
+) -> Result<(), hyper::Error>
+where
+    C: Future<Output = Result<(), hyper::Error>>,
+{
+    tokio::pin!(connection);
+
+    tokio::select! {
+        res = connection.as_mut() => res,
+        _ = shutdown.changed() => {
+            graceful_shutdown(connection.as_mut());
+            connection.await
+        }
+    }
+}
+
+/// Executor handing hyper's HTTP/2 stream tasks to the tokio runtime.
+#[derive(Clone, Copy)]
+pub(crate) struct TokioExecutor;
+
+impl<F> hyper::rt::Executor<F> for TokioExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        tokio::spawn(fut);
+    }
 }
 
 async fn handle_request<'a>(
     req: Request<Incoming>,
+    config: Arc<RuntimeConfig>,
     routes: Arc<Vec<Route>>,
+    info: ConnectionInfo,
 ) -> anyhow::Result<http::Response<Full<Bytes>>> {
-    let (parts, body) = req.into_parts();
+    let version = req.version();
+    let request_timeout = config.request_timeout;
+
+    if let Some(health_checks) = &config.health_checks {
+        if let Some(res) = health_checks.respond(req.method(), req.uri().path()) {
+            return Ok(res?.map(|body| Full::new(Bytes::from(body))));
+        }
+    }
+
+    if let Some(maintenance_routes) = &config.maintenance_routes {
+        let peer_ip = info.peer_addr.map(|PeerAddr(addr)| addr.ip());
+        if let Some(res) =
+            maintenance_routes.respond(req.method(), req.uri().path(), req.headers(), peer_ip)
+        {
+            return Ok(res?.map(|body| Full::new(Bytes::from(body))));
+        }
+    }
+
+    for static_files in &config.static_files {
+        if let Some(res) = static_files.respond(req.method(), req.uri().path()).await {
+            return Ok(res?.map(|body| Full::new(Bytes::from(body))));
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    let in_flight = match &config.metrics_path {
+        Some(path) if req.method() == http::Method::GET && req.uri().path() == path => {
+            let res = metrics::metrics().response()?;
+            return Ok(res.map(|body| Full::new(Bytes::from(body))));
+        }
+        Some(_) => Some(metrics::metrics().request_started(req.method())),
+        None => None,
+    };
+
+    #[cfg(feature = "opentelemetry")]
+    let span = crate::otel::request_span(&req);
 
-    let mut buf = body.collect().await?.aggregate();
-    let bytes = buf.copy_to_bytes(buf.remaining());
+    let routed = route(req, config, routes, info);
+    #[cfg(feature = "opentelemetry")]
+    let routed = routed.instrument(span);
 
-    let res = rustserve::route_request(Request::from_parts(parts, &bytes[..]), routes).await?;
+    let res = with_deadlines(routed, request_timeout, version).await?;
+
+    #[cfg(feature = "metrics")]
+    if let Some(in_flight) = in_flight {
+        in_flight.finish(res.status());
+    }
 
     Ok::<_, anyhow::Error>(res.map(|body| Full::new(Bytes::from(body))))
 }
+
+// Run `routed` in a request context, answering with `504 Gateway Timeout` once the deadline set
+// by the timeout filters of its route or `request_timeout` passes, whichever is first.
+async fn with_deadlines(
+    routed: impl Future<Output = anyhow::Result<http::Response<Vec<u8>>>>,
+    request_timeout: Duration,
+    version: Version,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    // Timeout filters of the route tighten the deadline once they run.
+    let (deadline, deadline_rx) = RouteDeadline::new();
+    let routed = context::scope(async move {
+        context::insert(deadline);
+        routed.await
+    });
+
+    let routed = async move {
+        tokio::select! {
+            res = routed => Some(res),
+            () = deadline_passed(deadline_rx) => None,
+        }
+    };
+
+    match tokio::time::timeout(request_timeout, routed).await {
+        Ok(Some(res)) => res,
+        Ok(None) => {
+            tracing::warn!("request exceeded the timeout of its route");
+            gateway_timeout(version)
+        }
+        Err(_) => {
+            tracing::warn!(timeout = ?request_timeout, "request timed out");
+            gateway_timeout(version)
+        }
+    }
+}
+
+// Answer a request that timed out, closing HTTP/1 connections after it.
+fn gateway_timeout(version: Version) -> anyhow::Result<http::Response<Vec<u8>>> {
+    let mut res = platform_error(GatewayTimeoutError::new());
+    if version < Version::HTTP_2 {
+        res.headers_mut()
+            .insert(CONNECTION, HeaderValue::from_static("close"));
+    }
+
+    Ok(res)
+}
+
+// Read the body of `req` and hand it to the router.
+async fn route(
+    req: Request<Incoming>,
+    config: Arc<RuntimeConfig>,
+    routes: Arc<Vec<Route>>,
+    info: ConnectionInfo,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    let (mut parts, body) = req.into_parts();
+
+    let head_as_get = config.head_as_get && parts.method == http::Method::HEAD;
+    if head_as_get {
+        parts.method = http::Method::GET;
+    }
+
+    let bytes = match collect_body(body, config.max_body_size).await? {
+        Some(bytes) => bytes,
+        None => {
+            return Ok(platform_error(PayloadTooLargeError::new(
+                config.max_body_size,
+            )));
+        }
+    };
+
+    let bytes = match decompress(&mut parts.headers, bytes, config.max_body_size) {
+        Ok(bytes) => bytes,
+        Err(DecompressError::TooLarge) => {
+            return Ok(platform_error(PayloadTooLargeError::new(
+                config.max_body_size,
+            )));
+        }
+        Err(DecompressError::Invalid(err)) => {
+            return Ok(platform_error(InvalidPayloadError::new(format!(
+                "failed to decompress request body: {err}"
+            ))));
+        }
+        Err(DecompressError::Unsupported(encoding)) => {
+            return Ok(platform_error(UnsupportedMediaTypeError::new(encoding)));
+        }
+    };
+
+    let mut req = Request::from_parts(parts, &bytes[..]);
+    if let Some(peer_addr) = info.peer_addr {
+        req.extensions_mut().insert(peer_addr);
+    }
+    if let Some(peer_certificates) = info.peer_certificates {
+        req.extensions_mut().insert(peer_certificates);
+    }
+    if let Some(client_identity) = info.client_identity {
+        req.extensions_mut().insert(client_identity);
+    }
+
+    let res = catch_panic(rustserve::route_request(req, routes)).await?;
+
+    Ok(if head_as_get { strip_body(res) } else { res })
+}
+
+// Answer the request with `500 Internal Server Error` if `routed` panics, so a panicking handler
+// or filter only takes down its own request, not the whole connection.
+async fn catch_panic(
+    routed: impl Future<Output = anyhow::Result<http::Response<Vec<u8>>>>,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    match AssertUnwindSafe(routed).catch_unwind().await {
+        Ok(res) => res,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            tracing::error!(panic = message, "request handler panicked");
+
+            Ok(platform_error(InternalServerError::new(format!(
+                "request handler panicked: {message}"
+            ))))
+        }
+    }
+}
+
+// Leave out the body of a `GET` response answering a `HEAD` request, keeping its length.
+fn strip_body(res: http::Response<Vec<u8>>) -> http::Response<Vec<u8>> {
+    let (mut parts, body) = res.into_parts();
+    parts
+        .headers
+        .entry(CONTENT_LENGTH)
+        .or_insert_with(|| HeaderValue::from(body.len()));
+
+    http::Response::from_parts(parts, Vec::new())
+}
+
+// Collect `body` into memory, or `None` if it is larger than `max_body_size` bytes.
+async fn collect_body(body: Incoming, max_body_size: usize) -> anyhow::Result<Option<Bytes>> {
+    let declared_size = body.size_hint().exact().unwrap_or(0);
+    if declared_size > max_body_size as u64 {
+        return Ok(None);
+    }
+
+    match Limited::new(body, max_body_size).collect().await {
+        // A body that arrived in a single frame is handed on without being copied.
+        Ok(collected) => Ok(Some(collected.to_bytes())),
+        Err(err) if err.is::<LengthLimitError>() => Ok(None),
+        Err(err) => Err(anyhow::anyhow!(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+    use http_body_util::Empty;
+    use tokio::sync::oneshot;
+
+    use super::*;
+    use crate::filters::TimeoutFilter;
+    use crate::mtls::TlsVersion;
+    use crate::testing::{self, TempDir, TestCa};
+
+    // Serve `config` without any service routes, until the returned sender is dropped.
+    async fn start(config: RuntimeConfig) -> (SocketAddr, oneshot::Sender<()>) {
+        let server = bind(config).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let (stop, stopped) = oneshot::channel::<()>();
+
+        tokio::spawn(server.serve_with_shutdown(Arc::new(Vec::new()), async {
+            let _ = stopped.await;
+        }));
+
+        (addr, stop)
+    }
+
+    // A TLS service presenting a certificate for `localhost` issued by `ca`, answering health
+    // checks.
+    fn tls_config(ca: &TestCa, dir: &TempDir) -> RuntimeConfigBuilder {
+        ca.issue(dir.path(), &["localhost"]);
+
+        RuntimeConfig::builder(testing::localhost())
+            .with_service_name("test")
+            .with_tls()
+            .with_cert_paths(dir.path().join("end.cert"), dir.path().join("end.key"))
+            .with_health_checks(HealthChecks::always_ready())
+    }
+
+    #[tokio::test]
+    async fn failed_handshake_does_not_stop_the_accept_loop() {
+        let ca = TestCa::new();
+        let dir = TempDir::new();
+        let (addr, _stop) = start(tls_config(&ca, &dir).build()).await;
+
+        // Plaintext HTTP instead of a TLS client hello.
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.writable().await.unwrap();
+        stream.try_write(b"GET /healthz HTTP/1.1\r\n\r\n").unwrap();
+        drop(stream);
+
+        let res = ca
+            .client(addr, "localhost")
+            .send(testing::get(DEFAULT_LIVENESS_PATH))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn serves_http2_when_negotiated() {
+        let ca = TestCa::new();
+        let dir = TempDir::new();
+        let (addr, _stop) = start(tls_config(&ca, &dir).build()).await;
+
+        let client = ca.client(addr, "localhost").with_prefer_h2(true);
+        let (mut request_sender, connection) = client.connect::<Empty<Bytes>>().await.unwrap();
+        tokio::spawn(connection);
+        assert!(request_sender.is_http2());
+
+        let req = hyper::Request::get("https://localhost/healthz")
+            .body(Empty::new())
+            .unwrap();
+        let res = request_sender.send_request(req).await.unwrap();
+
+        assert_eq!(res.version(), Version::HTTP_2);
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn times_out_stalled_handshakes() {
+        let ca = TestCa::new();
+        let dir = TempDir::new();
+        let config = tls_config(&ca, &dir)
+            .with_header_read_timeout(Duration::from_millis(100))
+            .with_max_connections(1)
+            .build();
+        let (addr, _stop) = start(config).await;
+
+        // Holds the only connection without ever sending a client hello.
+        let _stalled = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let client = ca.client(addr, "localhost");
+        let res = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.send(testing::get(DEFAULT_LIVENESS_PATH)),
+        )
+        .await
+        .expect("the stalled handshake keeps holding the connection")
+        .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn defers_connections_over_the_limit() {
+        let config = RuntimeConfig::builder(testing::localhost())
+            .with_max_connections(1)
+            .with_health_checks(HealthChecks::always_ready())
+            .build();
+        let (addr, _stop) = start(config).await;
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut first, connection) = hyper::client::conn::http1::handshake(stream).await.unwrap();
+        tokio::spawn(connection);
+        let status = first
+            .send_request(testing::get(DEFAULT_LIVENESS_PATH))
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::OK);
+
+        let second = tokio::spawn(testing::send(addr, testing::get(DEFAULT_LIVENESS_PATH)));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!second.is_finished());
+
+        // Closing the first connection lets the second one in.
+        drop(first);
+        let res = tokio::time::timeout(Duration::from_secs(5), second)
+            .await
+            .expect("the second connection is still deferred")
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn selects_the_certificate_by_sni_hostname() {
+        let ca = TestCa::new();
+        let dir = TempDir::new();
+        ca.issue(&dir.path().join("a"), &["a.test"]);
+        ca.issue(&dir.path().join("b"), &["b.test"]);
+        let config = RuntimeConfig::builder(testing::localhost())
+            .with_tls()
+            .with_sni_cert("a.test", dir.path().join("a"))
+            .with_sni_cert("B.test", dir.path().join("b"))
+            .with_sni_fallback(SniFallback::Reject)
+            .with_health_checks(HealthChecks::always_ready())
+            .build();
+        let (addr, _stop) = start(config).await;
+
+        // Each client only accepts a certificate for its own hostname.
+        for host in ["a.test", "b.test"] {
+            let res = ca
+                .client(addr, host)
+                .send(testing::get(DEFAULT_LIVENESS_PATH))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        let unknown = ca
+            .client(addr, "c.test")
+            .send(testing::get(DEFAULT_LIVENESS_PATH))
+            .await;
+        assert!(unknown.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_tls12_clients_when_tls13_is_required() {
+        let ca = TestCa::new();
+        let dir = TempDir::new();
+        let config = tls_config(&ca, &dir)
+            .with_tls_versions(TlsVersion::Tls13, TlsVersion::Tls13)
+            .build();
+        let (addr, _stop) = start(config).await;
+
+        let tls12 = ca
+            .client(addr, "localhost")
+            .with_tls_versions(TlsVersion::Tls12, TlsVersion::Tls12)
+            .send(testing::get(DEFAULT_LIVENESS_PATH))
+            .await;
+        assert!(tls12.is_err());
+
+        let res = ca
+            .client(addr, "localhost")
+            .send(testing::get(DEFAULT_LIVENESS_PATH))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn logs_handshakes_with_untrusted_client_certs() {
+        let ca = TestCa::new();
+        let dir = TempDir::new();
+        std::fs::write(dir.path().join("ca.pem"), ca.pem()).unwrap();
+        let config = tls_config(&ca, &dir)
+            .with_client_ca(dir.path().join("ca.pem"))
+            .build();
+
+        let logs = testing::capture_logs();
+        let (addr, _stop) = start(config).await;
+
+        let untrusted = TestCa::new();
+        let untrusted_dir = TempDir::new();
+        untrusted.issue(untrusted_dir.path(), &["client"]);
+        let res = ca
+            .client(addr, "localhost")
+            .with_client_cert(
+                untrusted_dir.path().join("end.cert"),
+                untrusted_dir.path().join("end.key"),
+            )
+            .unwrap()
+            .send(testing::get(DEFAULT_LIVENESS_PATH))
+            .await;
+        assert!(res.is_err());
+
+        // The server keeps accepting clients with trusted certificates.
+        let trusted_dir = TempDir::new();
+        ca.issue(trusted_dir.path(), &["client"]);
+        let res = ca
+            .client(addr, "localhost")
+            .with_client_cert(
+                trusted_dir.path().join("end.cert"),
+                trusted_dir.path().join("end.key"),
+            )
+            .unwrap()
+            .send(testing::get(DEFAULT_LIVENESS_PATH))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let logs = logs.contents();
+        assert!(logs.contains("TLS handshake failed"), "{logs}");
+        assert!(logs.contains("unknown CA"), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn serves_every_listener_until_shutdown() {
+        // `serve_all_with_shutdown` binds the listeners itself, so pick free ports up front.
+        let free_addr = || {
+            let listener = std::net::TcpListener::bind(testing::localhost()).unwrap();
+            listener.local_addr().unwrap()
+        };
+        let (internal, admin) = (free_addr(), free_addr());
+
+        let ca = TestCa::new();
+        let dir = TempDir::new();
+        ca.issue(dir.path(), &["localhost"]);
+        let internal_config = RuntimeConfig::builder(internal)
+            .with_tls()
+            .with_cert_paths(dir.path().join("end.cert"), dir.path().join("end.key"))
+            .with_health_checks(HealthChecks::always_ready())
+            .build();
+        let admin_config = RuntimeConfig::builder(admin)
+            .with_health_checks(HealthChecks::always_ready())
+            .build();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let served = tokio::spawn(serve_all_with_shutdown(
+            [
+                (internal_config, Arc::new(Vec::new())),
+                (admin_config, Arc::new(Vec::new())),
+            ],
+            async {
+                let _ = stopped.await;
+            },
+        ));
+
+        while tokio::net::TcpStream::connect(admin).await.is_err() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let res = ca
+            .client(internal, "localhost")
+            .send(testing::get(DEFAULT_LIVENESS_PATH))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = testing::send(admin, testing::get(DEFAULT_LIVENESS_PATH)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        drop(stop);
+        tokio::time::timeout(Duration::from_secs(5), served)
+            .await
+            .expect("shutdown stops every listener")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_bodies_over_the_limit() {
+        let config = RuntimeConfig::builder(testing::localhost())
+            .with_max_body_size(16)
+            .build();
+        let (addr, _stop) = start(config).await;
+
+        let req = hyper::Request::post("/users")
+            .header(http::header::HOST, "localhost")
+            .body(Full::new(Bytes::from(vec![b'a'; 17])))
+            .unwrap();
+        let res = testing::send(addr, req).await;
+
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn answers_panicking_requests_with_500() {
+        async fn panicking_route() -> anyhow::Result<http::Response<Vec<u8>>> {
+            panic!("boom")
+        }
+
+        let res = catch_panic(panicking_route()).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["error"], "internal server error");
+    }
+
+    #[tokio::test]
+    async fn passes_through_requests_that_dont_panic() {
+        let res = catch_panic(async { Ok(http::Response::new(b"ok".to_vec())) })
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    // A route behind timeout filters of `timeouts` that takes `takes` to answer.
+    async fn slow_route(
+        timeouts: &[Duration],
+        takes: Duration,
+    ) -> anyhow::Result<http::Response<Vec<u8>>> {
+        for timeout in timeouts {
+            let filter = Arc::new(TimeoutFilter::new(*timeout));
+            let req = http::Request::get("/reports").body(&b""[..]).unwrap();
+            assert!(testing::filter_request(&filter, req, HashMap::new())
+                .await
+                .is_ok());
+        }
+
+        tokio::time::sleep(takes).await;
+        Ok(http::Response::new(b"ok".to_vec()))
+    }
+
+    #[tokio::test]
+    async fn answers_requests_over_their_route_timeout_with_504() {
+        let routed = slow_route(&[Duration::from_millis(50)], Duration::from_secs(5));
+
+        let started_at = std::time::Instant::now();
+        let res = with_deadlines(routed, Duration::from_secs(10), Version::HTTP_11)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(res.headers()[CONNECTION], "close");
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn applies_the_stricter_of_the_route_and_request_timeouts() {
+        // The request timeout is stricter than the route's.
+        let routed = slow_route(&[Duration::from_secs(5)], Duration::from_secs(5));
+        let started_at = std::time::Instant::now();
+        let res = with_deadlines(routed, Duration::from_millis(50), Version::HTTP_2)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+
+        // Of several timeout filters the strictest is in effect, whatever their order.
+        let (strict, lax) = (Duration::from_millis(50), Duration::from_secs(5));
+        for timeouts in [[strict, lax], [lax, strict]] {
+            let routed = slow_route(&timeouts, Duration::from_millis(500));
+            let res = with_deadlines(routed, Duration::from_secs(10), Version::HTTP_2)
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+        }
+
+        // Routes answering within both timeouts are unaffected.
+        let routed = slow_route(&[Duration::from_millis(500)], Duration::from_millis(10));
+        let res = with_deadlines(routed, Duration::from_secs(10), Version::HTTP_2)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn strips_bodies_keeping_their_length() {
+        let res = http::Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(br#"{"id":1}"#.to_vec())
+            .unwrap();
+
+        let res = strip_body(res);
+
+        assert!(res.body().is_empty());
+        assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(res.headers()[CONTENT_LENGTH], "8");
+    }
+
+    #[test]
+    fn keeps_declared_lengths_when_stripping_bodies() {
+        let res = http::Response::builder()
+            .header(CONTENT_LENGTH, "1024")
+            .body(Vec::new())
+            .unwrap();
+
+        let res = strip_body(res);
+
+        assert_eq!(res.headers()[CONTENT_LENGTH], "1024");
+    }
+}
@@ -0,0 +1,592 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{HealthChecks, MaintenanceRoutes, StaticFiles};
+use crate::mtls::{SocketOptions, TlsVersion};
+
+/// How long the runtime waits for in-flight connections to finish once shutdown has been
+/// requested.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// The largest request body the runtime accepts unless configured otherwise, 2 MiB.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// How long a request may take, from reading its body to producing a response, unless configured
+/// otherwise.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long HTTP/1 clients are given to send the complete request head unless configured
+/// otherwise, cutting off clients that trickle in headers to hold connections open.
+pub const DEFAULT_HEADER_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The environment variable the passphrase of a PKCS#12 bundle is read from when none is
+/// configured with [`RuntimeConfigBuilder::with_pkcs12_passphrase`].
+pub const PKCS12_PASSPHRASE_VAR: &str = "PKCS12_PASSPHRASE";
+
+/// Where a service listens for connections.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Listen {
+    /// Listen on a TCP socket address.
+    Tcp(SocketAddr),
+    /// Listen on a Unix domain socket at the given path.  Connections are never served over TLS.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl From<SocketAddr> for Listen {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Tcp(addr)
+    }
+}
+
+#[cfg(unix)]
+impl From<PathBuf> for Listen {
+    fn from(path: PathBuf) -> Self {
+        Self::Unix(path)
+    }
+}
+
+/// What the runtime does with new connections once the connection limit has been reached.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SaturationPolicy {
+    /// Stop accepting until an open connection closes, leaving new connections in the listen
+    /// backlog.
+    #[default]
+    Wait,
+    /// Keep accepting and immediately close connections over the limit.
+    Close,
+    /// Keep accepting and answer the first request on connections over the limit with
+    /// `503 Service Unavailable` and a `Retry-After` of `retry_after`, rounded up to whole
+    /// seconds, before closing them.
+    ///
+    /// Unlike [`SaturationPolicy::Close`] this tells clients and load balancers to back off.
+    /// Connections that send no request within the header read timeout are closed.
+    ServiceUnavailable {
+        /// How long clients are asked to wait before retrying.
+        retry_after: Duration,
+    },
+}
+
+/// Which certificate is presented to clients whose SNI hostname has no certificate configured
+/// with [`RuntimeConfigBuilder::with_sni_cert`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SniFallback {
+    /// Present the service certificate found under `{CERTIFICATE_ROOT}/{service_name}`, or the
+    /// one configured with [`RuntimeConfigBuilder::with_cert_paths`].
+    #[default]
+    Default,
+    /// Fail the handshake.
+    Reject,
+}
+
+/// Configuration for a service run by [`serve`](super::serve).
+///
+/// # Examples
+///
+/// ```
+/// use std::net::SocketAddr;
+///
+/// use rustserve_platform::runtime::RuntimeConfig;
+///
+/// let addr: SocketAddr = "127.0.0.1:8443".parse().unwrap();
+/// let config = RuntimeConfig::builder(addr)
+///     .with_service_name("users")
+///     .with_tls()
+///     .build();
+///
+/// assert_eq!(config.service_name(), "users");
+/// assert!(config.use_tls());
+/// ```
+#[derive(Clone, Debug)]
+pub struct RuntimeConfig {
+    pub(super) listen: Listen,
+    pub(super) unlink_stale_socket: bool,
+    pub(super) service_name: String,
+    pub(super) use_tls: bool,
+    pub(super) cert_paths: Option<(PathBuf, PathBuf)>,
+    pub(super) pkcs12_bundle: Option<PathBuf>,
+    pub(super) pkcs12_passphrase: Option<Passphrase>,
+    pub(super) client_ca_path: Option<PathBuf>,
+    pub(super) tls_versions: (TlsVersion, TlsVersion),
+    pub(super) session_cache_size: Option<usize>,
+    pub(super) session_tickets: bool,
+    pub(super) grace_period: Duration,
+    pub(super) max_body_size: usize,
+    pub(super) request_timeout: Duration,
+    pub(super) keep_alive: bool,
+    pub(super) header_read_timeout: Duration,
+    pub(super) preserve_header_case: bool,
+    pub(super) max_buf_size: Option<usize>,
+    pub(super) max_connections: Option<usize>,
+    pub(super) socket_options: SocketOptions,
+    pub(super) saturation_policy: SaturationPolicy,
+    pub(super) head_as_get: bool,
+    #[cfg(unix)]
+    pub(super) reload_on_sighup: bool,
+    pub(super) sni_certs: Vec<(String, PathBuf)>,
+    pub(super) sni_fallback: SniFallback,
+    pub(super) health_checks: Option<HealthChecks>,
+    pub(super) maintenance_routes: Option<MaintenanceRoutes>,
+    pub(super) static_files: Vec<StaticFiles>,
+    #[cfg(feature = "metrics")]
+    pub(super) metrics_path: Option<String>,
+}
+
+impl RuntimeConfig {
+    /// Start building a configuration for a service listening on `listen`, either a
+    /// [`SocketAddr`] or the [`PathBuf`] of a Unix domain socket.
+    pub fn builder(listen: impl Into<Listen>) -> RuntimeConfigBuilder {
+        RuntimeConfigBuilder {
+            config: Self {
+                listen: listen.into(),
+                unlink_stale_socket: false,
+                service_name: String::new(),
+                use_tls: false,
+                cert_paths: None,
+                pkcs12_bundle: None,
+                pkcs12_passphrase: None,
+                client_ca_path: None,
+                tls_versions: (TlsVersion::Tls12, TlsVersion::Tls13),
+                session_cache_size: None,
+                session_tickets: false,
+                grace_period: DEFAULT_GRACE_PERIOD,
+                max_body_size: DEFAULT_MAX_BODY_SIZE,
+                request_timeout: DEFAULT_REQUEST_TIMEOUT,
+                keep_alive: true,
+                header_read_timeout: DEFAULT_HEADER_READ_TIMEOUT,
+                preserve_header_case: false,
+                max_buf_size: None,
+                max_connections: None,
+                socket_options: SocketOptions::default(),
+                saturation_policy: SaturationPolicy::default(),
+                head_as_get: false,
+                #[cfg(unix)]
+                reload_on_sighup: false,
+                sni_certs: Vec::new(),
+                sni_fallback: SniFallback::default(),
+                health_checks: None,
+                maintenance_routes: None,
+                static_files: Vec::new(),
+                #[cfg(feature = "metrics")]
+                metrics_path: None,
+            },
+        }
+    }
+
+    /// Where the service listens for connections.
+    pub fn listen(&self) -> &Listen {
+        &self.listen
+    }
+
+    /// The name of the service, used to locate its certificates.
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    /// Whether connections are served over TLS.
+    pub fn use_tls(&self) -> bool {
+        self.use_tls
+    }
+
+    /// The certificate chain and private key files presented to clients, if configured
+    /// explicitly instead of found under `{CERTIFICATE_ROOT}/{service_name}`.
+    pub fn cert_paths(&self) -> Option<(&PathBuf, &PathBuf)> {
+        self.cert_paths.as_ref().map(|(cert, key)| (cert, key))
+    }
+
+    /// The PKCS#12 bundle holding the certificate chain and private key presented to clients, if
+    /// configured with [`RuntimeConfigBuilder::with_pkcs12`].
+    pub fn pkcs12_bundle(&self) -> Option<&PathBuf> {
+        self.pkcs12_bundle.as_ref()
+    }
+
+    /// The CA bundle client certificates are verified against, if client auth is required.
+    pub fn client_ca_path(&self) -> Option<&PathBuf> {
+        self.client_ca_path.as_ref()
+    }
+
+    /// The lowest and highest TLS versions negotiated with clients.
+    pub fn tls_versions(&self) -> (TlsVersion, TlsVersion) {
+        self.tls_versions
+    }
+
+    /// How many TLS sessions are cached server side for resumption, if configured with
+    /// [`RuntimeConfigBuilder::with_session_cache`] instead of left at the rustls default.
+    pub fn session_cache_size(&self) -> Option<usize> {
+        self.session_cache_size
+    }
+
+    /// Whether TLS session tickets are issued to clients.
+    pub fn session_tickets(&self) -> bool {
+        self.session_tickets
+    }
+
+    /// How long in-flight connections are given to finish during shutdown.
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    /// The largest request body, in bytes, the service accepts.
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// How long a request may take before it is answered with `504 Gateway Timeout`.
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+
+    /// Whether HTTP/1 connections are kept alive between requests.
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    /// How long HTTP/1 clients are given to send the complete request head.
+    pub fn header_read_timeout(&self) -> Duration {
+        self.header_read_timeout
+    }
+
+    /// Whether the case of HTTP/1 header names is preserved.
+    pub fn preserve_header_case(&self) -> bool {
+        self.preserve_header_case
+    }
+
+    /// The largest HTTP/1 connection read buffer, if limited below hyper's default.
+    pub fn max_buf_size(&self) -> Option<usize> {
+        self.max_buf_size
+    }
+
+    /// The most connections served at once, if limited.
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    /// The options set on accepted TCP sockets.
+    pub fn socket_options(&self) -> SocketOptions {
+        self.socket_options
+    }
+
+    /// What happens to new connections once `max_connections` is reached.
+    pub fn saturation_policy(&self) -> SaturationPolicy {
+        self.saturation_policy
+    }
+
+    /// Whether `HEAD` requests are answered by the `GET` handler with the body left out.
+    pub fn head_as_get(&self) -> bool {
+        self.head_as_get
+    }
+
+    /// The certificate directories selected by SNI hostname.
+    pub fn sni_certs(&self) -> &[(String, PathBuf)] {
+        &self.sni_certs
+    }
+
+    /// Which certificate clients with an unknown SNI hostname are presented.
+    pub fn sni_fallback(&self) -> SniFallback {
+        self.sni_fallback
+    }
+
+    /// The liveness and readiness probes answered ahead of the service routes, if any.
+    pub fn health_checks(&self) -> Option<&HealthChecks> {
+        self.health_checks.as_ref()
+    }
+
+    /// The admin routes switching maintenance mode ahead of the service routes, if any.
+    pub fn maintenance_routes(&self) -> Option<&MaintenanceRoutes> {
+        self.maintenance_routes.as_ref()
+    }
+
+    /// The directories of static files served ahead of the service routes.
+    pub fn static_files(&self) -> &[StaticFiles] {
+        &self.static_files
+    }
+
+    /// The path metrics are served on, if metrics are enabled.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_path(&self) -> Option<&str> {
+        self.metrics_path.as_deref()
+    }
+}
+
+// Kept out of the derived `Debug` output of the config.
+#[derive(Clone)]
+pub(super) struct Passphrase(pub(super) String);
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// Builder for [`RuntimeConfig`].
+pub struct RuntimeConfigBuilder {
+    config: RuntimeConfig,
+}
+
+impl RuntimeConfigBuilder {
+    /// Set the name of the service.
+    pub fn with_service_name(mut self, service_name: impl Into<String>) -> Self {
+        self.config.service_name = service_name.into();
+        self
+    }
+
+    /// Serve connections over TLS using the certificates found under
+    /// `{CERTIFICATE_ROOT}/{service_name}`.
+    pub fn with_tls(mut self) -> Self {
+        self.config.use_tls = true;
+        self
+    }
+
+    /// Present the certificate chain at `cert_path`, signed with the private key at `key_path`,
+    /// instead of the certificate found under `{CERTIFICATE_ROOT}/{service_name}`.
+    ///
+    /// Useful with flat layouts such as Kubernetes TLS secrets mounted as `tls.crt` and
+    /// `tls.key`.
+    ///
+    /// The chain must list the leaf certificate first, each certificate followed by the
+    /// intermediate that issued it, and may leave out the root.  Misordered chains are rejected at
+    /// startup and a leaf without intermediates is logged as a warning.
+    pub fn with_cert_paths(
+        mut self,
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.config.cert_paths = Some((cert_path.into(), key_path.into()));
+        self
+    }
+
+    /// Present the certificate chain and private key stored in the PKCS#12 bundle at `path`,
+    /// instead of the certificate found under `{CERTIFICATE_ROOT}/{service_name}`.
+    ///
+    /// A `cert_path` ending in `.p12` or `.pfx` passed to
+    /// [`with_cert_paths`](Self::with_cert_paths) is loaded as a bundle too, use this for bundles
+    /// named otherwise.  The bundle is decrypted with the passphrase set with
+    /// [`with_pkcs12_passphrase`](Self::with_pkcs12_passphrase), or read from
+    /// [`PKCS12_PASSPHRASE_VAR`], or an empty passphrase.
+    pub fn with_pkcs12(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.pkcs12_bundle = Some(path.into());
+        self
+    }
+
+    /// Decrypt PKCS#12 bundles with `passphrase`.
+    pub fn with_pkcs12_passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.config.pkcs12_passphrase = Some(Passphrase(passphrase.into()));
+        self
+    }
+
+    /// Present the certificate in `cert_dir` to clients requesting `hostname` through SNI.
+    ///
+    /// `cert_dir` follows the same layout as the service certificate directory, an `end.cert`
+    /// chain and an `end.key` private key.  Hostnames are matched case-insensitively.
+    pub fn with_sni_cert(
+        mut self,
+        hostname: impl Into<String>,
+        cert_dir: impl Into<PathBuf>,
+    ) -> Self {
+        self.config
+            .sni_certs
+            .push((hostname.into(), cert_dir.into()));
+        self
+    }
+
+    /// Set which certificate clients with an unknown SNI hostname are presented.
+    pub fn with_sni_fallback(mut self, sni_fallback: SniFallback) -> Self {
+        self.config.sni_fallback = sni_fallback;
+        self
+    }
+
+    /// Require clients to present a certificate signed by one of the CAs in the bundle at `path`.
+    ///
+    /// Only has an effect when TLS is enabled.
+    pub fn with_client_ca(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.client_ca_path = Some(path.into());
+        self
+    }
+
+    /// Only negotiate TLS versions from `min` up to and including `max` with clients, such as
+    /// TLS 1.3 only with `(TlsVersion::Tls13, TlsVersion::Tls13)`.
+    ///
+    /// Defaults to TLS 1.2 up to TLS 1.3.  Serving fails to start if `min` is above `max`.
+    pub fn with_tls_versions(mut self, min: TlsVersion, max: TlsVersion) -> Self {
+        self.config.tls_versions = (min, max);
+        self
+    }
+
+    /// Cache up to `size` TLS sessions in memory so reconnecting clients can resume them with an
+    /// abbreviated handshake, or keep no sessions at all with a `size` of 0.
+    ///
+    /// rustls caches 256 sessions by default.  The cache is per process, so clients spread over
+    /// several replicas only resume when they reconnect to the same one.
+    pub fn with_session_cache(mut self, size: usize) -> Self {
+        self.config.session_cache_size = Some(size);
+        self
+    }
+
+    /// Issue session tickets, letting clients resume sessions without the service keeping any
+    /// state for them.
+    ///
+    /// Off by default.  Tickets are encrypted with a key generated at startup and rotated every
+    /// 6 hours, a ticket stays valid for up to 12 hours.  Anyone who obtains the ticket key within
+    /// that window can decrypt the sessions resumed with its tickets, and TLS 1.2 resumption
+    /// gives up forward secrecy for the ticket lifetime; TLS 1.3 resumption still runs an
+    /// ephemeral key exchange.  The key is never shared between replicas, so tickets only
+    /// resume against the process that issued them.
+    pub fn with_session_tickets(mut self) -> Self {
+        self.config.session_tickets = true;
+        self
+    }
+
+    /// Set how long in-flight connections are given to finish during shutdown.
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.config.grace_period = grace_period;
+        self
+    }
+
+    /// Set the largest request body, in bytes, the service accepts.
+    ///
+    /// Requests with a larger body are answered with `413 Payload Too Large`.
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.config.max_body_size = max_body_size;
+        self
+    }
+
+    /// Set how long a request may take, covering both reading the body and routing it.
+    ///
+    /// Requests that take longer are answered with `504 Gateway Timeout` and the connection is
+    /// closed.
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.config.request_timeout = request_timeout;
+        self
+    }
+
+    /// Set whether HTTP/1 connections are kept alive between requests, `true` by default.
+    ///
+    /// Passed on to hyper's `http1::Builder::keep_alive`.
+    pub fn with_keep_alive(mut self, keep_alive: bool) -> Self {
+        self.config.keep_alive = keep_alive;
+        self
+    }
+
+    /// Set how long HTTP/1 clients are given to send the complete request head before the
+    /// connection is closed.
+    ///
+    /// Passed on to hyper's `http1::Builder::header_read_timeout`.  Also bounds the TLS
+    /// handshake, so clients that never finish it don't keep holding a connection.
+    pub fn with_header_read_timeout(mut self, header_read_timeout: Duration) -> Self {
+        self.config.header_read_timeout = header_read_timeout;
+        self
+    }
+
+    /// Keep the case of HTTP/1 header names as sent by the client and handler, rather than
+    /// lowercasing them.
+    ///
+    /// Passed on to hyper's `http1::Builder::preserve_header_case`.
+    pub fn with_preserve_header_case(mut self) -> Self {
+        self.config.preserve_header_case = true;
+        self
+    }
+
+    /// Limit the HTTP/1 connection read buffer, and so the size of request heads, to
+    /// `max_buf_size` bytes.
+    ///
+    /// Passed on to hyper's `http1::Builder::max_buf_size`.
+    pub fn with_max_buf_size(mut self, max_buf_size: usize) -> Self {
+        self.config.max_buf_size = Some(max_buf_size);
+        self
+    }
+
+    /// Serve at most `max_connections` connections at once.
+    ///
+    /// Connections are unlimited by default, and a `max_connections` of 0 leaves them unlimited
+    /// rather than serving nothing.
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.config.max_connections = (max_connections > 0).then_some(max_connections);
+        self
+    }
+
+    /// Set `socket_options` on accepted TCP sockets.
+    ///
+    /// Defaults to [`SocketOptions::default`], which sets `TCP_NODELAY`.  Sockets accepted from a
+    /// Unix domain socket listener are left alone.
+    pub fn with_socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.config.socket_options = socket_options;
+        self
+    }
+
+    /// Set what happens to new connections once the connection limit is reached.
+    pub fn with_saturation_policy(mut self, saturation_policy: SaturationPolicy) -> Self {
+        self.config.saturation_policy = saturation_policy;
+        self
+    }
+
+    /// Answer `HEAD` requests by running the `GET` handler of the route and leaving out the body.
+    ///
+    /// The headers, including the `Content-Length` of the body the `GET` handler produced, are
+    /// kept. This takes the place of any `HEAD` handler the controllers implement themselves.
+    pub fn with_head_as_get(mut self) -> Self {
+        self.config.head_as_get = true;
+        self
+    }
+
+    /// Reload the TLS certificate and key from disk whenever the process receives `SIGHUP`.
+    ///
+    /// See [`CertReloader`](super::CertReloader) for reloading from other triggers.
+    #[cfg(unix)]
+    pub fn with_reload_on_sighup(mut self) -> Self {
+        self.config.reload_on_sighup = true;
+        self
+    }
+
+    /// Answer liveness and readiness probes ahead of the service routes.
+    pub fn with_health_checks(mut self, health_checks: HealthChecks) -> Self {
+        self.config.health_checks = Some(health_checks);
+        self
+    }
+
+    /// Answer the admin routes switching maintenance mode ahead of the service routes.
+    pub fn with_maintenance_routes(mut self, maintenance_routes: MaintenanceRoutes) -> Self {
+        self.config.maintenance_routes = Some(maintenance_routes);
+        self
+    }
+
+    /// Serve the files of a directory ahead of the service routes, in addition to any directories
+    /// added before.
+    ///
+    /// Requests under the mount of the files are only ever answered from the directory, so mount
+    /// them under a prefix of their own such as `/static`.
+    pub fn with_static_files(mut self, static_files: StaticFiles) -> Self {
+        self.config.static_files.push(static_files);
+        self
+    }
+
+    /// Record request and connection metrics and serve them on
+    /// [`DEFAULT_METRICS_PATH`](super::DEFAULT_METRICS_PATH).
+    ///
+    /// Recorded are the number of requests and their duration by method and status class, as
+    /// well as the number of requests in flight and open connections.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(self) -> Self {
+        self.with_metrics_path(super::DEFAULT_METRICS_PATH)
+    }
+
+    /// Record metrics like [`RuntimeConfigBuilder::with_metrics`], serving them on `path`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics_path(mut self, path: impl Into<String>) -> Self {
+        self.config.metrics_path = Some(path.into());
+        self
+    }
+
+    /// Remove a stale socket file left behind at the Unix domain socket path before binding.
+    ///
+    /// The file is only removed when nothing is listening on it anymore.
+    pub fn with_unlink_stale_socket(mut self) -> Self {
+        self.config.unlink_stale_socket = true;
+        self
+    }
+
+    /// Finish building the configuration.
+    pub fn build(self) -> RuntimeConfig {
+        self.config
+    }
+}
@@ -0,0 +1,141 @@
+use std::io::{self, Read};
+
+use bytes::Bytes;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use http::header::{HeaderMap, CONTENT_ENCODING, CONTENT_LENGTH};
+
+// Why a request body couldn't be decompressed.
+#[derive(Debug)]
+pub(super) enum DecompressError {
+    // The decompressed body is larger than the max body size.
+    TooLarge,
+    // The body isn't valid for its encoding.
+    Invalid(io::Error),
+    // The body uses an encoding other than gzip and deflate.
+    Unsupported(String),
+}
+
+// Undo the `Content-Encoding` of a request body, last applied encoding first, and drop the
+// header.  Decompression stops as soon as the body grows beyond `max_body_size`, so a small
+// compressed body can't expand into an arbitrarily large one.
+pub(super) fn decompress(
+    headers: &mut HeaderMap,
+    body: Bytes,
+    max_body_size: usize,
+) -> Result<Bytes, DecompressError> {
+    let encodings = headers
+        .get_all(CONTENT_ENCODING)
+        .iter()
+        .map(|value| value.to_str().unwrap_or_default().to_string())
+        .collect::<Vec<_>>();
+
+    let encodings = encodings
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(|encoding| encoding.trim().to_ascii_lowercase())
+        .filter(|encoding| !encoding.is_empty() && encoding != "identity")
+        .collect::<Vec<_>>();
+
+    if encodings.is_empty() {
+        return Ok(body);
+    }
+
+    let mut body = body;
+    for encoding in encodings.iter().rev() {
+        body = match encoding.as_str() {
+            "gzip" | "x-gzip" => read_limited(GzDecoder::new(&body[..]), max_body_size)?,
+            // `deflate` is meant to be zlib wrapped, but raw deflate is common enough to accept.
+            "deflate" => match read_limited(ZlibDecoder::new(&body[..]), max_body_size) {
+                Err(DecompressError::Invalid(_)) => {
+                    read_limited(DeflateDecoder::new(&body[..]), max_body_size)?
+                }
+                decompressed => decompressed?,
+            },
+            _ => return Err(DecompressError::Unsupported(encoding.clone())),
+        };
+    }
+
+    headers.remove(CONTENT_ENCODING);
+    headers.insert(CONTENT_LENGTH, body.len().into());
+
+    Ok(body)
+}
+
+fn read_limited(decoder: impl Read, max_body_size: usize) -> Result<Bytes, DecompressError> {
+    let mut decompressed = Vec::new();
+    decoder
+        .take(max_body_size as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(DecompressError::Invalid)?;
+
+    if decompressed.len() > max_body_size {
+        return Err(DecompressError::TooLarge);
+    }
+
+    Ok(Bytes::from(decompressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    use super::*;
+
+    const BODY: &[u8] = br#"{"name":"alice","email":"alice@example.com"}"#;
+
+    fn gzip(body: &[u8]) -> Bytes {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        Bytes::from(encoder.finish().unwrap())
+    }
+
+    fn headers(content_encoding: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, content_encoding.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn decompresses_gzip() {
+        let mut headers = headers("gzip");
+
+        let body = decompress(&mut headers, gzip(BODY), 1024).unwrap();
+
+        assert_eq!(body, BODY);
+        assert!(headers.get(CONTENT_ENCODING).is_none());
+        assert_eq!(headers[CONTENT_LENGTH], BODY.len().to_string().as_str());
+    }
+
+    #[test]
+    fn decompresses_zlib_and_raw_deflate() {
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(BODY).unwrap();
+        let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+        raw.write_all(BODY).unwrap();
+
+        for compressed in [zlib.finish().unwrap(), raw.finish().unwrap()] {
+            let body = decompress(&mut headers("deflate"), Bytes::from(compressed), 1024).unwrap();
+            assert_eq!(body, BODY);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_encodings() {
+        let err = decompress(&mut headers("br"), Bytes::from_static(BODY), 1024).unwrap_err();
+
+        assert!(matches!(err, DecompressError::Unsupported(encoding) if encoding == "br"));
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_max_size() {
+        let max_body_size = BODY.len();
+        let body = decompress(&mut headers("gzip"), gzip(BODY), max_body_size).unwrap();
+        assert_eq!(body, BODY);
+
+        let err = decompress(&mut headers("gzip"), gzip(BODY), max_body_size - 1).unwrap_err();
+        assert!(matches!(err, DecompressError::TooLarge));
+    }
+}
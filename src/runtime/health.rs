@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use http::{Method, StatusCode};
+
+use crate::{json_response, platform_error, ServiceUnavailableError};
+
+/// The path the liveness probe is served on unless configured otherwise.
+pub const DEFAULT_LIVENESS_PATH: &str = "/healthz";
+
+/// The path the readiness probe is served on unless configured otherwise.
+pub const DEFAULT_READINESS_PATH: &str = "/readyz";
+
+type ReadyFn = dyn Fn() -> bool + Send + Sync;
+
+/// Liveness and readiness probes answered by the runtime ahead of the service routes, see
+/// [`RuntimeConfigBuilder::with_health_checks`](super::RuntimeConfigBuilder::with_health_checks).
+///
+/// The liveness probe always answers `200 OK` while the service is serving requests, the
+/// readiness probe answers `200 OK` while the readiness check returns true and
+/// `503 Service Unavailable` otherwise.  Both only answer `GET` and `HEAD` requests.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// use rustserve_platform::runtime::HealthChecks;
+///
+/// let ready = Arc::new(AtomicBool::new(false));
+///
+/// let health_checks = HealthChecks::new({
+///     let ready = ready.clone();
+///     move || ready.load(Ordering::Relaxed)
+/// });
+///
+/// // Once caches are warm and upstreams are reachable:
+/// ready.store(true, Ordering::Relaxed);
+/// ```
+#[derive(Clone)]
+pub struct HealthChecks {
+    liveness_path: String,
+    readiness_path: String,
+    ready: Arc<ReadyFn>,
+}
+
+impl HealthChecks {
+    /// Create probes on the default paths with readiness reported by `ready`.
+    pub fn new(ready: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            liveness_path: DEFAULT_LIVENESS_PATH.into(),
+            readiness_path: DEFAULT_READINESS_PATH.into(),
+            ready: Arc::new(ready),
+        }
+    }
+
+    /// Create probes on the default paths that always report the service as ready.
+    pub fn always_ready() -> Self {
+        Self::new(|| true)
+    }
+
+    /// Serve the liveness probe on `path`.
+    pub fn with_liveness_path(mut self, path: impl Into<String>) -> Self {
+        self.liveness_path = path.into();
+        self
+    }
+
+    /// Serve the readiness probe on `path`.
+    pub fn with_readiness_path(mut self, path: impl Into<String>) -> Self {
+        self.readiness_path = path.into();
+        self
+    }
+
+    // The probe response for a request to `path`, or `None` if it isn't a probe.
+    pub(super) fn respond(
+        &self,
+        method: &Method,
+        path: &str,
+    ) -> Option<anyhow::Result<http::Response<Vec<u8>>>> {
+        if method != Method::GET && method != Method::HEAD {
+            return None;
+        }
+
+        if path == self.liveness_path {
+            Some(status_response("ok"))
+        } else if path == self.readiness_path {
+            if (self.ready)() {
+                Some(status_response("ready"))
+            } else {
+                Some(Ok(platform_error(ServiceUnavailableError::new())))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Debug for HealthChecks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HealthChecks")
+            .field("liveness_path", &self.liveness_path)
+            .field("readiness_path", &self.readiness_path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Status {
+    status: &'static str,
+}
+
+fn status_response(status: &'static str) -> anyhow::Result<http::Response<Vec<u8>>> {
+    json_response(StatusCode::OK, Status { status })
+}
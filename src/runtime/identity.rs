@@ -0,0 +1,65 @@
+use std::net::IpAddr;
+
+use tokio_rustls::rustls::Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// The identity of a client that authenticated with a certificate, read from the leaf of its
+/// certificate chain and carried in the request extensions.
+///
+/// Subject alternative names are formatted with the type prefixes OpenSSL uses, one of
+/// `DNS:example.com`, `IP:10.0.0.1`, `URI:spiffe://example.com/service` or
+/// `email:admin@example.com`.  Names of other types are left out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientIdentity {
+    /// The first common name in the certificate subject, if any.
+    pub common_name: Option<String>,
+    /// The subject alternative names of the certificate, in the order they're listed.
+    pub subject_alt_names: Vec<String>,
+}
+
+impl ClientIdentity {
+    /// Read the identity from `leaf`, returning `None` if it can't be parsed.
+    pub fn from_certificate(leaf: &Certificate) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(&leaf.0).ok()?;
+
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(String::from);
+
+        let subject_alt_names = match cert.subject_alternative_name() {
+            Ok(Some(san)) => san
+                .value
+                .general_names
+                .iter()
+                .filter_map(format_general_name)
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Some(Self {
+            common_name,
+            subject_alt_names,
+        })
+    }
+}
+
+fn format_general_name(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DNSName(dns) => Some(format!("DNS:{dns}")),
+        GeneralName::URI(uri) => Some(format!("URI:{uri}")),
+        GeneralName::RFC822Name(email) => Some(format!("email:{email}")),
+        GeneralName::IPAddress(bytes) => {
+            let ip = match bytes.len() {
+                4 => IpAddr::from(<[u8; 4]>::try_from(*bytes).ok()?),
+                16 => IpAddr::from(<[u8; 16]>::try_from(*bytes).ok()?),
+                _ => return None,
+            };
+            Some(format!("IP:{ip}"))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,86 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+
+use crate::mtls::SocketOptions;
+
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+// A bound listener the runtime accepts connections from.
+pub(super) trait Accept {
+    type Io: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    // Accept the next connection along with the address of the peer, if it has one.
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Io, Option<SocketAddr>)>>;
+
+    // Set `socket_options` on an accepted connection, where they apply.
+    fn configure(stream: &Self::Io, socket_options: &SocketOptions) -> io::Result<()>;
+}
+
+impl Accept for TcpListener {
+    type Io = tokio::net::TcpStream;
+
+    async fn accept(&self) -> io::Result<(Self::Io, Option<SocketAddr>)> {
+        let (stream, peer_addr) = TcpListener::accept(self).await?;
+        Ok((stream, Some(peer_addr)))
+    }
+
+    fn configure(stream: &Self::Io, socket_options: &SocketOptions) -> io::Result<()> {
+        socket_options.apply(stream)
+    }
+}
+
+#[cfg(unix)]
+impl Accept for UnixListener {
+    type Io = tokio::net::UnixStream;
+
+    async fn accept(&self) -> io::Result<(Self::Io, Option<SocketAddr>)> {
+        let (stream, _) = UnixListener::accept(self).await?;
+        Ok((stream, None))
+    }
+
+    fn configure(_stream: &Self::Io, _socket_options: &SocketOptions) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Bind a Unix domain socket at `path`.
+//
+// If the path is already taken by a socket nothing is listening on anymore, e.g. left behind by a
+// crashed process, it is removed and binding is retried when `unlink_stale_socket` is set.
+#[cfg(unix)]
+pub(super) fn bind_unix(path: &Path, unlink_stale_socket: bool) -> io::Result<UnixListener> {
+    match UnixListener::bind(path) {
+        Err(err) if err.kind() == io::ErrorKind::AddrInUse && unlink_stale_socket => {
+            match std::os::unix::net::UnixStream::connect(path) {
+                Err(connect_err) if connect_err.kind() == io::ErrorKind::ConnectionRefused => {
+                    tracing::warn!(path = %path.display(), "removing stale socket file");
+                    std::fs::remove_file(path)?;
+                    UnixListener::bind(path)
+                }
+                _ => Err(err),
+            }
+        }
+        res => res,
+    }
+}
+
+// Removes the socket file once the listener is done with it.
+#[cfg(unix)]
+pub(super) struct SocketFile(pub(super) PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.0) {
+            tracing::warn!(path = %self.0.display(), error = %err, "failed to remove socket file");
+        }
+    }
+}
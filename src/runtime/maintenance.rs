@@ -0,0 +1,204 @@
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use http::header::{HeaderValue, ALLOW, AUTHORIZATION};
+use http::{HeaderMap, Method, StatusCode};
+use ipnet::IpNet;
+
+use crate::filters::parse_cidr;
+use crate::{
+    auth_credentials, constant_time_eq, json_response, platform_error, ForbiddenError,
+    MethodNotAllowedError,
+};
+
+/// The path prefix the maintenance routes are served under unless configured otherwise.
+pub const DEFAULT_MAINTENANCE_PATH: &str = "/admin/maintenance";
+
+/// Admin routes switching maintenance mode on and off, answered by the runtime ahead of the
+/// service routes, see
+/// [`with_maintenance_routes`](super::RuntimeConfigBuilder::with_maintenance_routes).
+///
+/// `POST {path}/on` and `POST {path}/off` set the shared flag, typically the one a
+/// [`ReadOnlyFilter`](crate::filters::ReadOnlyFilter) reads, and `GET {path}` reports it.  All
+/// three answer `{"maintenance": true}` or `{"maintenance": false}` with the current state.
+///
+/// Only requests carrying the configured bearer token in their `Authorization` header, or
+/// received from a client address in one of the allowed ranges, are let through, all others are
+/// answered with `403 Forbidden`.  Without a token or an allowed range every request is
+/// forbidden.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::AtomicBool;
+/// use std::sync::Arc;
+///
+/// use rustserve_platform::filters::ReadOnlyFilter;
+/// use rustserve_platform::runtime::maintenance_routes;
+///
+/// let maintenance = Arc::new(AtomicBool::new(false));
+///
+/// let read_only = ReadOnlyFilter::new(maintenance.clone());
+/// let admin = maintenance_routes(maintenance)
+///     .with_token(std::env::var("ADMIN_TOKEN").unwrap_or_default())
+///     .allow("10.0.0.0/8")?;
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct MaintenanceRoutes {
+    path: String,
+    flag: Arc<AtomicBool>,
+    token: Option<String>,
+    allowed: Vec<IpNet>,
+}
+
+/// Create [`MaintenanceRoutes`] on the default path switching `flag`.
+pub fn maintenance_routes(flag: Arc<AtomicBool>) -> MaintenanceRoutes {
+    MaintenanceRoutes::new(flag)
+}
+
+impl MaintenanceRoutes {
+    /// Create maintenance routes on the default path switching `flag`.
+    pub fn new(flag: Arc<AtomicBool>) -> Self {
+        Self {
+            path: DEFAULT_MAINTENANCE_PATH.into(),
+            flag,
+            token: None,
+            allowed: Vec::new(),
+        }
+    }
+
+    /// Serve the maintenance routes under `path`.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Let through requests sending `Authorization: Bearer {token}`.
+    ///
+    /// An empty token is ignored.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into()).filter(|token| !token.is_empty());
+        self
+    }
+
+    /// Let through requests from `cidr`, an IPv4 or IPv6 range such as `10.0.0.0/8` or a single
+    /// address.
+    pub fn allow(mut self, cidr: &str) -> anyhow::Result<Self> {
+        self.allowed.push(parse_cidr(cidr)?);
+        Ok(self)
+    }
+
+    /// Whether maintenance mode is currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    fn is_authorized(&self, headers: &HeaderMap, peer_ip: Option<IpAddr>) -> bool {
+        let has_token = self.token.as_ref().is_some_and(|token| {
+            auth_credentials(headers.get(AUTHORIZATION), "Bearer")
+                .is_some_and(|sent| constant_time_eq(sent.as_bytes(), token.as_bytes()))
+        });
+
+        let from_allowed = peer_ip.is_some_and(|ip| {
+            let ip = ip.to_canonical();
+            self.allowed.iter().any(|net| net.contains(&ip))
+        });
+
+        has_token || from_allowed
+    }
+
+    // The response for a request to `path`, or `None` if it isn't a maintenance route.
+    pub(super) fn respond(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        peer_ip: Option<IpAddr>,
+    ) -> Option<anyhow::Result<http::Response<Vec<u8>>>> {
+        let enable = match path.strip_prefix(self.path.as_str())? {
+            "" => None,
+            "/on" => Some(true),
+            "/off" => Some(false),
+            _ => return None,
+        };
+
+        if !self.is_authorized(headers, peer_ip) {
+            return Some(Ok(platform_error(ForbiddenError::new())));
+        }
+
+        match (method, enable) {
+            (&Method::GET, None) => {}
+            (&Method::POST, Some(enable)) => {
+                let was_enabled = self.flag.swap(enable, Ordering::Relaxed);
+                if was_enabled != enable {
+                    tracing::warn!(maintenance = enable, "switched maintenance mode");
+                }
+            }
+            (method, enable) => {
+                let allow = if enable.is_some() { "POST" } else { "GET" };
+                let mut res = platform_error(MethodNotAllowedError::new(method.as_str()));
+                res.headers_mut()
+                    .insert(ALLOW, HeaderValue::from_static(allow));
+                return Some(Ok(res));
+            }
+        }
+
+        Some(json_response(
+            StatusCode::OK,
+            MaintenanceState {
+                maintenance: self.is_enabled(),
+            },
+        ))
+    }
+}
+
+impl std::fmt::Debug for MaintenanceRoutes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MaintenanceRoutes")
+            .field("path", &self.path)
+            .field("enabled", &self.is_enabled())
+            .field("allowed", &self.allowed)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(serde::Serialize)]
+struct MaintenanceState {
+    maintenance: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes() -> MaintenanceRoutes {
+        maintenance_routes(Arc::new(AtomicBool::new(false)))
+            .with_token("secret")
+            .allow("10.0.0.0/8")
+            .unwrap()
+    }
+
+    fn bearer(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(authorization).unwrap());
+        headers
+    }
+
+    #[test]
+    fn authorizes_the_token_with_any_scheme_case() {
+        assert!(routes().is_authorized(&bearer("Bearer secret"), None));
+        assert!(routes().is_authorized(&bearer("bearer secret"), None));
+        assert!(!routes().is_authorized(&bearer("Bearer wrong"), None));
+        assert!(!routes().is_authorized(&bearer("Basic secret"), None));
+    }
+
+    #[test]
+    fn authorizes_allowed_addresses() {
+        let headers = HeaderMap::new();
+        assert!(routes().is_authorized(&headers, Some("10.1.2.3".parse().unwrap())));
+        assert!(!routes().is_authorized(&headers, Some("192.168.0.1".parse().unwrap())));
+        assert!(!routes().is_authorized(&headers, None));
+    }
+}
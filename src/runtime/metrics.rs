@@ -0,0 +1,154 @@
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use http::header::CONTENT_TYPE;
+use http::{Method, StatusCode};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+/// The path metrics are served on unless configured otherwise.
+pub const DEFAULT_METRICS_PATH: &str = "/metrics";
+
+pub(super) struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    requests_in_flight: IntGauge,
+    connections: IntGauge,
+}
+
+/// The registry the runtime records its metrics in, and which is served on the metrics path.
+///
+/// Register the metrics of the service itself here to serve them alongside the runtime metrics.
+pub fn metrics_registry() -> &'static Registry {
+    &metrics().registry
+}
+
+pub(super) fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("runtime metrics are valid"))
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Requests served."),
+            &["method", "status"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to serve requests.",
+            ),
+            &["method", "status"],
+        )?;
+        let requests_in_flight = IntGauge::new(
+            "http_requests_in_flight",
+            "Requests currently being served.",
+        )?;
+        let connections = IntGauge::new("http_connections", "Open connections.")?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(requests_in_flight.clone()))?;
+        registry.register(Box::new(connections.clone()))?;
+
+        Ok(Self {
+            registry,
+            requests,
+            request_duration,
+            requests_in_flight,
+            connections,
+        })
+    }
+
+    // Count the request as in flight until the returned guard is finished or dropped.
+    pub(super) fn request_started(&'static self, method: &Method) -> InFlightRequest {
+        self.requests_in_flight.inc();
+
+        InFlightRequest {
+            metrics: self,
+            method: method_label(method),
+            started_at: Instant::now(),
+        }
+    }
+
+    // Count the connection as open until the returned guard is dropped.
+    pub(super) fn connection_opened(&'static self) -> OpenConnection {
+        self.connections.inc();
+
+        OpenConnection { metrics: self }
+    }
+
+    pub(super) fn response(&self) -> anyhow::Result<http::Response<Vec<u8>>> {
+        let encoder = TextEncoder::new();
+        let mut body = Vec::new();
+        encoder.encode(&self.registry.gather(), &mut body)?;
+
+        Ok(http::Response::builder()
+            .header(CONTENT_TYPE, encoder.format_type())
+            .body(body)?)
+    }
+}
+
+pub(super) struct InFlightRequest {
+    metrics: &'static Metrics,
+    method: &'static str,
+    started_at: Instant,
+}
+
+impl InFlightRequest {
+    pub(super) fn finish(self, status: StatusCode) {
+        let labels = [self.method, status_class(status)];
+
+        self.metrics.requests.with_label_values(&labels).inc();
+        self.metrics
+            .request_duration
+            .with_label_values(&labels)
+            .observe(self.started_at.elapsed().as_secs_f64());
+    }
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        self.metrics.requests_in_flight.dec();
+    }
+}
+
+pub(super) struct OpenConnection {
+    metrics: &'static Metrics,
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.metrics.connections.dec();
+    }
+}
+
+// Only the standard methods get a label of their own, so clients can't blow up the number of
+// series with made up methods.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::PATCH => "PATCH",
+        Method::DELETE => "DELETE",
+        Method::OPTIONS => "OPTIONS",
+        _ => "other",
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
@@ -0,0 +1,328 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use http::header::{HeaderValue, ALLOW, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Method, StatusCode};
+use percent_encoding::percent_decode_str;
+
+use crate::{platform_error, InternalServerError, MethodNotAllowedError, NotFoundError};
+
+/// The largest file [`StaticFiles`] reads into memory unless configured otherwise, 16 MiB.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Files under a directory served by the runtime ahead of the service routes, see
+/// [`RuntimeConfigBuilder::with_static_files`](super::RuntimeConfigBuilder::with_static_files).
+///
+/// A request to `{mount}/css/site.css` is answered with the file `{dir}/css/site.css`, and a
+/// request for a directory with the `index.html` inside it.  The `Content-Type` is picked by the
+/// file extension, falling back to `application/octet-stream`.  Only `GET` and `HEAD` are
+/// answered, other methods get `405 Method Not Allowed`.  Paths with `..` segments and files
+/// that resolve outside of the directory, for example through a symlink, are answered with
+/// `404 Not Found` like missing files.  Files larger than the configured maximum are never read
+/// into memory, and they and files that can't be read are answered with
+/// `500 Internal Server Error`, logging the cause.
+///
+/// # Examples
+///
+/// ```
+/// use rustserve_platform::runtime::static_routes;
+///
+/// let assets = static_routes("/static", "./assets".into())?.with_max_file_size(1024 * 1024);
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticFiles {
+    mount: String,
+    dir: PathBuf,
+    max_file_size: u64,
+}
+
+/// Create [`StaticFiles`] serving the files under `dir` at `mount`.
+pub fn static_routes(mount: &str, dir: PathBuf) -> anyhow::Result<StaticFiles> {
+    StaticFiles::new(mount, dir)
+}
+
+impl StaticFiles {
+    /// Serve the files under `dir` at `mount`.
+    ///
+    /// Fails if `mount` is empty or `/`, since the files would shadow every service route.
+    pub fn new(mount: &str, dir: PathBuf) -> anyhow::Result<Self> {
+        let mount = mount.trim_end_matches('/');
+        if mount.is_empty() {
+            anyhow::bail!("static files can't be mounted at the root");
+        }
+        if !mount.starts_with('/') {
+            anyhow::bail!("static file mount {mount} must start with a /");
+        }
+
+        Ok(Self {
+            mount: mount.to_string(),
+            dir,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        })
+    }
+
+    /// Answer requests for files larger than `max_file_size` bytes with
+    /// `500 Internal Server Error` instead of reading them into memory.
+    ///
+    /// Defaults to [`DEFAULT_MAX_FILE_SIZE`].
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// The path prefix the files are served under.
+    pub fn mount(&self) -> &str {
+        &self.mount
+    }
+
+    /// The directory the files are read from.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // The file under the directory `path` refers to, or `None` if it can't refer to one.
+    fn file_path(&self, path: &str) -> Option<PathBuf> {
+        let relative = path.strip_prefix(self.mount.as_str())?;
+        if !relative.is_empty() && !relative.starts_with('/') {
+            return None;
+        }
+
+        let relative = percent_decode_str(relative).decode_utf8().ok()?;
+
+        let mut file_path = self.dir.clone();
+        for segment in relative.split('/').filter(|segment| !segment.is_empty()) {
+            if segment == "." || segment == ".." || segment.contains(['\\', '\0']) {
+                return None;
+            }
+            file_path.push(segment);
+        }
+
+        Some(file_path)
+    }
+
+    // The response for a request to `path`, or `None` if it isn't under the mount.
+    pub(super) async fn respond(
+        &self,
+        method: &Method,
+        path: &str,
+    ) -> Option<anyhow::Result<http::Response<Vec<u8>>>> {
+        if path != self.mount && !path.starts_with(&format!("{}/", self.mount)) {
+            return None;
+        }
+
+        if method != Method::GET && method != Method::HEAD {
+            let mut res = platform_error(MethodNotAllowedError::new(method.as_str()));
+            res.headers_mut()
+                .insert(ALLOW, HeaderValue::from_static("GET, HEAD"));
+            return Some(Ok(res));
+        }
+
+        let not_found = || Ok(platform_error(NotFoundError::new(path)));
+
+        let Some(file_path) = self.file_path(path) else {
+            return Some(not_found());
+        };
+
+        match self.read(file_path).await {
+            Ok(Some((file_path, body))) => Some(file_response(method, &file_path, body)),
+            Ok(None) => Some(not_found()),
+            Err(err) => {
+                tracing::error!(error = %err, path, "failed to serve static file");
+
+                Some(Ok(platform_error(InternalServerError::new(format!(
+                    "failed to serve static file {path}: {err}"
+                )))))
+            }
+        }
+    }
+
+    // Read the file at `file_path`, or `index.html` if it is a directory, unless it is missing
+    // or outside of the directory.
+    async fn read(&self, file_path: PathBuf) -> io::Result<Option<(PathBuf, Vec<u8>)>> {
+        let dir = tokio::fs::canonicalize(&self.dir).await?;
+
+        let mut file_path = match tokio::fs::canonicalize(&file_path).await {
+            Ok(file_path) => file_path,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if !file_path.starts_with(&dir) {
+            return Ok(None);
+        }
+
+        let mut metadata = tokio::fs::metadata(&file_path).await?;
+        if metadata.is_dir() {
+            file_path.push("index.html");
+            metadata = match tokio::fs::metadata(&file_path).await {
+                Ok(metadata) => metadata,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err),
+            };
+        }
+
+        if metadata.len() > self.max_file_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} is larger than {} bytes",
+                    file_path.display(),
+                    self.max_file_size
+                ),
+            ));
+        }
+
+        match tokio::fs::read(&file_path).await {
+            Ok(body) => Ok(Some((file_path, body))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn file_response(
+    method: &Method,
+    file_path: &Path,
+    body: Vec<u8>,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    let content_length = body.len();
+    let body = if method == Method::HEAD {
+        Vec::new()
+    } else {
+        body
+    };
+
+    Ok(http::Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, content_type(file_path))
+        .header(CONTENT_LENGTH, content_length)
+        .body(body)?)
+}
+
+fn content_type(file_path: &Path) -> &'static str {
+    let extension = file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{capture_logs, TempDir};
+
+    // A directory holding `secret.txt` and `public/css/site.css`, with static files serving
+    // `public` at `/static`.
+    fn assets() -> (TempDir, StaticFiles) {
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.path().join("public/css")).unwrap();
+        std::fs::write(dir.path().join("public/css/site.css"), "body {}").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+        let static_files = static_routes("/static", dir.path().join("public")).unwrap();
+        (dir, static_files)
+    }
+
+    async fn get(static_files: &StaticFiles, path: &str) -> http::Response<Vec<u8>> {
+        static_files
+            .respond(&Method::GET, path)
+            .await
+            .expect("the path is under the mount")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_files() {
+        let (_dir, static_files) = assets();
+
+        let res = get(&static_files, "/static/css/site.css").await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/css; charset=utf-8");
+        assert_eq!(res.headers()[CONTENT_LENGTH], "7");
+        assert_eq!(res.body(), b"body {}");
+    }
+
+    #[tokio::test]
+    async fn answers_missing_files_with_not_found() {
+        let (_dir, static_files) = assets();
+
+        let res = get(&static_files, "/static/css/missing.css").await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        assert!(static_files
+            .respond(&Method::GET, "/staticfiles")
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn refuses_to_traverse_out_of_the_directory() {
+        let (_dir, static_files) = assets();
+
+        for path in [
+            "/static/../secret.txt",
+            "/static/css/%2e%2e/%2e%2e/secret.txt",
+        ] {
+            let res = get(&static_files, path).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{path}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn refuses_symlinks_out_of_the_directory() {
+        let (dir, static_files) = assets();
+        std::os::unix::fs::symlink(
+            dir.path().join("secret.txt"),
+            dir.path().join("public/secret.txt"),
+        )
+        .unwrap();
+
+        let res = get(&static_files, "/static/secret.txt").await;
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn fails_files_over_the_max_size() {
+        let (_dir, static_files) = assets();
+        let static_files = static_files.with_max_file_size(6);
+
+        let logs = capture_logs();
+        let res = get(&static_files, "/static/css/site.css").await;
+
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(logs.contents().contains("larger than 6 bytes"));
+        assert!(!String::from_utf8_lossy(res.body()).contains("larger than"));
+    }
+
+    #[test]
+    fn rejects_root_mounts() {
+        assert!(static_routes("", "./assets".into()).is_err());
+        assert!(static_routes("/", "./assets".into()).is_err());
+        assert!(static_routes("static", "./assets".into()).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use tokio_rustls::rustls::server::{
+    AllowAnyAuthenticatedClient, ClientCertVerifier, ClientHello, NoServerSessionStorage,
+    ResolvesServerCert, ServerSessionMemoryCache,
+};
+use tokio_rustls::rustls::sign::{self, CertifiedKey};
+use tokio_rustls::rustls::{self, Certificate};
+use tokio_rustls::TlsAcceptor;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+
+use super::{RuntimeConfig, SniFallback, PKCS12_PASSPHRASE_VAR};
+
+use crate::mtls::protocol_versions;
+use crate::pem::{load_certs, load_keys, load_pkcs12};
+
+pub(super) const H2_ALPN: &[u8] = b"h2";
+
+// The key type directories produced by `build-a-pki.sh`, in order of preference.
+const KEY_TYPE_DIRS: [&str; 3] = ["rsa", "ecdsa", "eddsa"];
+
+fn cert_dir(cert_root_path: &str, name: &str) -> PathBuf {
+    KEY_TYPE_DIRS
+        .iter()
+        .map(|dir| Path::new(cert_root_path).join(name).join(dir))
+        .find(|path| path.join("end.cert").exists())
+        .unwrap_or_else(|| Path::new(cert_root_path).join(name).join(KEY_TYPE_DIRS[0]))
+}
+
+pub(super) fn tls_acceptor(config: &RuntimeConfig) -> anyhow::Result<(TlsAcceptor, CertReloader)> {
+    let reloader = CertReloader::new(cert_sources(config))?;
+
+    let builder = rustls::ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&protocol_versions(config.tls_versions)?)?;
+    let builder = match &config.client_ca_path {
+        Some(client_ca_path) => {
+            builder.with_client_cert_verifier(client_cert_verifier(client_ca_path)?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder.with_cert_resolver(reloader.resolver.clone());
+
+    server_config.alpn_protocols = vec![H2_ALPN.to_vec(), b"http/1.1".to_vec()];
+
+    match config.session_cache_size {
+        Some(0) => server_config.session_storage = Arc::new(NoServerSessionStorage {}),
+        Some(size) => server_config.session_storage = ServerSessionMemoryCache::new(size),
+        None => {}
+    }
+    if config.session_tickets {
+        server_config.ticketer = rustls::Ticketer::new()?;
+    }
+
+    Ok((TlsAcceptor::from(Arc::new(server_config)), reloader))
+}
+
+fn cert_sources(config: &RuntimeConfig) -> CertSources {
+    let default = match config.sni_fallback {
+        SniFallback::Default => match (&config.pkcs12_bundle, &config.cert_paths) {
+            (Some(bundle), _) => Some(CertPaths::pkcs12(bundle, config)),
+            (None, Some((cert, _))) if is_pkcs12(cert) => Some(CertPaths::pkcs12(cert, config)),
+            (None, Some((cert, key))) => Some(CertPaths::Pem {
+                cert: cert.clone(),
+                key: key.clone(),
+            }),
+            (None, None) => {
+                let cert_root_path = std::env::var("CERTIFICATE_ROOT").unwrap_or(".".into());
+                Some(CertPaths::in_dir(&cert_dir(
+                    &cert_root_path,
+                    &config.service_name,
+                )))
+            }
+        },
+        SniFallback::Reject => None,
+    };
+
+    let by_host = config
+        .sni_certs
+        .iter()
+        .map(|(hostname, dir)| (hostname.to_ascii_lowercase(), CertPaths::in_dir(dir)))
+        .collect();
+
+    CertSources { default, by_host }
+}
+
+fn is_pkcs12(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("p12") || ext.eq_ignore_ascii_case("pfx"))
+}
+
+#[derive(Clone)]
+enum CertPaths {
+    Pem { cert: PathBuf, key: PathBuf },
+    Pkcs12 { bundle: PathBuf, passphrase: String },
+}
+
+impl CertPaths {
+    fn in_dir(dir: &Path) -> Self {
+        Self::Pem {
+            cert: dir.join("end.cert"),
+            key: dir.join("end.key"),
+        }
+    }
+
+    fn pkcs12(bundle: &Path, config: &RuntimeConfig) -> Self {
+        let passphrase = match &config.pkcs12_passphrase {
+            Some(passphrase) => passphrase.0.clone(),
+            None => std::env::var(PKCS12_PASSPHRASE_VAR).unwrap_or_default(),
+        };
+
+        Self::Pkcs12 {
+            bundle: bundle.to_path_buf(),
+            passphrase,
+        }
+    }
+
+    fn load(&self) -> anyhow::Result<Arc<CertifiedKey>> {
+        let (certs, key, key_source) = match self {
+            Self::Pem { cert, key } => {
+                let mut keys = load_keys(key)?;
+                (load_certs(cert)?, keys.remove(0), key)
+            }
+            Self::Pkcs12 { bundle, passphrase } => {
+                let (certs, key) = load_pkcs12(bundle, passphrase)?;
+                (certs, key, bundle)
+            }
+        };
+
+        if certs.is_empty() {
+            anyhow::bail!("no certificates found in {}", self.cert_source().display());
+        }
+        check_chain(&certs, self.cert_source())?;
+
+        let key = sign::any_supported_type(&key).map_err(|_| {
+            anyhow::anyhow!("unsupported private key type in {}", key_source.display())
+        })?;
+
+        Ok(Arc::new(CertifiedKey::new(certs, key)))
+    }
+
+    fn cert_source(&self) -> &Path {
+        match self {
+            Self::Pem { cert, .. } => cert,
+            Self::Pkcs12 { bundle, .. } => bundle,
+        }
+    }
+}
+
+// Make sure `certs` are ordered leaf first, each certificate followed by the one that issued it,
+// so a misordered chain fails at startup rather than every handshake.  The root may be left out,
+// a leaf without intermediates is only warned about since it may be issued by the root directly.
+fn check_chain(certs: &[Certificate], source: &Path) -> anyhow::Result<()> {
+    let parsed = certs
+        .iter()
+        .enumerate()
+        .map(|(i, cert)| {
+            X509Certificate::from_der(&cert.0)
+                .map(|(_, cert)| cert)
+                .map_err(|err| {
+                    anyhow::anyhow!(
+                        "invalid certificate #{} in {}: {err}",
+                        i + 1,
+                        source.display()
+                    )
+                })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if is_ca(&parsed[0]) && parsed.iter().any(|cert| !is_ca(cert)) {
+        anyhow::bail!(
+            "the first certificate in {} is the CA certificate {}, the leaf certificate must come \
+             first followed by its intermediates",
+            source.display(),
+            parsed[0].subject(),
+        );
+    }
+
+    for (i, pair) in parsed.windows(2).enumerate() {
+        if pair[0].issuer().as_raw() != pair[1].subject().as_raw() {
+            anyhow::bail!(
+                "certificate #{} in {} ({}) is followed by {} instead of its issuer {}, \
+                 certificates must be ordered leaf first, each followed by the one that issued it",
+                i + 1,
+                source.display(),
+                pair[0].subject(),
+                pair[1].subject(),
+                pair[0].issuer(),
+            );
+        }
+    }
+
+    let leaf = &parsed[0];
+    if parsed.len() == 1 && leaf.issuer().as_raw() != leaf.subject().as_raw() {
+        tracing::warn!(
+            path = %source.display(),
+            issuer = %leaf.issuer(),
+            "certificate file holds no intermediate certificates, clients only trusting the root \
+             will fail to verify it; append the intermediates after the leaf",
+        );
+    }
+
+    Ok(())
+}
+
+fn is_ca(cert: &X509Certificate) -> bool {
+    matches!(cert.basic_constraints(), Ok(Some(constraints)) if constraints.value.ca)
+}
+
+// Where every certificate a service presents is loaded from.
+#[derive(Clone)]
+struct CertSources {
+    default: Option<CertPaths>,
+    by_host: Vec<(String, CertPaths)>,
+}
+
+impl CertSources {
+    fn load(&self) -> anyhow::Result<CertSet> {
+        let default = self.default.as_ref().map(CertPaths::load).transpose()?;
+
+        let by_host = self
+            .by_host
+            .iter()
+            .map(|(hostname, paths)| Ok((hostname.clone(), paths.load()?)))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(CertSet { default, by_host })
+    }
+}
+
+struct CertSet {
+    default: Option<Arc<CertifiedKey>>,
+    by_host: HashMap<String, Arc<CertifiedKey>>,
+}
+
+// Picks a certificate by SNI hostname out of whichever set was loaded most recently.
+struct ReloadableCert {
+    current: RwLock<CertSet>,
+}
+
+impl ResolvesServerCert for ReloadableCert {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let current = self.current.read().unwrap();
+
+        client_hello
+            .server_name()
+            .and_then(|hostname| current.by_host.get(&hostname.to_ascii_lowercase()))
+            .or(current.default.as_ref())
+            .cloned()
+    }
+}
+
+/// Reloads the certificates and keys a running service presents from disk.
+///
+/// Connections established after a reload are served the new certificates, while connections that
+/// are already open keep their session.
+#[derive(Clone)]
+pub struct CertReloader {
+    resolver: Arc<ReloadableCert>,
+    sources: CertSources,
+}
+
+impl CertReloader {
+    fn new(sources: CertSources) -> anyhow::Result<Self> {
+        let current = sources.load()?;
+
+        Ok(Self {
+            resolver: Arc::new(ReloadableCert {
+                current: RwLock::new(current),
+            }),
+            sources,
+        })
+    }
+
+    /// Load every certificate and key again and start serving them.
+    ///
+    /// All files are loaded and validated before anything is swapped, so on error the previous
+    /// certificates keep being served.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let current = self.sources.load()?;
+        *self.resolver.current.write().unwrap() = current;
+
+        tracing::info!("reloaded TLS certificates");
+
+        Ok(())
+    }
+}
+
+// Reload the certificate every time the process receives SIGHUP.
+#[cfg(unix)]
+pub(super) fn reload_on_sighup(reloader: CertReloader) -> io::Result<tokio::task::JoinHandle<()>> {
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    Ok(tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            if let Err(err) = reloader.reload() {
+                tracing::error!(error = %err, "failed to reload TLS certificate");
+            }
+        }
+    }))
+}
+
+// Why a TLS handshake failed, when rustls rejected it rather than the connection being closed or
+// reset by the peer.
+pub(super) fn handshake_failure(err: &io::Error) -> Option<String> {
+    let err = err.get_ref()?.downcast_ref::<rustls::Error>()?;
+
+    let kind = match err {
+        rustls::Error::NoCertificatesPresented => "no client certificate".into(),
+        rustls::Error::InvalidCertificateData(reason) if reason.contains("UnknownIssuer") => {
+            "unknown CA".into()
+        }
+        rustls::Error::InvalidCertificateData(reason) if reason.contains("CertExpired") => {
+            "expired certificate".into()
+        }
+        rustls::Error::InvalidCertificateData(_)
+        | rustls::Error::InvalidCertificateEncoding
+        | rustls::Error::InvalidCertificateSignature
+        | rustls::Error::InvalidCertificateSignatureType => "bad certificate".into(),
+        rustls::Error::PeerIncompatibleError(_) => "incompatible protocol version or cipher".into(),
+        rustls::Error::NoApplicationProtocol => "no common application protocol".into(),
+        rustls::Error::AlertReceived(alert) => format!("alert from client: {alert:?}"),
+        rustls::Error::CorruptMessage
+        | rustls::Error::CorruptMessagePayload(_)
+        | rustls::Error::InappropriateMessage { .. }
+        | rustls::Error::InappropriateHandshakeMessage { .. } => "not a TLS handshake".into(),
+        _ => "other".into(),
+    };
+
+    Some(kind)
+}
+
+// Only clients presenting a certificate signed by one of the CAs in `path` are accepted.
+fn client_cert_verifier(path: &Path) -> anyhow::Result<Arc<dyn ClientCertVerifier>> {
+    let mut roots = rustls::RootCertStore::empty();
+
+    for cert in load_certs(path)? {
+        roots.add(&cert).map_err(|err| {
+            anyhow::anyhow!("invalid client CA certificate in {}: {err}", path.display())
+        })?;
+    }
+
+    Ok(AllowAnyAuthenticatedClient::new(roots))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_pkcs12_bundles_by_extension() {
+        assert!(is_pkcs12(Path::new("certs/server.p12")));
+        assert!(is_pkcs12(Path::new("certs/server.PFX")));
+        assert!(!is_pkcs12(Path::new("certs/server.pem")));
+        assert!(!is_pkcs12(Path::new("certs/p12")));
+    }
+}
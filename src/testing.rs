@@ -0,0 +1,222 @@
+// Helpers shared by the unit tests.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+use rustserve::{Filter, IdParam, NotFound, RequestFilterOutcome, ResponseFilterOutcome};
+use tokio_rustls::rustls;
+
+use crate::mtls::Mtls;
+
+// An address to bind test listeners to, letting the OS pick the port.
+pub(crate) fn localhost() -> SocketAddr {
+    "127.0.0.1:0".parse().unwrap()
+}
+
+// A directory under the system temp dir, removed again on drop.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        let path =
+            std::env::temp_dir().join(format!("rustserve-platform-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// A CA issuing the certificates of test servers and clients.
+pub(crate) struct TestCa {
+    cert: Certificate,
+    pem: String,
+}
+
+impl TestCa {
+    pub(crate) fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::new());
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "test ca");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+
+        let cert = Certificate::from_params(params).unwrap();
+        let pem = cert.serialize_pem().unwrap();
+
+        Self { cert, pem }
+    }
+
+    // Issue a certificate for `names`, the first one doubling as its common name, written to
+    // `end.cert` and `end.key` in `dir` the way the runtime expects a certificate directory.
+    pub(crate) fn issue(&self, dir: &Path, names: &[&str]) {
+        let mut params = CertificateParams::new(
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>(),
+        );
+        params.distinguished_name.push(DnType::CommonName, names[0]);
+
+        let cert = Certificate::from_params(params).unwrap();
+
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(
+            dir.join("end.cert"),
+            cert.serialize_pem_with_signer(&self.cert).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("end.key"), cert.serialize_private_key_pem()).unwrap();
+    }
+
+    // A TLS server config presenting a certificate for `names` issued by the CA.
+    pub(crate) fn server_config(&self, names: &[&str]) -> rustls::ServerConfig {
+        let cert = Certificate::from_params(CertificateParams::new(
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>(),
+        ))
+        .unwrap();
+        let chain = vec![rustls::Certificate(
+            cert.serialize_der_with_signer(&self.cert).unwrap(),
+        )];
+        let key = rustls::PrivateKey(cert.serialize_private_key_der());
+
+        rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .unwrap()
+    }
+
+    // The CA certificate, PEM encoded.
+    pub(crate) fn pem(&self) -> &str {
+        &self.pem
+    }
+
+    // A client for the test server at `addr` presenting a certificate for `host`.
+    pub(crate) fn client(&self, addr: SocketAddr, host: &str) -> Mtls {
+        Mtls::from_pem(addr.to_string(), self.pem.as_bytes(), host).unwrap()
+    }
+}
+
+// Log lines written while held, by tasks on the current thread.
+pub(crate) struct CapturedLogs {
+    logs: Arc<Mutex<Vec<u8>>>,
+    _guard: tracing::subscriber::DefaultGuard,
+}
+
+impl CapturedLogs {
+    pub(crate) fn contents(&self) -> String {
+        String::from_utf8(self.logs.lock().unwrap().clone()).unwrap()
+    }
+}
+
+struct LogWriter(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Capture the `DEBUG` and more severe log lines of the current thread.
+pub(crate) fn capture_logs() -> CapturedLogs {
+    let logs = Arc::new(Mutex::new(Vec::new()));
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || LogWriter(writer.clone()))
+        .finish();
+
+    CapturedLogs {
+        logs,
+        _guard: tracing::subscriber::set_default(subscriber),
+    }
+}
+
+// A `GET` request for `path` as sent to a test server.
+pub(crate) fn get(path: &str) -> hyper::Request<Empty<Bytes>> {
+    hyper::Request::get(path)
+        .header(http::header::HOST, "localhost")
+        .body(Empty::new())
+        .unwrap()
+}
+
+// Send `req` to the plaintext test server at `addr` over a new HTTP/1.1 connection and read the
+// whole response.
+pub(crate) async fn send<B>(addr: SocketAddr, req: hyper::Request<B>) -> http::Response<Vec<u8>>
+where
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut request_sender, connection) =
+        hyper::client::conn::http1::handshake(stream).await.unwrap();
+    tokio::spawn(connection);
+
+    let res = request_sender.send_request(req).await.unwrap();
+    let (parts, body) = res.into_parts();
+    let body = body.collect().await.unwrap().to_bytes().to_vec();
+
+    http::Response::from_parts(parts, body)
+}
+
+// Run `req` through the request side of `filter`, returning the request and params it passed on
+// or the response it answered the request with.
+pub(crate) async fn filter_request<'a, F: Filter + 'static>(
+    filter: &Arc<F>,
+    req: http::Request<&'a [u8]>,
+    params: HashMap<String, String>,
+) -> Result<(http::Request<&'a [u8]>, HashMap<String, String>), http::Response<Vec<u8>>> {
+    match filter.clone().filter_request(req, params).await.unwrap() {
+        RequestFilterOutcome::Pass(req, params) => Ok((req, params)),
+        RequestFilterOutcome::Fail(res) => Err(res),
+    }
+}
+
+// Run `res` through the response side of `filter`.
+pub(crate) async fn filter_response<F: Filter + 'static>(
+    filter: &Arc<F>,
+    res: http::Response<Vec<u8>>,
+) -> http::Response<Vec<u8>> {
+    match filter.clone().filter_response(res).await.unwrap() {
+        ResponseFilterOutcome::Pass(res) | ResponseFilterOutcome::Fail(res) => res,
+    }
+}
+
+// A resource identified by the `user_id` route parameter.
+pub(crate) struct User;
+
+impl IdParam for User {
+    fn id() -> String {
+        "user_id".into()
+    }
+}
+
+impl NotFound for User {
+    fn not_found() -> anyhow::Result<http::Response<Vec<u8>>> {
+        Ok(crate::platform_error(crate::NotFoundError::new("/users")))
+    }
+}